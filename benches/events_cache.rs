@@ -0,0 +1,136 @@
+//! Events cache under several sources delivering the same events, first as fast
+//! as possible, then paced at 5k events/sec per source like pump.fun at peak.
+//! Run with `cargo bench --bench events_cache`.
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use transactions_listener::{
+    events_cache::EventsCache,
+    types::{Event, EventType, SchemaVersion, SwapPlatform, TransactionFees},
+};
+
+const SOURCES: usize = 4;
+const CAPACITY: usize = 100_000;
+const TTL: Duration = Duration::from_secs(120);
+const EVENTS: usize = 500_000;
+const PACED_RATE: usize = 5_000;
+const PACED_DURATION: Duration = Duration::from_secs(5);
+
+fn get_event(index: usize) -> Event {
+    let mut signature = [0; 64];
+    signature[..8].copy_from_slice(&(index as u64 / 4).to_le_bytes());
+
+    Event {
+        schema_version: SchemaVersion,
+        signature: Signature::from(signature),
+        event_type: EventType::PoolCreation {
+            mint: Pubkey::new_unique(),
+            platform: SwapPlatform::PumpFun,
+        },
+        user: Pubkey::new_unique(),
+        slot: index as u64,
+        block_time: None,
+        transaction_index: None,
+        instruction_index: (index % 4) as u32,
+        inner_instruction_index: None,
+        received_at: 0,
+        transaction_fees: TransactionFees::default(),
+        watchlist_entry: None,
+    }
+}
+
+/// Every source inserts every event, returns how many were emitted and the
+/// latency of each insert
+fn run(
+    cache: Arc<EventsCache>,
+    events: Arc<Vec<Event>>,
+    rate: Option<usize>,
+) -> (usize, Vec<Duration>) {
+    let emitted = Arc::new(AtomicUsize::new(0));
+
+    let threads = (0..SOURCES)
+        .map(|_| {
+            let cache = cache.clone();
+            let events = events.clone();
+            let emitted = emitted.clone();
+
+            thread::spawn(move || {
+                let mut latencies = Vec::with_capacity(events.len());
+                let start = Instant::now();
+                for (index, event) in events.iter().enumerate() {
+                    if let Some(rate) = rate {
+                        let deadline = start + Duration::from_secs(1) * index as u32 / rate as u32;
+                        if let Some(wait) = deadline.checked_duration_since(Instant::now()) {
+                            thread::sleep(wait);
+                        }
+                    }
+
+                    let inserted_at = Instant::now();
+                    if cache.insert(event) {
+                        emitted.fetch_add(1, Ordering::Relaxed);
+                    }
+                    latencies.push(inserted_at.elapsed());
+                }
+
+                latencies
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut latencies = threads
+        .into_iter()
+        .flat_map(|thread| thread.join().unwrap())
+        .collect::<Vec<_>>();
+    latencies.sort();
+
+    (emitted.load(Ordering::Relaxed), latencies)
+}
+
+fn percentile(latencies: &[Duration], percentile: usize) -> Duration {
+    latencies[(latencies.len() - 1) * percentile / 100]
+}
+
+fn main() {
+    let events = Arc::new((0..EVENTS).map(get_event).collect::<Vec<_>>());
+    let cache = Arc::new(EventsCache::new(CAPACITY, Some(TTL)));
+    let start = Instant::now();
+    let (emitted, latencies) = run(cache.clone(), events, None);
+    let elapsed = start.elapsed();
+    println!(
+        "unpaced: {} inserts from {} sources in {:?}, {:.0} inserts/sec, {} emitted, p50 {:?}, p99 {:?}",
+        latencies.len(),
+        SOURCES,
+        elapsed,
+        latencies.len() as f64 / elapsed.as_secs_f64(),
+        emitted,
+        percentile(&latencies, 50),
+        percentile(&latencies, 99),
+    );
+    println!("{:?}", cache.metrics());
+
+    let paced_events = PACED_RATE * PACED_DURATION.as_secs() as usize;
+    let events = Arc::new((0..paced_events).map(get_event).collect::<Vec<_>>());
+    let cache = Arc::new(EventsCache::new(CAPACITY, Some(TTL)));
+    let start = Instant::now();
+    let (emitted, latencies) = run(cache.clone(), events, Some(PACED_RATE));
+    let elapsed = start.elapsed();
+    println!(
+        "paced: {} events/sec from {} sources for {:?}, {} emitted, p50 {:?}, p99 {:?}, max {:?}",
+        PACED_RATE,
+        SOURCES,
+        elapsed,
+        emitted,
+        percentile(&latencies, 50),
+        percentile(&latencies, 99),
+        latencies[latencies.len() - 1],
+    );
+    println!("{:?}", cache.metrics());
+}
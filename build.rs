@@ -0,0 +1,12 @@
+fn main() {
+    println!("cargo:rerun-if-changed=proto");
+
+    // Uses a vendored protoc so the feature builds without one installed. The
+    // swap is boxed like in `types::EventType`, it's much larger than the rest
+    #[cfg(feature = "proto")]
+    prost_build::Config::new()
+        .protoc_executable(protobuf_src::protoc())
+        .boxed(".transactions_listener.EventType.event_type.swap")
+        .compile_protos(&["proto/events.proto"], &["proto"])
+        .expect("failed to compile proto/events.proto");
+}
@@ -1,17 +1,21 @@
-use crate::utils::get_now_timestamp;
+use crate::utils::get_now_timestamp_millis;
 
 use super::*;
 
-use carbon_core::error::Error;
-use carbon_spl_associated_token_account_decoder::instructions::SplAssociatedTokenAccountInstruction;
+use carbon_core::{deserialize::ArrangeAccounts, error::Error};
+use carbon_spl_associated_token_account_decoder::instructions::{
+    SplAssociatedTokenAccountInstruction, create::Create,
+};
+use solana_sdk::{instruction::AccountMeta, pubkey::Pubkey};
 
-pub struct PumpFunMonitor {
-    pub sender: Sender<Event>,
-    pub parsed_events: Arc<RwLock<HashSet<Event>>>,
+pub struct AssociatedTokenAccountMonitor {
+    pub sender: EventSender,
+    pub events_cache: Arc<EventsCache>,
+    pub instruction_positions: InstructionPositions,
 }
 
 #[tonic::async_trait]
-impl Processor for PumpFunMonitor {
+impl Processor for AssociatedTokenAccountMonitor {
     type InputType = InstructionProcessorInputType<SplAssociatedTokenAccountInstruction>;
 
     async fn process(
@@ -19,8 +23,12 @@ impl Processor for PumpFunMonitor {
         (metadata, instruction, _nested_instructions): Self::InputType,
         _metrics: Arc<MetricsCollection>,
     ) -> CarbonResult<()> {
-        let signature = metadata.transaction_metadata.signature;
-        let now_timestamp = get_now_timestamp();
+        let received_at = get_now_timestamp_millis();
+
+        let status = &metadata.transaction_metadata.meta.status;
+        if status.is_err() {
+            return Ok(());
+        }
 
         let idempotent = match instruction.data {
             SplAssociatedTokenAccountInstruction::Create(_) => false,
@@ -30,26 +38,122 @@ impl Processor for PumpFunMonitor {
             }
         };
 
-        let event = Event {
-            signature,
-            event_type: EventType::AssociatedAccountCreation {
-                mint: instruction.accounts[1].pubkey,
-                account: instruction.accounts[0].pubkey,
-                idempotent,
-            },
-            user: instruction.accounts[2].pubkey,
-            timestamp: now_timestamp,
-        };
+        let (event_type, wallet) =
+            match get_associated_account_creation(&instruction.accounts, idempotent) {
+                Some(associated_account_creation) => associated_account_creation,
+                None => {
+                    return Ok(());
+                }
+            };
+
+        let position = self.instruction_positions.resolve(
+            &metadata,
+            &instruction.program_id,
+            &instruction.accounts,
+        );
+        let event = Event::new(
+            &metadata.transaction_metadata,
+            position,
+            event_type,
+            wallet,
+            received_at,
+        );
 
-        let parsed_events_cache = self.parsed_events.read().await;
-        if parsed_events_cache.contains(&event) {
+        if !self.events_cache.insert(&event) {
             return Ok(());
         }
-        self.parsed_events.write().await.insert(event.clone());
 
-        self.sender
-            .send(event)
-            .await
-            .map_err(|error| Error::Custom(format!("send pumpfun event to receiver: {}", error)))
+        self.sender.send(event).await.map_err(|error| {
+            Error::Custom(format!(
+                "send associated token account event to receiver: {}",
+                error
+            ))
+        })
+    }
+}
+
+/// Event of the creation and the wallet owning the account. Create and
+/// CreateIdempotent share the same account layout, an instruction missing
+/// accounts creates nothing
+fn get_associated_account_creation(
+    accounts: &[AccountMeta],
+    idempotent: bool,
+) -> Option<(EventType, Pubkey)> {
+    let accounts = Create::arrange_accounts(accounts)?;
+
+    Some((
+        EventType::AssociatedAccountCreation {
+            mint: accounts.mint,
+            account: accounts.associated_account,
+            idempotent,
+        },
+        accounts.wallet,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{TransactionsListener, decode::parse_transaction, mock_rpc::load_fixture};
+
+    const WALLET: Pubkey = solana_sdk::pubkey!("AKnL4NNf3DGWZJS6cPknBuEGnVsV4A4m5tgebLHaRSZ9");
+    const ACCOUNT: Pubkey = solana_sdk::pubkey!("DLCWYY3FN54MWjEEftyH6hAb3S7PEgek2YNoTHYbGjzC");
+    const MINT: Pubkey = solana_sdk::pubkey!("8qbHbw2BbbTHBW1sbeqakYXVKRQM8Ne7pLK7m6CVfeR");
+
+    fn get_accounts() -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(WALLET, true),
+            AccountMeta::new(ACCOUNT, false),
+            AccountMeta::new_readonly(WALLET, false),
+            AccountMeta::new_readonly(MINT, false),
+            AccountMeta::new_readonly(Pubkey::new_unique(), false),
+            AccountMeta::new_readonly(Pubkey::new_unique(), false),
+        ]
+    }
+
+    #[test]
+    fn test_get_associated_account_creation() {
+        assert_eq!(
+            get_associated_account_creation(&get_accounts(), false),
+            Some((
+                EventType::AssociatedAccountCreation {
+                    mint: MINT,
+                    account: ACCOUNT,
+                    idempotent: false,
+                },
+                WALLET
+            ))
+        );
+
+        // Short of the token program
+        let accounts = get_accounts();
+        assert_eq!(get_associated_account_creation(&accounts[..5], true), None);
+        assert_eq!(get_associated_account_creation(&[], true), None);
+    }
+
+    #[tokio::test]
+    async fn test_decodes_create_idempotent() {
+        let update =
+            parse_transaction(&load_fixture("create_associated_account").to_string()).unwrap();
+        let (mut transactions_listener, _receiver) =
+            TransactionsListener::new(16, 255, HashMap::new()).unwrap();
+        transactions_listener.config.associated_accounts_monitor = true;
+
+        let decoded = transactions_listener.decode(update).await.unwrap();
+        assert!(decoded.errors.is_empty(), "{:?}", decoded.errors);
+        assert_eq!(decoded.events.len(), 1);
+
+        let event = &decoded.events[0];
+        assert_eq!(event.user, WALLET);
+        assert_eq!(
+            event.event_type,
+            EventType::AssociatedAccountCreation {
+                mint: MINT,
+                account: ACCOUNT,
+                idempotent: true,
+            }
+        );
     }
 }
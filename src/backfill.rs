@@ -0,0 +1,428 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use carbon_core::datasource::{Datasource, Update, UpdateType};
+use solana_client::{
+    nonblocking::rpc_client::RpcClient, rpc_client::GetConfirmedSignaturesForAddress2Config,
+};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature};
+use tokio::{
+    sync::{mpsc::UnboundedSender, oneshot, watch},
+    time::MissedTickBehavior,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    constants::{BACKFILL_PROCESS_TIMEOUT, BACKFILL_SIGNATURES_PAGE_SIZE},
+    monitor_errors::MonitorErrors,
+    prelude::*,
+    rpc_source::get_transaction_update,
+};
+
+/// End of the history to backfill
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackfillBound {
+    /// Included
+    Slot(u64),
+    /// Excluded, as with `getSignaturesForAddress`
+    Signature(Signature),
+}
+
+/// History of an address to run through the monitors, see
+/// `TransactionsListener::backfill`
+#[derive(Debug, Clone)]
+pub struct Backfill {
+    pub rpc_url: String,
+    /// Program, pool or mint
+    pub address: Pubkey,
+    /// Oldest end, the first transaction of the address when `None`
+    pub from: Option<BackfillBound>,
+    /// Newest end, the latest transaction when `None`
+    pub to: Option<BackfillBound>,
+    /// Of every RPC call, signature pages and transactions alike
+    pub requests_per_second: u32,
+    /// File keeping the last signature the monitors are done with. The
+    /// backfill resumes right after it when it exists
+    pub checkpoint_path: Option<PathBuf>,
+}
+
+/// Transaction the monitors are done with, and what they failed with on it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Processed {
+    pub signature: Signature,
+    pub errors: Vec<String>,
+}
+
+/// Pages through the signatures of the address from the newest, and sends each
+/// transaction once the monitors are done with the previous one so that the
+/// checkpoint never gets ahead of the events. Monitor errors are logged and
+/// the backfill goes on, as the live pipeline does. Fails on the first
+/// transaction the monitors don't get to.
+pub struct BackfillSource {
+    pub backfill: Backfill,
+    pub commitment: CommitmentConfig,
+    pub failed_transactions: bool,
+    /// Last transaction the monitors are done with, set by `BackfillMonitor`
+    pub processed: watch::Receiver<Option<Processed>>,
+    /// How the backfill ended, the pipeline only logs datasource errors
+    pub result_sender: Mutex<Option<oneshot::Sender<CarbonResult<()>>>>,
+}
+
+#[tonic::async_trait]
+impl Datasource for BackfillSource {
+    async fn consume(
+        &self,
+        sender: &UnboundedSender<Update>,
+        cancellation_token: CancellationToken,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let result = tokio::select! {
+            _ = cancellation_token.cancelled() => {
+                Err(Error::Custom("backfill cancelled".to_string()))
+            }
+            result = self.send_transactions(sender) => result,
+        };
+
+        if let Some(result_sender) = self.result_sender.lock().unwrap().take() {
+            let _ = result_sender.send(result);
+        }
+
+        Ok(())
+    }
+
+    fn update_types(&self) -> Vec<UpdateType> {
+        vec![UpdateType::Transaction]
+    }
+}
+
+impl BackfillSource {
+    async fn send_transactions(&self, sender: &UnboundedSender<Update>) -> CarbonResult<()> {
+        let rpc_client =
+            RpcClient::new_with_commitment(self.backfill.rpc_url.clone(), self.commitment);
+        let mut rate_limit = tokio::time::interval(
+            Duration::from_secs(1) / self.backfill.requests_per_second.max(1),
+        );
+        rate_limit.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut processed = self.processed.clone();
+
+        let checkpoint = match &self.backfill.checkpoint_path {
+            Some(checkpoint_path) => read_checkpoint(checkpoint_path)?,
+            None => None,
+        };
+        let mut before = match (checkpoint, self.backfill.to) {
+            (Some(signature), _) | (None, Some(BackfillBound::Signature(signature))) => {
+                Some(signature)
+            }
+            _ => None,
+        };
+        let until = match self.backfill.from {
+            Some(BackfillBound::Signature(signature)) => Some(signature),
+            _ => None,
+        };
+
+        loop {
+            rate_limit.tick().await;
+            let statuses = rpc_client
+                .get_signatures_for_address_with_config(
+                    &self.backfill.address,
+                    GetConfirmedSignaturesForAddress2Config {
+                        before,
+                        until,
+                        limit: Some(BACKFILL_SIGNATURES_PAGE_SIZE),
+                        commitment: Some(self.commitment),
+                    },
+                )
+                .await
+                .map_err(|error| Error::Custom(format!("get signatures: {}", error)))?;
+
+            if statuses.is_empty() {
+                return Ok(());
+            }
+
+            for status in statuses {
+                let signature = Signature::from_str(&status.signature).map_err(|error| {
+                    Error::Custom(format!("parse signature {}: {}", status.signature, error))
+                })?;
+                before = Some(signature);
+
+                if let Some(BackfillBound::Slot(slot)) = self.backfill.from
+                    && status.slot < slot
+                {
+                    return Ok(());
+                }
+                if let Some(BackfillBound::Slot(slot)) = self.backfill.to
+                    && status.slot > slot
+                {
+                    continue;
+                }
+                if status.err.is_some() && !self.failed_transactions {
+                    continue;
+                }
+
+                rate_limit.tick().await;
+                let update = get_transaction_update(&rpc_client, signature, self.commitment)
+                    .await
+                    .map_err(|error| {
+                        Error::Custom(format!("fetch transaction {}: {:?}", signature, error))
+                    })?;
+                sender
+                    .send(update)
+                    .map_err(|error| Error::Custom(format!("send update: {}", error)))?;
+
+                let errors = match tokio::time::timeout(
+                    BACKFILL_PROCESS_TIMEOUT,
+                    processed.wait_for(|processed| {
+                        processed
+                            .as_ref()
+                            .is_some_and(|processed| processed.signature == signature)
+                    }),
+                )
+                .await
+                {
+                    Ok(Ok(processed)) => match processed.as_ref() {
+                        Some(processed) => processed.errors.clone(),
+                        None => Vec::new(),
+                    },
+                    Ok(Err(_)) => {
+                        return Err(Error::Custom("backfill pipeline stopped".to_string()));
+                    }
+                    // Carbon drops the transactions it can't parse
+                    Err(_) => {
+                        return Err(Error::Custom(format!(
+                            "transaction {} not processed",
+                            signature
+                        )));
+                    }
+                };
+
+                if !errors.is_empty() {
+                    tracing::error!(
+                        "monitors failed on backfilled transaction {}: {}",
+                        signature,
+                        errors.join(", ")
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Registered after every monitor, so once their events are sent, reports the
+/// transactions they're done with and checkpoints them, failed on or not
+pub struct BackfillMonitor {
+    pub checkpoint_path: Option<PathBuf>,
+    pub processed: watch::Sender<Option<Processed>>,
+    /// Those of the pipeline the monitors were registered in
+    pub monitor_errors: MonitorErrors,
+}
+
+#[tonic::async_trait]
+impl Processor for BackfillMonitor {
+    type InputType = InstructionProcessorInputType<()>;
+
+    async fn process(
+        &mut self,
+        (metadata, _instruction, _nested_instructions): Self::InputType,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let signature = metadata.transaction_metadata.signature;
+
+        // Runs on every instruction of the transaction
+        if let Some(processed) = self.processed.borrow().as_ref()
+            && processed.signature == signature
+        {
+            return Ok(());
+        }
+
+        let errors = self
+            .monitor_errors
+            .take(&signature)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        if let Some(checkpoint_path) = &self.checkpoint_path {
+            write_checkpoint(checkpoint_path, &signature)?;
+        }
+        self.processed
+            .send_replace(Some(Processed { signature, errors }));
+
+        Ok(())
+    }
+}
+
+fn read_checkpoint(checkpoint_path: &Path) -> CarbonResult<Option<Signature>> {
+    if !checkpoint_path.exists() {
+        return Ok(None);
+    }
+
+    let checkpoint = fs::read_to_string(checkpoint_path)
+        .map_err(|error| Error::Custom(format!("read backfill checkpoint: {}", error)))?;
+    let signature = Signature::from_str(checkpoint.trim())
+        .map_err(|error| Error::Custom(format!("parse backfill checkpoint: {}", error)))?;
+
+    Ok(Some(signature))
+}
+
+/// Written aside then renamed, so that a crash never leaves half a checkpoint
+fn write_checkpoint(checkpoint_path: &Path, signature: &Signature) -> CarbonResult<()> {
+    let temporary_path = checkpoint_path.with_extension("tmp");
+    fs::write(&temporary_path, format!("{}\n", signature))
+        .and_then(|_| fs::rename(&temporary_path, checkpoint_path))
+        .map_err(|error| Error::Custom(format!("write backfill checkpoint: {}", error)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::{
+        TransactionsListener,
+        constants::programs::ASSOCIATED_TOKEN_ACCOUNT,
+        mock_rpc::{MockRpc, get_fixture_signature, get_transaction_handler, load_fixture},
+        types::Event,
+    };
+
+    /// Newest first, the pump.fun buy fails its monitor
+    const FIXTURES: [&str; 3] = [
+        "create_associated_account_2",
+        "pumpfun_buy_without_trade_event",
+        "create_associated_account",
+    ];
+
+    async fn run_backfill(
+        transactions_listener: &TransactionsListener,
+        mock_rpc: &MockRpc,
+        checkpoint_path: &Path,
+    ) -> (Vec<Event>, CarbonResult<()>) {
+        let (mut receiver, handle) = transactions_listener
+            .backfill(
+                16,
+                Backfill {
+                    rpc_url: mock_rpc.url.clone(),
+                    address: ASSOCIATED_TOKEN_ACCOUNT,
+                    from: None,
+                    to: None,
+                    requests_per_second: 1_000,
+                    checkpoint_path: Some(checkpoint_path.to_path_buf()),
+                },
+            )
+            .unwrap();
+
+        // Far below the time a transaction is waited for
+        let result = tokio::time::timeout(Duration::from_secs(10), handle)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let mut events = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            events.push(event);
+        }
+
+        (events, result)
+    }
+
+    #[tokio::test]
+    async fn test_goes_past_failed_transaction_and_resumes() {
+        let fixtures = FIXTURES.map(load_fixture);
+        let signatures = fixtures.each_ref().map(get_fixture_signature);
+        let statuses = fixtures
+            .iter()
+            .zip(signatures)
+            .map(|(fixture, signature)| {
+                json!({
+                    "signature": signature.to_string(),
+                    "slot": fixture["slot"],
+                    "err": null,
+                    "memo": null,
+                    "blockTime": fixture["blockTime"],
+                    "confirmationStatus": "finalized",
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let get_transaction = get_transaction_handler(fixtures.to_vec());
+        let mock_rpc = MockRpc::start(
+            Arc::new(move |method, params| match method {
+                // One page, from right after the `before` signature
+                "getSignaturesForAddress" => {
+                    let start = match params[1]["before"].as_str() {
+                        Some(before) => {
+                            match statuses
+                                .iter()
+                                .position(|status| status["signature"] == before)
+                            {
+                                Some(position) => position + 1,
+                                None => statuses.len(),
+                            }
+                        }
+                        None => 0,
+                    };
+
+                    Value::Array(statuses[start..].to_vec())
+                }
+                _ => get_transaction(method, params),
+            }),
+            Vec::new(),
+        )
+        .await;
+
+        let checkpoint_path = std::env::temp_dir().join(format!(
+            "transactions-listener-backfill-{}.checkpoint",
+            Signature::new_unique()
+        ));
+        let (mut transactions_listener, _receiver) =
+            TransactionsListener::new(16, 255, HashMap::new()).unwrap();
+        transactions_listener.config.associated_accounts_monitor = true;
+
+        // The pump.fun monitor fails on the buy, the other monitors' events of
+        // it are still sent
+        let (events, result) =
+            run_backfill(&transactions_listener, &mock_rpc, &checkpoint_path).await;
+        result.unwrap();
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| event.signature)
+                .collect::<Vec<_>>(),
+            signatures
+        );
+        assert_eq!(
+            read_checkpoint(&checkpoint_path).unwrap(),
+            Some(signatures[2])
+        );
+
+        // Resumes after the oldest transaction, nothing is sent again
+        let (events, result) =
+            run_backfill(&transactions_listener, &mock_rpc, &checkpoint_path).await;
+        result.unwrap();
+        assert!(events.is_empty(), "{:?}", events);
+
+        // From a checkpoint left on the failed transaction
+        write_checkpoint(&checkpoint_path, &signatures[1]).unwrap();
+        let (events, result) =
+            run_backfill(&transactions_listener, &mock_rpc, &checkpoint_path).await;
+        result.unwrap();
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| event.signature)
+                .collect::<Vec<_>>(),
+            signatures[2..]
+        );
+        assert_eq!(
+            read_checkpoint(&checkpoint_path).unwrap(),
+            Some(signatures[2])
+        );
+
+        fs::remove_file(checkpoint_path).unwrap();
+    }
+}
@@ -0,0 +1,347 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use solana_sdk::pubkey::Pubkey;
+use strum::IntoEnumIterator;
+use yellowstone_grpc_proto::geyser::{CommitmentLevel, SubscribeRequestFilterTransactions};
+
+use crate::{
+    constants::{
+        RAYDIUM_AMM_V4_PROTOCOL_FEE_RATE, RAYDIUM_AMM_V4_TRADE_FEE_RATE,
+        RAYDIUM_CLMM_FUND_FEE_RATE, RAYDIUM_CLMM_PROTOCOL_FEE_RATE, RAYDIUM_CLMM_TRADE_FEE_RATES,
+        RAYDIUM_CPMM_FUND_FEE_RATE, RAYDIUM_CPMM_PROTOCOL_FEE_RATE, RAYDIUM_CPMM_TRADE_FEE_RATES,
+        programs::ASSOCIATED_TOKEN_ACCOUNT,
+    },
+    fees::FeeRates,
+    types::SwapPlatform,
+    watchlist::Watchlist,
+};
+
+/// What the listener subscribes to and emits
+#[derive(Debug, Clone)]
+pub struct ListenerConfig {
+    /// Platforms whose programs are subscribed to and whose monitors are
+    /// registered. All of them by default
+    pub platforms: HashSet<SwapPlatform>,
+    /// Emit `AssociatedAccountCreation` events. Off by default since it
+    /// subscribes to every transaction that creates an associated token account
+    pub associated_accounts_monitor: bool,
+    /// Emit `FailedSwapAttempt` events. Off by default, failed transactions
+    /// aren't even subscribed to then
+    pub failed_swap_attempts: bool,
+    /// Commitment of the subscription, processed by default. Must stay
+    /// processed once confirmations are enabled
+    pub commitment: CommitmentLevel,
+    /// Time gRPC sources hold a transaction back waiting for the block meta of
+    /// its slot, which only comes once the block is complete, so that its
+    /// events get a block time. Zero by default: events come out right away,
+    /// and without a block time below confirmed commitment
+    pub block_time_wait: Duration,
+    /// Only transactions involving one of these wallets or of `mints`, when
+    /// either isn't empty. Shared with the running pipeline, see
+    /// `TransactionsListener::add_watched_wallet`
+    pub wallets: Watchlist,
+    /// Only transactions involving one of these mints or of `wallets`, when
+    /// either isn't empty
+    pub mints: HashSet<Pubkey>,
+    /// Never transactions involving one of these accounts
+    pub excluded_accounts: HashSet<Pubkey>,
+    /// RPC node to look up the accounts transactions don't carry, the bin step
+    /// of the Meteora DLMM pairs created before the listener started. Those
+    /// pairs have no bin price without it
+    pub rpc_url: Option<String>,
+    /// Fee rates of the Raydium AMM v4 pools, the program's defaults unless
+    /// changed
+    pub raydium_amm_v4_fee_rates: FeeRates,
+    /// Fee rates of the Raydium CPMM amm configs, by config account. Swaps
+    /// through other configs report unknown fees. The public configs by default
+    pub raydium_cpmm_amm_configs: HashMap<Pubkey, FeeRates>,
+    /// Same for the Raydium CLMM amm configs, whose accounts are distinct
+    pub raydium_clmm_amm_configs: HashMap<Pubkey, FeeRates>,
+}
+
+impl Default for ListenerConfig {
+    fn default() -> Self {
+        Self {
+            platforms: SwapPlatform::iter().collect(),
+            associated_accounts_monitor: false,
+            failed_swap_attempts: false,
+            commitment: CommitmentLevel::Processed,
+            block_time_wait: Duration::ZERO,
+            wallets: Watchlist::default(),
+            mints: HashSet::new(),
+            excluded_accounts: HashSet::new(),
+            rpc_url: None,
+            raydium_amm_v4_fee_rates: FeeRates {
+                trade_fee_rate: RAYDIUM_AMM_V4_TRADE_FEE_RATE,
+                protocol_fee_rate: RAYDIUM_AMM_V4_PROTOCOL_FEE_RATE,
+                fund_fee_rate: 0,
+            },
+            raydium_cpmm_amm_configs: get_amm_configs(
+                &RAYDIUM_CPMM_TRADE_FEE_RATES,
+                RAYDIUM_CPMM_PROTOCOL_FEE_RATE,
+                RAYDIUM_CPMM_FUND_FEE_RATE,
+            ),
+            raydium_clmm_amm_configs: get_amm_configs(
+                &RAYDIUM_CLMM_TRADE_FEE_RATES,
+                RAYDIUM_CLMM_PROTOCOL_FEE_RATE,
+                RAYDIUM_CLMM_FUND_FEE_RATE,
+            ),
+        }
+    }
+}
+
+fn get_amm_configs(
+    trade_fee_rates: &[(Pubkey, u64)],
+    protocol_fee_rate: u64,
+    fund_fee_rate: u64,
+) -> HashMap<Pubkey, FeeRates> {
+    trade_fee_rates
+        .iter()
+        .map(|(amm_config, trade_fee_rate)| {
+            (
+                *amm_config,
+                FeeRates {
+                    trade_fee_rate: *trade_fee_rate,
+                    protocol_fee_rate,
+                    fund_fee_rate,
+                },
+            )
+        })
+        .collect()
+}
+
+impl ListenerConfig {
+    /// Programs of the enabled platforms, plus the associated token account
+    /// program when its monitor is enabled
+    pub fn get_programs(&self) -> Vec<Pubkey> {
+        let mut programs = SwapPlatform::iter()
+            .filter(|platform| self.platforms.contains(platform))
+            .map(|platform| platform.program_id())
+            .collect::<Vec<_>>();
+        if self.associated_accounts_monitor {
+            programs.push(ASSOCIATED_TOKEN_ACCOUNT);
+        }
+
+        programs
+    }
+
+    /// Geyser delivers a transaction matching any of the filters, while a
+    /// filter matches transactions involving any of `account_include` and all
+    /// of `account_required`. So there's a filter per program, whatever the
+    /// number of wallets and mints, which are the included accounts when set,
+    /// the program being required then. Providers cap the number of filters,
+    /// not the accounts in them.
+    pub fn get_transaction_filters(&self) -> HashMap<String, SubscribeRequestFilterTransactions> {
+        let wallets = self.wallets.wallets();
+        let accounts = get_sorted_strings(wallets.iter().chain(&self.mints));
+        let account_exclude = get_sorted_strings(&self.excluded_accounts);
+
+        let mut filters = HashMap::new();
+        for program in self.get_programs() {
+            let program = program.to_string();
+
+            let (account_include, account_required) = if accounts.is_empty() {
+                (vec![program.clone()], vec![])
+            } else {
+                (accounts.clone(), vec![program.clone()])
+            };
+
+            filters.insert(
+                format!("subscribe_transactions_{}", program),
+                SubscribeRequestFilterTransactions {
+                    vote: Some(false),
+                    failed: if self.failed_swap_attempts {
+                        None
+                    } else {
+                        Some(false)
+                    },
+                    signature: None,
+                    account_include,
+                    account_exclude: account_exclude.clone(),
+                    account_required,
+                },
+            );
+        }
+
+        filters
+    }
+}
+
+/// Sorted so the same config always gives the same filters
+fn get_sorted_strings<'a>(pubkeys: impl IntoIterator<Item = &'a Pubkey>) -> Vec<String> {
+    let mut strings = pubkeys
+        .into_iter()
+        .map(|pubkey| pubkey.to_string())
+        .collect::<Vec<_>>();
+    strings.sort();
+
+    strings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{constants::programs::PUMPFUN, types::WatchlistEntry};
+
+    struct Case {
+        name: &'static str,
+        associated_accounts_monitor: bool,
+        wallets: Vec<Pubkey>,
+        mints: Vec<Pubkey>,
+        /// Program of the filter, `account_include` and `account_required`
+        expected: Vec<(Pubkey, Vec<Pubkey>, Vec<Pubkey>)>,
+    }
+
+    fn get_strings(pubkeys: &[Pubkey]) -> Vec<String> {
+        let mut strings = pubkeys.iter().map(ToString::to_string).collect::<Vec<_>>();
+        strings.sort();
+
+        strings
+    }
+
+    #[test]
+    fn test_get_transaction_filters() {
+        let (wallet_a, wallet_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (mint_a, mint_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let ata = ASSOCIATED_TOKEN_ACCOUNT;
+
+        let cases = vec![
+            Case {
+                name: "program",
+                associated_accounts_monitor: false,
+                wallets: vec![],
+                mints: vec![],
+                expected: vec![(PUMPFUN, vec![PUMPFUN], vec![])],
+            },
+            Case {
+                name: "programs",
+                associated_accounts_monitor: true,
+                wallets: vec![],
+                mints: vec![],
+                expected: vec![(PUMPFUN, vec![PUMPFUN], vec![]), (ata, vec![ata], vec![])],
+            },
+            Case {
+                name: "program x mints",
+                associated_accounts_monitor: false,
+                wallets: vec![],
+                mints: vec![mint_a, mint_b],
+                expected: vec![(PUMPFUN, vec![mint_a, mint_b], vec![PUMPFUN])],
+            },
+            Case {
+                name: "programs x mints",
+                associated_accounts_monitor: true,
+                wallets: vec![],
+                mints: vec![mint_a, mint_b],
+                expected: vec![
+                    (PUMPFUN, vec![mint_a, mint_b], vec![PUMPFUN]),
+                    (ata, vec![mint_a, mint_b], vec![ata]),
+                ],
+            },
+            Case {
+                name: "wallets",
+                associated_accounts_monitor: false,
+                wallets: vec![wallet_a, wallet_b],
+                mints: vec![],
+                expected: vec![(PUMPFUN, vec![wallet_a, wallet_b], vec![PUMPFUN])],
+            },
+            Case {
+                name: "wallets x programs x mints",
+                associated_accounts_monitor: true,
+                wallets: vec![wallet_a, wallet_b],
+                mints: vec![mint_a, mint_b],
+                expected: vec![
+                    (
+                        PUMPFUN,
+                        vec![wallet_a, wallet_b, mint_a, mint_b],
+                        vec![PUMPFUN],
+                    ),
+                    (ata, vec![wallet_a, wallet_b, mint_a, mint_b], vec![ata]),
+                ],
+            },
+        ];
+
+        for case in cases {
+            let config = ListenerConfig {
+                platforms: HashSet::from([SwapPlatform::PumpFun]),
+                associated_accounts_monitor: case.associated_accounts_monitor,
+                mints: case.mints.iter().copied().collect(),
+                ..Default::default()
+            };
+            for wallet in &case.wallets {
+                config.wallets.add(WatchlistEntry {
+                    wallet: *wallet,
+                    label: None,
+                });
+            }
+
+            let filters = config.get_transaction_filters();
+            assert_eq!(filters.len(), case.expected.len(), "{}", case.name);
+            for (program, account_include, account_required) in case.expected {
+                let filter = &filters[&format!("subscribe_transactions_{}", program)];
+                assert_eq!(
+                    filter.account_include,
+                    get_strings(&account_include),
+                    "{}: {}",
+                    case.name,
+                    program
+                );
+                assert_eq!(
+                    filter.account_required,
+                    get_strings(&account_required),
+                    "{}: {}",
+                    case.name,
+                    program
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_transaction_filters_scale() {
+        // A filter per program, however many mints and wallets
+        let config = ListenerConfig {
+            mints: (0..1_000).map(|_| Pubkey::new_unique()).collect(),
+            ..Default::default()
+        };
+        for _ in 0..1_000 {
+            config.wallets.add(WatchlistEntry {
+                wallet: Pubkey::new_unique(),
+                label: None,
+            });
+        }
+
+        let filters = config.get_transaction_filters();
+        assert_eq!(filters.len(), config.get_programs().len());
+        assert!(
+            filters
+                .values()
+                .all(|filter| filter.account_include.len() == 2_000)
+        );
+    }
+
+    #[test]
+    fn test_get_transaction_filters_toggles() {
+        let excluded = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let mut config = ListenerConfig {
+            platforms: HashSet::from([SwapPlatform::PumpFun]),
+            excluded_accounts: excluded.iter().copied().collect(),
+            ..Default::default()
+        };
+
+        let filter = config.get_transaction_filters()
+            [&format!("subscribe_transactions_{}", PUMPFUN)]
+            .clone();
+        assert_eq!(filter.vote, Some(false));
+        assert_eq!(filter.failed, Some(false));
+        assert_eq!(filter.account_exclude, get_strings(&excluded));
+
+        // Failed transactions are subscribed to as well
+        config.failed_swap_attempts = true;
+        let filter =
+            &config.get_transaction_filters()[&format!("subscribe_transactions_{}", PUMPFUN)];
+        assert_eq!(filter.failed, None);
+    }
+}
@@ -0,0 +1,308 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use tokio::sync::mpsc::{Sender, error::TrySendError};
+
+use crate::{constants::SLOT_DROPPED_DISTANCE, events_cache::EventKey};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventStatus {
+    Confirmed,
+    Finalized,
+    /// The event's slot was skipped by the cluster, the event never happened
+    Dropped,
+}
+
+/// New status of an event emitted at processed commitment
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EventStatusUpdate {
+    pub key: EventKey,
+    pub slot: u64,
+    pub status: EventStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotStatus {
+    Confirmed,
+    Finalized,
+    Dead,
+}
+
+/// Follows the slots of the events emitted at processed commitment, and
+/// reports each event once its slot is confirmed, then once it's finalized or
+/// dropped. Never waits for the receiver, updates it has no room for are
+/// dropped and counted.
+#[derive(Debug)]
+pub struct Confirmations {
+    sender: Sender<EventStatusUpdate>,
+    slots: Mutex<Slots>,
+    dropped: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+struct Slots {
+    /// Events waiting for their slot to be finalized or dropped
+    pending: BTreeMap<u64, Vec<EventKey>>,
+    /// Status of the recent slots, sources may report them before the events
+    statuses: BTreeMap<u64, SlotStatus>,
+    finalized_slot: u64,
+}
+
+impl Confirmations {
+    pub fn new(sender: Sender<EventStatusUpdate>) -> Self {
+        Self {
+            sender,
+            slots: Mutex::new(Slots::default()),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Status updates dropped so far because the receiver was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Called once the event has been emitted
+    pub fn track(&self, key: EventKey, slot: u64) {
+        let updates = {
+            let mut slots = self.slots.lock().unwrap();
+
+            match slots.statuses.get(&slot).copied() {
+                Some(SlotStatus::Finalized) => vec![(key, slot, EventStatus::Finalized)],
+                Some(SlotStatus::Dead) => vec![(key, slot, EventStatus::Dropped)],
+                _ if slots.finalized_slot >= slot + SLOT_DROPPED_DISTANCE => {
+                    vec![(key, slot, EventStatus::Dropped)]
+                }
+                status => {
+                    slots.pending.entry(slot).or_default().push(key);
+
+                    match status {
+                        Some(SlotStatus::Confirmed) => vec![(key, slot, EventStatus::Confirmed)],
+                        _ => vec![],
+                    }
+                }
+            }
+        };
+
+        self.send(updates);
+    }
+
+    /// Called for every slot update of every source, so the same update may
+    /// come several times
+    pub fn update_slot(&self, slot: u64, status: SlotStatus) {
+        let mut updates = Vec::new();
+
+        {
+            let mut slots = self.slots.lock().unwrap();
+            let previous_status = slots.statuses.get(&slot).copied();
+
+            match (status, previous_status) {
+                (SlotStatus::Confirmed, None) => {
+                    slots.statuses.insert(slot, status);
+                    for key in slots.pending.get(&slot).into_iter().flatten() {
+                        updates.push((*key, slot, EventStatus::Confirmed));
+                    }
+                }
+                (SlotStatus::Finalized, None | Some(SlotStatus::Confirmed)) => {
+                    slots.statuses.insert(slot, status);
+                    slots.finalized_slot = slots.finalized_slot.max(slot);
+                    for key in slots.pending.remove(&slot).into_iter().flatten() {
+                        updates.push((key, slot, EventStatus::Finalized));
+                    }
+                }
+                (SlotStatus::Dead, None | Some(SlotStatus::Confirmed)) => {
+                    slots.statuses.insert(slot, status);
+                    for key in slots.pending.remove(&slot).into_iter().flatten() {
+                        updates.push((key, slot, EventStatus::Dropped));
+                    }
+                }
+                _ => {}
+            }
+
+            // Slots still not finalized long after the ones following them
+            // were are on an abandoned fork, as `track` tells
+            if let Some(oldest_slot) = slots.finalized_slot.checked_sub(SLOT_DROPPED_DISTANCE) {
+                let pending = slots.pending.split_off(&(oldest_slot + 1));
+                for (slot, keys) in std::mem::replace(&mut slots.pending, pending) {
+                    for key in keys {
+                        updates.push((key, slot, EventStatus::Dropped));
+                    }
+                }
+
+                slots.statuses = slots.statuses.split_off(&oldest_slot);
+            }
+        }
+
+        self.send(updates);
+    }
+
+    /// Status updates are lost once the receiver is dropped, without stopping
+    /// the listener
+    fn send(&self, updates: Vec<(EventKey, u64, EventStatus)>) {
+        for (key, slot, status) in updates {
+            if let Err(TrySendError::Full(_)) =
+                self.sender
+                    .try_send(EventStatusUpdate { key, slot, status })
+                && self.dropped.fetch_add(1, Ordering::Relaxed) == 0
+            {
+                tracing::warn!("confirmations receiver full, dropping status updates");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::signature::Signature;
+    use tokio::sync::mpsc::{self, Receiver};
+
+    use super::*;
+    use crate::types::EventKind;
+
+    fn get_key() -> EventKey {
+        EventKey {
+            signature: Signature::new_unique(),
+            instruction_index: 0,
+            inner_instruction_index: None,
+            kind: EventKind::Swap,
+        }
+    }
+
+    fn get_updates(receiver: &mut Receiver<EventStatusUpdate>) -> Vec<EventStatusUpdate> {
+        let mut updates = Vec::new();
+        while let Ok(update) = receiver.try_recv() {
+            updates.push(update);
+        }
+
+        updates
+    }
+
+    #[test]
+    fn test_confirmed_then_finalized_slot() {
+        let (sender, mut receiver) = mpsc::channel(16);
+        let confirmations = Confirmations::new(sender);
+        let key = get_key();
+
+        confirmations.track(key, 100);
+        assert_eq!(get_updates(&mut receiver), vec![]);
+
+        // Every source reports the slot
+        confirmations.update_slot(100, SlotStatus::Confirmed);
+        confirmations.update_slot(100, SlotStatus::Confirmed);
+        assert_eq!(
+            get_updates(&mut receiver),
+            vec![EventStatusUpdate {
+                key,
+                slot: 100,
+                status: EventStatus::Confirmed,
+            }]
+        );
+
+        confirmations.update_slot(100, SlotStatus::Finalized);
+        confirmations.update_slot(100, SlotStatus::Finalized);
+        assert_eq!(
+            get_updates(&mut receiver),
+            vec![EventStatusUpdate {
+                key,
+                slot: 100,
+                status: EventStatus::Finalized,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_slot_reported_before_the_event() {
+        let (sender, mut receiver) = mpsc::channel(16);
+        let confirmations = Confirmations::new(sender);
+        let key = get_key();
+
+        confirmations.update_slot(100, SlotStatus::Confirmed);
+        confirmations.track(key, 100);
+        assert_eq!(
+            get_updates(&mut receiver),
+            vec![EventStatusUpdate {
+                key,
+                slot: 100,
+                status: EventStatus::Confirmed,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_dropped_slot() {
+        let (sender, mut receiver) = mpsc::channel(16);
+        let confirmations = Confirmations::new(sender);
+        let (key, late_key) = (get_key(), get_key());
+
+        confirmations.track(key, 100);
+        confirmations.update_slot(100 + SLOT_DROPPED_DISTANCE - 1, SlotStatus::Finalized);
+        assert_eq!(get_updates(&mut receiver), vec![]);
+
+        // Never finalized while a slot that far after it was
+        confirmations.update_slot(100 + SLOT_DROPPED_DISTANCE, SlotStatus::Finalized);
+        assert_eq!(
+            get_updates(&mut receiver),
+            vec![EventStatusUpdate {
+                key,
+                slot: 100,
+                status: EventStatus::Dropped,
+            }]
+        );
+
+        // Same for an event emitted after that
+        confirmations.track(late_key, 100);
+        assert_eq!(
+            get_updates(&mut receiver),
+            vec![EventStatusUpdate {
+                key: late_key,
+                slot: 100,
+                status: EventStatus::Dropped,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_dead_slot() {
+        let (sender, mut receiver) = mpsc::channel(16);
+        let confirmations = Confirmations::new(sender);
+        let key = get_key();
+
+        confirmations.track(key, 100);
+        confirmations.update_slot(100, SlotStatus::Dead);
+        assert_eq!(
+            get_updates(&mut receiver),
+            vec![EventStatusUpdate {
+                key,
+                slot: 100,
+                status: EventStatus::Dropped,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_full_receiver() {
+        let (sender, mut receiver) = mpsc::channel(1);
+        let confirmations = Confirmations::new(sender);
+        let keys = [get_key(), get_key(), get_key()];
+
+        for key in keys {
+            confirmations.track(key, 100);
+        }
+        confirmations.update_slot(100, SlotStatus::Confirmed);
+
+        assert_eq!(confirmations.dropped(), 2);
+        assert_eq!(
+            get_updates(&mut receiver),
+            vec![EventStatusUpdate {
+                key: keys[0],
+                slot: 100,
+                status: EventStatus::Confirmed,
+            }]
+        );
+    }
+}
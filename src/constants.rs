@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 pub(super) use solana_sdk::pubkey::Pubkey;
 
 pub mod programs {
@@ -8,11 +10,148 @@ pub mod programs {
     pub const RAYDIUM_CPMM: Pubkey =
         solana_sdk::pubkey!("CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C");
     pub const RAYDIUM_CLMM: Pubkey =
-        solana_sdk::pubkey!("CLMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C");
+        solana_sdk::pubkey!("CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK");
+    pub const METEORA_DLMM: Pubkey =
+        solana_sdk::pubkey!("LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo");
+    pub const ASSOCIATED_TOKEN_ACCOUNT: Pubkey =
+        solana_sdk::pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+    pub const COMPUTE_BUDGET: Pubkey =
+        solana_sdk::pubkey!("ComputeBudget111111111111111111111111111111");
     pub const TOKEN: Pubkey = solana_sdk::pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
     pub const TOKEN_2022: Pubkey =
         solana_sdk::pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
 }
 
+/// Events not seen again for this long are dropped from the events cache
+pub const EVENTS_CACHE_TTL: Duration = Duration::from_secs(120);
+
+/// Finalized slots past an event's slot after which the event is dropped if its
+/// own slot never got finalized
+pub const SLOT_DROPPED_DISTANCE: u64 = 32;
+
+/// Delay before the first reconnect of a source, doubled on every failed attempt
+pub const SOURCE_RECONNECT_INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+pub const SOURCE_RECONNECT_MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// Failed reconnects in a row after which a source is reported failed, it's
+/// still reconnected
+pub const SOURCE_FAILED_RECONNECT_ATTEMPTS: u32 = 20;
+/// A source that sends nothing for this long, not even a ping, has stalled and
+/// is reconnected
+pub const SOURCE_STALL_TIMEOUT: Duration = Duration::from_secs(30);
+
 pub const SOLANA_PUBKEY: Pubkey =
     solana_sdk::pubkey!("So11111111111111111111111111111111111111112");
+
+pub const JITO_TIP_ACCOUNTS: [Pubkey; 8] = [
+    solana_sdk::pubkey!("96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5"),
+    solana_sdk::pubkey!("HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe"),
+    solana_sdk::pubkey!("Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY"),
+    solana_sdk::pubkey!("ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49"),
+    solana_sdk::pubkey!("DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh"),
+    solana_sdk::pubkey!("ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt"),
+    solana_sdk::pubkey!("DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL"),
+    solana_sdk::pubkey!("3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT"),
+];
+
+/// Raydium fee rates are expressed per million
+pub const RAYDIUM_FEE_RATE_DENOMINATOR: u64 = 1_000_000;
+/// Trade fee rate of the public Raydium CPMM amm configs, the default
+/// `ListenerConfig::raydium_cpmm_amm_configs`
+pub const RAYDIUM_CPMM_TRADE_FEE_RATES: [(Pubkey, u64); 4] = [
+    (
+        solana_sdk::pubkey!("D4FPEruKEHrG5TenZ2mpDGEfu1iUvTiqBxvpU8HLBvC2"),
+        2_500,
+    ),
+    (
+        solana_sdk::pubkey!("G95xxie3XbkCqtE39GgQ9Ggc7xBC8Uceve7HFDEFApkc"),
+        10_000,
+    ),
+    (
+        solana_sdk::pubkey!("2fGXL8uhqxJ4tpgtosHZXT4zcQap6j62z3bMDxdkMvy5"),
+        20_000,
+    ),
+    (
+        solana_sdk::pubkey!("C7Cx2pMLtjybS3mDKSfsBj4zQ3PRZGkKt7RCYTTbCSx2"),
+        40_000,
+    ),
+];
+/// Shares of the trade fee going to the protocol and the fund, per million
+pub const RAYDIUM_CPMM_PROTOCOL_FEE_RATE: u64 = 120_000;
+pub const RAYDIUM_CPMM_FUND_FEE_RATE: u64 = 40_000;
+
+/// Trade fee rate of the public Raydium CLMM amm configs of the 0.01%, 0.25%,
+/// 0.05% and 1% tiers, the default `ListenerConfig::raydium_clmm_amm_configs`
+pub const RAYDIUM_CLMM_TRADE_FEE_RATES: [(Pubkey, u64); 4] = [
+    (
+        solana_sdk::pubkey!("4BLNHtVe942GSs4teSZqGX24xwKNkqU7bGgNn3iUiUpw"),
+        100,
+    ),
+    (
+        solana_sdk::pubkey!("E64NGkDLLCdQ2yFNPcavaKptrEgmiQaNykUuLC1Qgwyp"),
+        2_500,
+    ),
+    (
+        solana_sdk::pubkey!("HfERMT5DRA6C1TAqecrJQFpmkf3wsWTMncqnj3RDg5aw"),
+        500,
+    ),
+    (
+        solana_sdk::pubkey!("A1BBtTYJd4i3xU8D6Tc2FzU6ZN4oXZWXKZnCxwbHXr8x"),
+        10_000,
+    ),
+];
+pub const RAYDIUM_CLMM_PROTOCOL_FEE_RATE: u64 = 120_000;
+pub const RAYDIUM_CLMM_FUND_FEE_RATE: u64 = 40_000;
+
+/// Raydium AMM v4 trade fee rate and protocol share, per million, the default
+/// `ListenerConfig::raydium_amm_v4_fee_rates`
+pub const RAYDIUM_AMM_V4_TRADE_FEE_RATE: u64 = 2_500;
+pub const RAYDIUM_AMM_V4_PROTOCOL_FEE_RATE: u64 = 120_000;
+
+/// `getTransaction` calls for a transaction seen in the logs before giving up,
+/// the node may not serve it yet
+pub const RPC_GET_TRANSACTION_ATTEMPTS: u32 = 10;
+pub const RPC_GET_TRANSACTION_RETRY_DELAY: Duration = Duration::from_millis(400);
+/// Signatures remembered by an RPC source so that a transaction mentioning
+/// several programs is fetched once
+pub const RPC_RECENT_SIGNATURES: usize = 10_000;
+/// Transactions fetched at once by an RPC source, the logs notifications wait
+/// beyond that
+pub const RPC_MAX_CONCURRENT_FETCHES: usize = 32;
+
+/// Signatures per `getSignaturesForAddress` page, the most nodes serve
+pub const BACKFILL_SIGNATURES_PAGE_SIZE: usize = 1_000;
+/// Events remembered by the events cache of a backfill, a backfill sees each
+/// transaction once
+pub const BACKFILL_EVENTS_CACHE_CAPACITY: usize = 1_024;
+/// Time a backfilled transaction may take to go through the monitors before
+/// the backfill fails on it
+pub const BACKFILL_PROCESS_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Events buffered for each mint receiver of `track_mint`
+pub const TRACKED_MINT_BUFFER_SIZE: usize = 128;
+
+/// Updates waiting to be written by the recorder, sources drop those beyond
+pub const RECORDER_BUFFER_SIZE: usize = 16_384;
+/// Recorded updates are flushed to the file at least this often
+pub const RECORDER_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+/// Replayed updates sent to the pipeline and not processed yet, before the
+/// replay waits for it
+pub const REPLAY_MAX_PENDING: usize = 1_024;
+/// Time the pipeline may go without starting on the replayed updates left
+/// before the replay ends regardless, it drops those it can't parse
+pub const REPLAY_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time a decoded transaction may take to go through the monitors, which only
+/// fail to finish when one of them errors
+pub const DECODE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Meteora DLMM pairs whose bin step is remembered, the oldest are forgotten
+pub const METEORA_DLMM_BIN_STEPS_CAPACITY: usize = 100_000;
+/// Time a Meteora DLMM pair account lookup may take, the monitor waits on it
+pub const METEORA_DLMM_LB_PAIR_FETCH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Transactions whose index within their block is remembered until their
+/// events are sent
+pub const TRANSACTION_INDEXES_CAPACITY: usize = 10_000;
+/// Slots whose block time a gRPC source remembers
+pub const BLOCK_TIMES_CAPACITY: usize = 1_000;
@@ -0,0 +1,244 @@
+#[cfg(any(test, feature = "serde"))]
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use carbon_core::datasource::{Datasource, Update, UpdateType};
+#[cfg(any(test, feature = "serde"))]
+use solana_sdk::{
+    bs58,
+    hash::Hash,
+    instruction::CompiledInstruction,
+    message::{MessageHeader, VersionedMessage, legacy, v0},
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::VersionedTransaction,
+};
+#[cfg(any(test, feature = "serde"))]
+use solana_transaction_status::{
+    Encodable, EncodedConfirmedTransactionWithStatusMeta, EncodedTransaction, UiMessage,
+    UiTransaction, UiTransactionEncoding,
+};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
+
+#[cfg(any(test, feature = "serde"))]
+use crate::rpc_source::get_update;
+use crate::{prelude::*, transfer::SwapTransfersTrace, types::Event};
+
+/// What the monitors made of a transaction, see `TransactionsListener::decode`
+#[derive(Debug, Clone)]
+pub struct Decoded {
+    pub events: Vec<Event>,
+    /// One per `extract_swap_transfers` call, in the order the monitors ran
+    pub swap_transfers: Vec<SwapTransfersTrace>,
+    /// Errors of the monitors that failed on the transaction, the events and
+    /// transfers are those of the others
+    pub errors: Vec<String>,
+}
+
+/// Parses a `getTransaction` response, or only its `result`, requested with
+/// the json, base64 or base58 encoding. Needs the `serde` feature, as the
+/// binary does
+#[cfg(any(test, feature = "serde"))]
+pub fn parse_transaction(json: &str) -> CarbonResult<Update> {
+    let mut value: serde_json::Value = serde_json::from_str(json)
+        .map_err(|error| Error::Custom(format!("parse transaction JSON: {}", error)))?;
+    if let Some(result) = value.get_mut("result") {
+        value = result.take();
+    }
+
+    let mut transaction: EncodedConfirmedTransactionWithStatusMeta = serde_json::from_value(value)
+        .map_err(|error| Error::Custom(format!("parse transaction: {}", error)))?;
+
+    match &transaction.transaction.transaction {
+        EncodedTransaction::LegacyBinary(_) | EncodedTransaction::Binary(_, _) => {}
+        EncodedTransaction::Json(ui_transaction) => {
+            transaction.transaction.transaction =
+                get_versioned_transaction(ui_transaction)?.encode(UiTransactionEncoding::Base64);
+        }
+        EncodedTransaction::Accounts(_) => {
+            return Err(Error::Custom(
+                "transaction must be json, base64 or base58 encoded".to_string(),
+            ));
+        }
+    }
+
+    get_update(transaction)
+}
+
+/// `jsonParsed` drops the raw instructions, only `json` has them
+#[cfg(any(test, feature = "serde"))]
+fn get_versioned_transaction(ui_transaction: &UiTransaction) -> CarbonResult<VersionedTransaction> {
+    let message = match &ui_transaction.message {
+        UiMessage::Raw(message) => message,
+        UiMessage::Parsed(_) => {
+            return Err(Error::Custom(
+                "transaction must be json, base64 or base58 encoded".to_string(),
+            ));
+        }
+    };
+
+    let signatures = ui_transaction
+        .signatures
+        .iter()
+        .map(|signature| {
+            Signature::from_str(signature)
+                .map_err(|error| Error::Custom(format!("parse signature: {}", error)))
+        })
+        .collect::<CarbonResult<Vec<_>>>()?;
+    let account_keys = message
+        .account_keys
+        .iter()
+        .map(|account_key| parse_pubkey(account_key))
+        .collect::<CarbonResult<Vec<_>>>()?;
+    let recent_blockhash = Hash::from_str(&message.recent_blockhash)
+        .map_err(|error| Error::Custom(format!("parse recent blockhash: {}", error)))?;
+    let instructions = message
+        .instructions
+        .iter()
+        .map(|instruction| {
+            Ok(CompiledInstruction {
+                program_id_index: instruction.program_id_index,
+                accounts: instruction.accounts.clone(),
+                data: bs58::decode(&instruction.data)
+                    .into_vec()
+                    .map_err(|error| Error::Custom(format!("parse instruction data: {}", error)))?,
+            })
+        })
+        .collect::<CarbonResult<Vec<_>>>()?;
+    let header = MessageHeader {
+        num_required_signatures: message.header.num_required_signatures,
+        num_readonly_signed_accounts: message.header.num_readonly_signed_accounts,
+        num_readonly_unsigned_accounts: message.header.num_readonly_unsigned_accounts,
+    };
+
+    // Only versioned messages have address table lookups, if empty
+    let message = match &message.address_table_lookups {
+        Some(address_table_lookups) => VersionedMessage::V0(v0::Message {
+            header,
+            account_keys,
+            recent_blockhash,
+            instructions,
+            address_table_lookups: address_table_lookups
+                .iter()
+                .map(|lookup| {
+                    Ok(v0::MessageAddressTableLookup {
+                        account_key: parse_pubkey(&lookup.account_key)?,
+                        writable_indexes: lookup.writable_indexes.clone(),
+                        readonly_indexes: lookup.readonly_indexes.clone(),
+                    })
+                })
+                .collect::<CarbonResult<Vec<_>>>()?,
+        }),
+        None => VersionedMessage::Legacy(legacy::Message {
+            header,
+            account_keys,
+            recent_blockhash,
+            instructions,
+        }),
+    };
+
+    Ok(VersionedTransaction {
+        signatures,
+        message,
+    })
+}
+
+#[cfg(any(test, feature = "serde"))]
+fn parse_pubkey(pubkey: &str) -> CarbonResult<Pubkey> {
+    Pubkey::from_str(pubkey).map_err(|error| Error::Custom(format!("parse pubkey: {}", error)))
+}
+
+/// Sends a single update, then idles until the pipeline is cancelled
+pub struct DecodeSource {
+    pub update: Mutex<Option<Update>>,
+}
+
+#[tonic::async_trait]
+impl Datasource for DecodeSource {
+    async fn consume(
+        &self,
+        sender: &UnboundedSender<Update>,
+        cancellation_token: CancellationToken,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let update = self.update.lock().unwrap().take();
+        if let Some(update) = update {
+            sender
+                .send(update)
+                .map_err(|error| Error::Custom(format!("send update: {}", error)))?;
+        }
+
+        cancellation_token.cancelled().await;
+
+        Ok(())
+    }
+
+    fn update_types(&self) -> Vec<UpdateType> {
+        vec![UpdateType::Transaction]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use solana_transaction_status::EncodableWithMeta;
+
+    use super::*;
+    use crate::{TransactionsListener, mock_rpc::load_fixture, types::EventType};
+
+    fn get_transaction_update(update: Update) -> Box<carbon_core::datasource::TransactionUpdate> {
+        match update {
+            Update::Transaction(transaction_update) => transaction_update,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_parses_json_encoding() {
+        let fixture = load_fixture("pumpfun_buy_without_trade_event");
+        let transaction_update =
+            get_transaction_update(parse_transaction(&fixture.to_string()).unwrap());
+
+        let mut json_fixture = fixture.clone();
+        json_fixture["transaction"] =
+            serde_json::to_value(transaction_update.transaction.json_encode()).unwrap();
+        let json_transaction_update = get_transaction_update(
+            parse_transaction(&serde_json::json!({ "result": json_fixture }).to_string()).unwrap(),
+        );
+
+        assert_eq!(
+            json_transaction_update.transaction,
+            transaction_update.transaction
+        );
+        assert_eq!(
+            json_transaction_update.signature,
+            transaction_update.signature
+        );
+    }
+
+    #[tokio::test]
+    async fn test_decodes_past_failing_monitor() {
+        let update =
+            parse_transaction(&load_fixture("pumpfun_buy_without_trade_event").to_string())
+                .unwrap();
+        let (mut transactions_listener, _receiver) =
+            TransactionsListener::new(16, 255, HashMap::new()).unwrap();
+        transactions_listener.config.associated_accounts_monitor = true;
+
+        let decoded = transactions_listener.decode(update).await.unwrap();
+
+        assert_eq!(decoded.events.len(), 1);
+        assert!(matches!(
+            decoded.events[0].event_type,
+            EventType::AssociatedAccountCreation { .. }
+        ));
+        assert_eq!(decoded.errors.len(), 1);
+        assert!(
+            decoded.errors[0].contains("pumpfun trade event not found"),
+            "{}",
+            decoded.errors[0]
+        );
+    }
+}
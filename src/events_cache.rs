@@ -0,0 +1,287 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::{BuildHasher, RandomState},
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use solana_sdk::signature::Signature;
+
+use crate::types::{Event, EventKind};
+
+/// Identifies an event regardless of the source it was received from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EventKey {
+    pub signature: Signature,
+    pub instruction_index: u32,
+    pub inner_instruction_index: Option<u32>,
+    pub kind: EventKind,
+}
+
+impl From<&Event> for EventKey {
+    fn from(event: &Event) -> Self {
+        Self {
+            signature: event.signature,
+            instruction_index: event.instruction_index,
+            inner_instruction_index: event.inner_instruction_index,
+            kind: EventKind::from(&event.event_type),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EventsCacheMetrics {
+    /// Events already in the cache, i.e. duplicates dropped
+    pub hits: u64,
+    /// Keys dropped because the cache was full or they expired
+    pub evictions: u64,
+    pub len: usize,
+}
+
+/// Number of independently locked shards. Keys are spread by hash, so monitors
+/// only wait on each other when they touch the same shard.
+const SHARDS: usize = 16;
+
+/// Bounded cache of the events already emitted, shared by every monitor.
+/// Evicts the least recently seen key of a shard when it's full, and keys not
+/// seen for `ttl`.
+#[derive(Debug)]
+pub struct EventsCache {
+    shard_capacity: usize,
+    ttl: Option<Duration>,
+    shards: Vec<Mutex<Shard>>,
+    hasher: RandomState,
+    hits: AtomicU64,
+    evictions: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+struct Shard {
+    /// Key to its tick and the last time it was seen
+    keys: HashMap<EventKey, (u64, Instant)>,
+    /// Tick to key, oldest first
+    ticks: BTreeMap<u64, EventKey>,
+    next_tick: u64,
+}
+
+impl EventsCache {
+    pub fn new(capacity: usize, ttl: Option<Duration>) -> Self {
+        Self {
+            shard_capacity: capacity.div_ceil(SHARDS).max(1),
+            ttl,
+            shards: (0..SHARDS).map(|_| Mutex::new(Shard::default())).collect(),
+            hasher: RandomState::new(),
+            hits: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    /// Records the event, returns `false` if it was already in the cache. Checking
+    /// and inserting happen under the same lock, so of several sources racing on
+    /// the same event exactly one gets `true`.
+    pub fn insert(&self, event: &Event) -> bool {
+        let key = EventKey::from(event);
+        let shard = &self.shards[self.hasher.hash_one(key) as usize % SHARDS];
+
+        let (inserted, evictions) =
+            shard
+                .lock()
+                .unwrap()
+                .insert(key, Instant::now(), self.ttl, self.shard_capacity);
+
+        if !inserted {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+        if evictions > 0 {
+            self.evictions.fetch_add(evictions, Ordering::Relaxed);
+        }
+
+        inserted
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap().keys.len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn metrics(&self) -> EventsCacheMetrics {
+        EventsCacheMetrics {
+            hits: self.hits.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            len: self.len(),
+        }
+    }
+}
+
+impl Shard {
+    /// Returns whether the key is new and how many keys were evicted
+    fn insert(
+        &mut self,
+        key: EventKey,
+        now: Instant,
+        ttl: Option<Duration>,
+        capacity: usize,
+    ) -> (bool, u64) {
+        let mut evictions = 0;
+        if let Some(ttl) = ttl {
+            while let Some((&tick, key)) = self.ticks.first_key_value() {
+                let key = *key;
+                let seen_at = self.keys[&key].1;
+                if now.duration_since(seen_at) < ttl {
+                    break;
+                }
+
+                self.ticks.remove(&tick);
+                self.keys.remove(&key);
+                evictions += 1;
+            }
+        }
+
+        let tick = self.next_tick;
+        self.next_tick += 1;
+
+        let inserted = match self.keys.insert(key, (tick, now)) {
+            Some((previous_tick, _)) => {
+                self.ticks.remove(&previous_tick);
+                false
+            }
+            None => true,
+        };
+        self.ticks.insert(tick, key);
+
+        if self.keys.len() > capacity
+            && let Some((_, key)) = self.ticks.pop_first()
+        {
+            self.keys.remove(&key);
+            evictions += 1;
+        }
+
+        (inserted, evictions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Barrier},
+        thread,
+    };
+
+    use super::*;
+    use crate::{
+        test_utils::{get_event, get_pool_creation},
+        types::SwapPlatform,
+    };
+
+    fn get_key() -> EventKey {
+        EventKey {
+            signature: Signature::new_unique(),
+            instruction_index: 0,
+            inner_instruction_index: None,
+            kind: EventKind::PoolCreation,
+        }
+    }
+
+    #[test]
+    fn test_evicts_least_recently_seen_at_capacity() {
+        let (a, b, c) = (get_key(), get_key(), get_key());
+        let now = Instant::now();
+        let mut shard = Shard::default();
+
+        assert_eq!(shard.insert(a, now, None, 2), (true, 0));
+        assert_eq!(shard.insert(b, now, None, 2), (true, 0));
+        // Seeing `a` again makes `b` the least recently seen
+        assert_eq!(shard.insert(a, now, None, 2), (false, 0));
+        assert_eq!(shard.insert(c, now, None, 2), (true, 1));
+
+        assert!(shard.keys.contains_key(&a));
+        assert!(!shard.keys.contains_key(&b));
+        assert!(shard.keys.contains_key(&c));
+        assert_eq!(shard.ticks.len(), 2);
+    }
+
+    #[test]
+    fn test_evicts_expired_keys() {
+        let (a, b) = (get_key(), get_key());
+        let ttl = Some(Duration::from_secs(60));
+        let now = Instant::now();
+        let mut shard = Shard::default();
+
+        assert_eq!(shard.insert(a, now, ttl, 16), (true, 0));
+        assert_eq!(
+            shard.insert(b, now + Duration::from_secs(59), ttl, 16),
+            (true, 0)
+        );
+        // `a` expires, `b` was seen since
+        assert_eq!(
+            shard.insert(a, now + Duration::from_secs(60), ttl, 16),
+            (true, 1)
+        );
+        assert_eq!(
+            shard.insert(b, now + Duration::from_secs(61), ttl, 16),
+            (false, 0)
+        );
+        assert_eq!(shard.keys.len(), 2);
+    }
+
+    #[test]
+    fn test_metrics() {
+        // A key per shard
+        let events_cache = EventsCache::new(SHARDS, None);
+        let events = (0..40)
+            .map(|_| get_event(get_pool_creation(SwapPlatform::PumpFun)))
+            .collect::<Vec<_>>();
+
+        for event in events.iter() {
+            assert!(events_cache.insert(event));
+        }
+        // The last one inserted in its shard is still there
+        assert!(!events_cache.insert(&events[39]));
+
+        let metrics = events_cache.metrics();
+        assert_eq!(metrics.hits, 1);
+        assert!(metrics.len <= SHARDS);
+        assert_eq!(metrics.evictions + metrics.len as u64, 40);
+    }
+
+    #[test]
+    fn test_concurrent_inserts_of_the_same_event() {
+        const SOURCES: usize = 8;
+
+        for _ in 0..100 {
+            let events_cache = Arc::new(EventsCache::new(255, None));
+            let event = Arc::new(get_event(get_pool_creation(SwapPlatform::PumpFun)));
+            let barrier = Arc::new(Barrier::new(SOURCES));
+
+            let threads = (0..SOURCES)
+                .map(|_| {
+                    let events_cache = events_cache.clone();
+                    let event = event.clone();
+                    let barrier = barrier.clone();
+
+                    thread::spawn(move || {
+                        barrier.wait();
+                        events_cache.insert(&event)
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let inserted = threads
+                .into_iter()
+                .map(|thread| thread.join().unwrap())
+                .filter(|inserted| *inserted)
+                .count();
+            assert_eq!(inserted, 1);
+            assert_eq!(events_cache.metrics().hits, SOURCES as u64 - 1);
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+
+use carbon_core::transaction::TransactionMetadata;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    constants::{JITO_TIP_ACCOUNTS, RAYDIUM_FEE_RATE_DENOMINATOR, programs},
+    transfer::get_account_keys,
+    types::{Fee, SwapFees, TransactionFees},
+};
+
+const SET_COMPUTE_UNIT_PRICE_DISCRIMINATOR: u8 = 3;
+
+pub fn get_transaction_fees(transaction_metadata: &TransactionMetadata) -> TransactionFees {
+    let account_keys = get_account_keys(transaction_metadata);
+    let meta = &transaction_metadata.meta;
+
+    // Compute budget instructions only take effect at the top level
+    let priority_fee = transaction_metadata
+        .message
+        .instructions()
+        .iter()
+        .filter(|instruction| {
+            account_keys.get(instruction.program_id_index as usize)
+                == Some(&programs::COMPUTE_BUDGET)
+        })
+        .find_map(|instruction| match instruction.data.split_first() {
+            Some((&SET_COMPUTE_UNIT_PRICE_DISCRIMINATOR, data)) => {
+                data.try_into().ok().map(u64::from_le_bytes)
+            }
+            _ => None,
+        });
+
+    // Tips can be paid from any instruction, inner ones included, so look at
+    // the balances rather than at the transfers
+    let jito_tip = account_keys
+        .iter()
+        .enumerate()
+        .filter(|(_, account_key)| JITO_TIP_ACCOUNTS.contains(account_key))
+        .filter_map(|(index, _)| {
+            let pre_balance = meta.pre_balances.get(index)?;
+            let post_balance = meta.post_balances.get(index)?;
+
+            Some(post_balance.saturating_sub(*pre_balance))
+        })
+        .sum::<u64>();
+
+    TransactionFees {
+        total_fee: meta.fee,
+        priority_fee,
+        compute_units_consumed: meta.compute_units_consumed,
+        jito_tip: (jito_tip > 0).then_some(jito_tip),
+    }
+}
+
+/// Fee rates of a Raydium pool, per million: the trade fee is charged on the
+/// input amount and the protocol and the fund take their share of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeRates {
+    pub trade_fee_rate: u64,
+    pub protocol_fee_rate: u64,
+    pub fund_fee_rate: u64,
+}
+
+impl FeeRates {
+    /// What isn't taken by the protocol and the fund stays in the pool. The
+    /// fund fee is `None` when there's no fund
+    pub fn get_swap_fees(&self, amount_in: u64, mint: Pubkey) -> SwapFees {
+        let denominator = RAYDIUM_FEE_RATE_DENOMINATOR as u128;
+        let trade_fee =
+            (amount_in as u128 * self.trade_fee_rate as u128).div_ceil(denominator) as u64;
+        let protocol_fee =
+            (trade_fee as u128 * self.protocol_fee_rate as u128 / denominator) as u64;
+        let fund_fee = (trade_fee as u128 * self.fund_fee_rate as u128 / denominator) as u64;
+
+        SwapFees {
+            lp_fee: Some(Fee {
+                amount: trade_fee.saturating_sub(protocol_fee + fund_fee),
+                mint,
+            }),
+            protocol_fee: Some(Fee {
+                amount: protocol_fee,
+                mint,
+            }),
+            creator_fee: None,
+            fund_fee: (self.fund_fee_rate > 0).then_some(Fee {
+                amount: fund_fee,
+                mint,
+            }),
+        }
+    }
+}
+
+/// Fees of a Raydium CPMM or CLMM swap, `None` for amm configs of unknown fee
+/// rates. Logged at debug level, a pool of a private config swaps all the time
+pub fn get_amm_config_swap_fees(
+    amm_configs: &HashMap<Pubkey, FeeRates>,
+    amm_config: &Pubkey,
+    amount_in: u64,
+    mint: Pubkey,
+) -> Option<SwapFees> {
+    match amm_configs.get(amm_config) {
+        Some(fee_rates) => Some(fee_rates.get_swap_fees(amount_in, mint)),
+        None => {
+            tracing::debug!("unknown raydium amm config {}, unknown fees", amm_config);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::ListenerConfig, constants::SOLANA_PUBKEY};
+
+    #[test]
+    fn test_get_amm_config_swap_fees() {
+        let amm_configs = ListenerConfig::default().raydium_cpmm_amm_configs;
+
+        // 0.25% config, 12% of the trade fee to the protocol and 4% to the fund
+        let amm_config = solana_sdk::pubkey!("D4FPEruKEHrG5TenZ2mpDGEfu1iUvTiqBxvpU8HLBvC2");
+        assert_eq!(
+            get_amm_config_swap_fees(&amm_configs, &amm_config, 1_000_000_000, SOLANA_PUBKEY),
+            Some(SwapFees {
+                lp_fee: Some(Fee {
+                    amount: 2_100_000,
+                    mint: SOLANA_PUBKEY,
+                }),
+                protocol_fee: Some(Fee {
+                    amount: 300_000,
+                    mint: SOLANA_PUBKEY,
+                }),
+                creator_fee: None,
+                fund_fee: Some(Fee {
+                    amount: 100_000,
+                    mint: SOLANA_PUBKEY,
+                }),
+            })
+        );
+
+        // CPMM and CLMM configs are distinct accounts
+        let clmm_amm_configs = ListenerConfig::default().raydium_clmm_amm_configs;
+        assert_eq!(
+            get_amm_config_swap_fees(&clmm_amm_configs, &amm_config, 1_000_000_000, SOLANA_PUBKEY),
+            None
+        );
+        // 0.05% CLMM config
+        let clmm_amm_config = solana_sdk::pubkey!("HfERMT5DRA6C1TAqecrJQFpmkf3wsWTMncqnj3RDg5aw");
+        assert_eq!(
+            get_amm_config_swap_fees(
+                &clmm_amm_configs,
+                &clmm_amm_config,
+                1_000_000_000,
+                SOLANA_PUBKEY
+            ),
+            Some(SwapFees {
+                lp_fee: Some(Fee {
+                    amount: 420_000,
+                    mint: SOLANA_PUBKEY,
+                }),
+                protocol_fee: Some(Fee {
+                    amount: 60_000,
+                    mint: SOLANA_PUBKEY,
+                }),
+                creator_fee: None,
+                fund_fee: Some(Fee {
+                    amount: 20_000,
+                    mint: SOLANA_PUBKEY,
+                }),
+            })
+        );
+
+        assert_eq!(
+            get_amm_config_swap_fees(
+                &amm_configs,
+                &Pubkey::new_unique(),
+                1_000_000_000,
+                SOLANA_PUBKEY
+            ),
+            None
+        );
+    }
+}
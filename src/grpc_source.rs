@@ -0,0 +1,531 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::Duration,
+};
+
+use carbon_core::datasource::{Datasource, TransactionUpdate, Update, UpdateType};
+use futures::{SinkExt, StreamExt};
+use solana_sdk::signature::Signature;
+use tokio::{
+    sync::{mpsc::UnboundedSender, watch},
+    time::Instant,
+};
+use tokio_util::sync::CancellationToken;
+use yellowstone_grpc_client::GeyserGrpcClient;
+use yellowstone_grpc_proto::{
+    convert_from::{create_tx_meta, create_tx_versioned},
+    geyser::{
+        CommitmentLevel, SlotStatus as GeyserSlotStatus, SubscribeRequest,
+        SubscribeRequestFilterBlocksMeta, SubscribeRequestFilterSlots,
+        SubscribeRequestFilterTransactions, SubscribeRequestPing, SubscribeUpdateBlockMeta,
+        SubscribeUpdateTransaction, subscribe_update::UpdateOneof,
+    },
+    tonic::transport::ClientTlsConfig,
+};
+
+use crate::{
+    confirmations::{Confirmations, SlotStatus},
+    constants::{BLOCK_TIMES_CAPACITY, SOURCE_STALL_TIMEOUT},
+    prelude::*,
+    recorder::Recorder,
+    sources::{SourceHealth, SourceState, Subscription, consume_with_reconnects},
+    transaction_indexes::TransactionIndexes,
+};
+
+/// Yellowstone gRPC transactions subscription. Reconnects with exponential
+/// backoff when the stream fails or stalls, and reports its health.
+pub struct GrpcSource {
+    pub url: String,
+    pub x_token: Option<String>,
+    /// Sent to the server again whenever they change, without reconnecting
+    pub transaction_filters: watch::Receiver<HashMap<String, SubscribeRequestFilterTransactions>>,
+    pub commitment: CommitmentLevel,
+    /// Also subscribes to slot updates and reports them, when set
+    pub confirmations: Option<Arc<Confirmations>>,
+    pub health: Arc<SourceHealth>,
+    /// Writes every transaction and block meta update received to disk, when
+    /// set
+    pub recorder: Option<Arc<Recorder>>,
+    /// Where the index of every transaction within its block is reported
+    pub transaction_indexes: TransactionIndexes,
+    /// Time transactions are held back waiting for the block meta of their
+    /// slot, see `ListenerConfig::block_time_wait`
+    pub block_time_wait: Duration,
+}
+
+#[tonic::async_trait]
+impl Datasource for GrpcSource {
+    async fn consume(
+        &self,
+        sender: &UnboundedSender<Update>,
+        cancellation_token: CancellationToken,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        consume_with_reconnects(self, sender, cancellation_token).await
+    }
+
+    fn update_types(&self) -> Vec<UpdateType> {
+        vec![UpdateType::Transaction]
+    }
+}
+
+#[tonic::async_trait]
+impl Subscription for GrpcSource {
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn health(&self) -> &SourceHealth {
+        &self.health
+    }
+
+    /// Fails when the stream stalls too
+    async fn subscribe(
+        &self,
+        sender: &UnboundedSender<Update>,
+        received: &mut bool,
+    ) -> CarbonResult<()> {
+        let mut client = GeyserGrpcClient::build_from_shared(self.url.clone())
+            .map_err(|error| Error::Custom(format!("build gRPC client: {}", error)))?
+            .x_token(self.x_token.clone())
+            .map_err(|error| Error::Custom(format!("set gRPC x-token: {}", error)))?
+            .connect_timeout(Duration::from_secs(15))
+            .timeout(Duration::from_secs(15))
+            .tls_config(ClientTlsConfig::new().with_enabled_roots())
+            .map_err(|error| Error::Custom(format!("set gRPC tls config: {}", error)))?
+            .connect()
+            .await
+            .map_err(|error| Error::Custom(format!("connect gRPC client: {}", error)))?;
+
+        let mut transaction_filters = self.transaction_filters.clone();
+        let request = self.get_subscribe_request(&transaction_filters.borrow_and_update());
+        let (mut subscribe_sender, mut stream) = client
+            .subscribe_with_request(Some(request))
+            .await
+            .map_err(|error| Error::Custom(format!("subscribe to gRPC: {}", error)))?;
+
+        self.health.set_state(SourceState::Connected);
+
+        let mut block_times = BlockTimes::default();
+        let mut pending_transactions = PendingTransactions::default();
+        loop {
+            for transaction_update in pending_transactions.pop_ready(&block_times, Instant::now()) {
+                let update = match get_transaction_update(
+                    transaction_update,
+                    &block_times,
+                    &self.transaction_indexes,
+                ) {
+                    Some(update) => update,
+                    None => {
+                        continue;
+                    }
+                };
+
+                sender
+                    .send(update)
+                    .map_err(|error| Error::Custom(format!("send update: {}", error)))?;
+            }
+
+            let next_deadline = pending_transactions.next_deadline();
+            let message = tokio::select! {
+                result = tokio::time::timeout(SOURCE_STALL_TIMEOUT, stream.next()) => match result {
+                    Ok(Some(Ok(message))) => message,
+                    Ok(Some(Err(status))) => {
+                        return Err(Error::Custom(format!("gRPC stream: {}", status)));
+                    }
+                    Ok(None) => {
+                        return Ok(());
+                    }
+                    Err(_) => {
+                        return Err(Error::Custom(format!(
+                            "no gRPC message for {:?}",
+                            SOURCE_STALL_TIMEOUT
+                        )));
+                    }
+                },
+                Ok(()) = transaction_filters.changed() => {
+                    // A new request replaces the filters of the subscription
+                    let request =
+                        self.get_subscribe_request(&transaction_filters.borrow_and_update());
+                    subscribe_sender.send(request).await.map_err(|error| {
+                        Error::Custom(format!("update gRPC subscription: {}", error))
+                    })?;
+
+                    continue;
+                }
+                _ = tokio::time::sleep_until(next_deadline.unwrap_or_else(Instant::now)),
+                    if next_deadline.is_some() =>
+                {
+                    continue;
+                }
+            };
+            *received = true;
+
+            if let Some(recorder) = &self.recorder
+                && matches!(
+                    message.update_oneof,
+                    Some(UpdateOneof::Transaction(_) | UpdateOneof::BlockMeta(_))
+                )
+            {
+                recorder.record(&message);
+            }
+
+            match message.update_oneof {
+                Some(UpdateOneof::Transaction(transaction_update)) => {
+                    self.health.record_message(transaction_update.slot);
+
+                    pending_transactions
+                        .push(transaction_update, Instant::now() + self.block_time_wait);
+                }
+                Some(UpdateOneof::BlockMeta(block_meta)) => {
+                    block_times.insert(&block_meta);
+                }
+                Some(UpdateOneof::Slot(slot_update)) => {
+                    let confirmations = match &self.confirmations {
+                        Some(confirmations) => confirmations,
+                        None => {
+                            continue;
+                        }
+                    };
+
+                    let status = match GeyserSlotStatus::try_from(slot_update.status) {
+                        Ok(GeyserSlotStatus::SlotConfirmed) => SlotStatus::Confirmed,
+                        Ok(GeyserSlotStatus::SlotFinalized) => SlotStatus::Finalized,
+                        Ok(GeyserSlotStatus::SlotDead) => SlotStatus::Dead,
+                        _ => {
+                            continue;
+                        }
+                    };
+
+                    confirmations.update_slot(slot_update.slot, status);
+                }
+                Some(UpdateOneof::Ping(_)) => {
+                    subscribe_sender
+                        .send(SubscribeRequest {
+                            ping: Some(SubscribeRequestPing { id: 1 }),
+                            ..Default::default()
+                        })
+                        .await
+                        .map_err(|error| Error::Custom(format!("send gRPC ping: {}", error)))?;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl GrpcSource {
+    fn get_subscribe_request(
+        &self,
+        transaction_filters: &HashMap<String, SubscribeRequestFilterTransactions>,
+    ) -> SubscribeRequest {
+        SubscribeRequest {
+            slots: match self.confirmations {
+                Some(_) => {
+                    let mut map = HashMap::new();
+                    map.insert(
+                        "subscribe_slots".to_string(),
+                        SubscribeRequestFilterSlots {
+                            filter_by_commitment: Some(false),
+                            interslot_updates: Some(false),
+                        },
+                    );
+
+                    map
+                }
+                None => HashMap::new(),
+            },
+            transactions: transaction_filters.clone(),
+            blocks_meta: {
+                let mut map = HashMap::new();
+                map.insert(
+                    "subscribe_blocks_meta".to_string(),
+                    SubscribeRequestFilterBlocksMeta {},
+                );
+
+                map
+            },
+            commitment: Some(self.commitment as i32),
+            ..Default::default()
+        }
+    }
+}
+
+/// Block time of the latest slots, from the blocks meta updates. A block meta
+/// is only sent once its slot is complete, so transactions streamed before
+/// that, as at processed commitment, get no block time unless they wait for it
+/// in `PendingTransactions`
+#[derive(Debug, Default)]
+pub(crate) struct BlockTimes {
+    block_times: HashMap<u64, i64>,
+    slots: VecDeque<u64>,
+}
+
+impl BlockTimes {
+    pub(crate) fn insert(&mut self, block_meta: &SubscribeUpdateBlockMeta) {
+        let block_time = match &block_meta.block_time {
+            Some(block_time) => block_time.timestamp,
+            None => {
+                return;
+            }
+        };
+
+        if self
+            .block_times
+            .insert(block_meta.slot, block_time)
+            .is_some()
+        {
+            return;
+        }
+
+        self.slots.push_back(block_meta.slot);
+        if self.slots.len() > BLOCK_TIMES_CAPACITY
+            && let Some(slot) = self.slots.pop_front()
+        {
+            self.block_times.remove(&slot);
+        }
+    }
+}
+
+/// Transactions waiting for the block meta of their slot, up to a deadline each.
+/// They come out in the order they came in, a transaction whose block time is
+/// known waits for those before it
+#[derive(Debug, Default)]
+pub(crate) struct PendingTransactions {
+    transactions: VecDeque<(Instant, SubscribeUpdateTransaction)>,
+}
+
+impl PendingTransactions {
+    pub(crate) fn push(
+        &mut self,
+        transaction_update: SubscribeUpdateTransaction,
+        deadline: Instant,
+    ) {
+        self.transactions.push_back((deadline, transaction_update));
+    }
+
+    /// Deadlines only grow, the first one is the next to pass
+    pub(crate) fn next_deadline(&self) -> Option<Instant> {
+        self.transactions.front().map(|(deadline, _)| *deadline)
+    }
+
+    pub(crate) fn pop_ready(
+        &mut self,
+        block_times: &BlockTimes,
+        now: Instant,
+    ) -> Vec<SubscribeUpdateTransaction> {
+        let mut ready = Vec::new();
+        while let Some((deadline, transaction_update)) = self.transactions.front() {
+            if *deadline > now
+                && !block_times
+                    .block_times
+                    .contains_key(&transaction_update.slot)
+            {
+                break;
+            }
+
+            if let Some((_, transaction_update)) = self.transactions.pop_front() {
+                ready.push(transaction_update);
+            }
+        }
+
+        ready
+    }
+}
+
+/// Reports the index of the transaction within its block on the way
+pub(crate) fn get_transaction_update(
+    transaction_update: SubscribeUpdateTransaction,
+    block_times: &BlockTimes,
+    transaction_indexes: &TransactionIndexes,
+) -> Option<Update> {
+    let transaction_info = transaction_update.transaction?;
+    let signature = Signature::try_from(transaction_info.signature.as_slice()).ok()?;
+    let transaction = create_tx_versioned(transaction_info.transaction?).ok()?;
+    let meta = create_tx_meta(transaction_info.meta?).ok()?;
+
+    transaction_indexes.insert(signature, transaction_info.index);
+
+    Some(Update::Transaction(Box::new(TransactionUpdate {
+        signature,
+        transaction,
+        meta,
+        is_vote: transaction_info.is_vote,
+        slot: transaction_update.slot,
+        block_time: block_times
+            .block_times
+            .get(&transaction_update.slot)
+            .copied(),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::pubkey::Pubkey;
+    use yellowstone_grpc_proto::solana::storage::confirmed_block::UnixTimestamp;
+
+    use super::*;
+    use crate::{
+        TransactionsListener,
+        mock_rpc::{get_subscribe_update_transaction, load_fixture},
+    };
+
+    /// In slots 340000100 and 340000300
+    const FIXTURES: [&str; 2] = ["create_associated_account", "create_associated_account_2"];
+
+    fn get_block_meta(slot: u64, timestamp: i64) -> SubscribeUpdateBlockMeta {
+        SubscribeUpdateBlockMeta {
+            slot,
+            block_time: Some(UnixTimestamp { timestamp }),
+            ..Default::default()
+        }
+    }
+
+    /// Slot and block time of the updates
+    fn get_block_times(
+        transaction_updates: Vec<SubscribeUpdateTransaction>,
+        block_times: &BlockTimes,
+    ) -> Vec<(u64, Option<i64>)> {
+        transaction_updates
+            .into_iter()
+            .map(|transaction_update| {
+                match get_transaction_update(
+                    transaction_update,
+                    block_times,
+                    &TransactionIndexes::default(),
+                ) {
+                    Some(Update::Transaction(transaction_update)) => {
+                        (transaction_update.slot, transaction_update.block_time)
+                    }
+                    update => panic!("expected a transaction update, got {:?}", update),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_transactions_wait_for_their_block_meta() {
+        let now = Instant::now();
+        let mut block_times = BlockTimes::default();
+        let mut pending_transactions = PendingTransactions::default();
+        for (index, fixture) in FIXTURES.iter().enumerate() {
+            pending_transactions.push(
+                get_subscribe_update_transaction(&load_fixture(fixture), index as u64),
+                now + Duration::from_secs(1),
+            );
+        }
+        assert!(pending_transactions.pop_ready(&block_times, now).is_empty());
+
+        // The second transaction waits for the first
+        block_times.insert(&get_block_meta(340_000_300, 1_750_000_300));
+        assert!(pending_transactions.pop_ready(&block_times, now).is_empty());
+
+        block_times.insert(&get_block_meta(340_000_100, 1_750_000_100));
+        let ready = pending_transactions.pop_ready(&block_times, now);
+        assert_eq!(
+            get_block_times(ready, &block_times),
+            vec![
+                (340_000_100, Some(1_750_000_100)),
+                (340_000_300, Some(1_750_000_300)),
+            ]
+        );
+        assert_eq!(pending_transactions.next_deadline(), None);
+    }
+
+    #[test]
+    fn test_transactions_stop_waiting_at_their_deadline() {
+        let now = Instant::now();
+        let block_times = BlockTimes::default();
+        let mut pending_transactions = PendingTransactions::default();
+        for (index, fixture) in FIXTURES.iter().enumerate() {
+            pending_transactions.push(
+                get_subscribe_update_transaction(&load_fixture(fixture), index as u64),
+                now + Duration::from_secs(index as u64 + 1),
+            );
+        }
+        assert_eq!(
+            pending_transactions.next_deadline(),
+            Some(now + Duration::from_secs(1))
+        );
+
+        let ready = pending_transactions.pop_ready(&block_times, now + Duration::from_secs(1));
+        assert_eq!(
+            get_block_times(ready, &block_times),
+            vec![(340_000_100, None)]
+        );
+
+        let ready = pending_transactions.pop_ready(&block_times, now + Duration::from_secs(2));
+        assert_eq!(
+            get_block_times(ready, &block_times),
+            vec![(340_000_300, None)]
+        );
+    }
+
+    #[test]
+    fn test_transactions_without_wait_come_out_right_away() {
+        let now = Instant::now();
+        let block_times = BlockTimes::default();
+        let mut pending_transactions = PendingTransactions::default();
+        pending_transactions.push(
+            get_subscribe_update_transaction(&load_fixture(FIXTURES[0]), 0),
+            now,
+        );
+
+        let ready = pending_transactions.pop_ready(&block_times, now);
+        assert_eq!(
+            get_block_times(ready, &block_times),
+            vec![(340_000_100, None)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_request_follows_watchlist() {
+        let (transactions_listener, _receiver) =
+            TransactionsListener::new(16, 255, HashMap::new()).unwrap();
+        transactions_listener.update_transaction_filters();
+        let grpc_source = transactions_listener.get_grpc_source(
+            "http://127.0.0.1:10000".to_string(),
+            None,
+            Arc::default(),
+        );
+        let mut transaction_filters = grpc_source.transaction_filters.clone();
+        let wallet = Pubkey::new_unique();
+
+        // What the subscription sends again once the filters change
+        let request = grpc_source.get_subscribe_request(&transaction_filters.borrow_and_update());
+        assert!(!request.transactions.is_empty());
+        assert!(
+            request
+                .transactions
+                .values()
+                .all(|filter| filter.account_required.is_empty())
+        );
+
+        transactions_listener.add_watched_wallet(wallet, None);
+        tokio::time::timeout(Duration::from_secs(1), transaction_filters.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        let request = grpc_source.get_subscribe_request(&transaction_filters.borrow_and_update());
+        assert!(
+            request
+                .transactions
+                .values()
+                .all(|filter| filter.account_include == vec![wallet.to_string()])
+        );
+
+        transactions_listener.remove_watched_wallet(&wallet);
+        tokio::time::timeout(Duration::from_secs(1), transaction_filters.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        let request = grpc_source.get_subscribe_request(&transaction_filters.borrow_and_update());
+        assert!(
+            request
+                .transactions
+                .values()
+                .all(|filter| !filter.account_include.contains(&wallet.to_string()))
+        );
+    }
+}
@@ -1,139 +1,467 @@
 use std::{
-    collections::{HashMap, HashSet},
-    sync::Arc,
+    collections::{HashMap, VecDeque},
+    path::PathBuf,
+    sync::{Arc, Mutex},
 };
 
-use carbon_core::pipeline::Pipeline;
+use carbon_core::{
+    datasource::{Datasource, Update},
+    pipeline::{Pipeline, PipelineBuilder},
+};
+use carbon_meteora_dlmm_decoder::MeteoraDlmmDecoder;
 use carbon_pumpfun_decoder::PumpfunDecoder;
+use carbon_raydium_amm_v4_decoder::RaydiumAmmV4Decoder;
+use carbon_raydium_clmm_decoder::RaydiumClmmDecoder;
 use carbon_raydium_cpmm_decoder::RaydiumCpmmDecoder;
-use carbon_yellowstone_grpc_datasource::YellowstoneGrpcGeyserClient;
+use carbon_spl_associated_token_account_decoder::SplAssociatedTokenAccountDecoder;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
 use strum::IntoEnumIterator;
 use tokio::{
     sync::{
-        RwLock,
         mpsc::{self, Receiver, Sender},
+        oneshot, watch,
     },
     task::JoinHandle,
 };
 use tokio_util::sync::CancellationToken;
-use types::Event;
-use yellowstone_grpc_proto::geyser::SubscribeRequestFilterTransactions;
+use transaction_events::{
+    EventSender, ListenerSender, TransactionEndDecoder, TransactionEventsMonitor,
+};
+use types::{Event, TransactionEvents};
+use yellowstone_grpc_proto::geyser::{CommitmentLevel, SubscribeRequestFilterTransactions};
 
-use crate::{constants::programs::*, prelude::*, types::*};
+use crate::{
+    backfill::{Backfill, BackfillMonitor, BackfillSource},
+    config::ListenerConfig,
+    confirmations::{Confirmations, EventStatusUpdate},
+    constants::{
+        BACKFILL_EVENTS_CACHE_CAPACITY, DECODE_TIMEOUT, EVENTS_CACHE_TTL,
+        METEORA_DLMM_LB_PAIR_FETCH_TIMEOUT, TRACKED_MINT_BUFFER_SIZE,
+    },
+    decode::{DecodeSource, Decoded},
+    events_cache::{EventsCache, EventsCacheMetrics},
+    grpc_source::GrpcSource,
+    monitor_errors::{IsolatedMonitor, MonitorErrors},
+    position::InstructionPositions,
+    prelude::*,
+    recorder::Recorder,
+    replay::{
+        Replay, ReplayClock, ReplayClockDecoder, ReplayClockMonitor, ReplayEndMonitor, ReplaySource,
+    },
+    rpc_source::{RpcSource, get_commitment_config},
+    sources::{SourceConfig, SourceError, SourceHealth, SourceStatus, Sources},
+    tracked_mints::TrackedMints,
+    transaction_indexes::TransactionIndexes,
+    transfer::SWAP_TRANSFERS_TRACE,
+    types::*,
+};
 
 pub mod ata;
+pub mod backfill;
+pub mod config;
+pub mod confirmations;
 pub(crate) mod constants;
+pub mod decode;
+pub mod events_cache;
+pub mod fees;
+pub mod grpc_source;
+pub mod meteora_dlmm;
+#[cfg(test)]
+pub(crate) mod mock_rpc;
+pub mod monitor_errors;
+pub mod position;
 pub(crate) mod prelude;
+#[cfg(feature = "proto")]
+pub mod proto;
 pub mod pumpfun;
+pub mod raydium_amm_v4;
+pub mod raydium_clmm;
 pub mod raydium_cpmm;
+pub mod recorder;
+pub mod replay;
+pub mod rpc_source;
+#[cfg(feature = "serde")]
+pub(crate) mod serde_helpers;
+pub mod sources;
+#[cfg(test)]
+pub(crate) mod test_utils;
+pub mod tracked_mints;
+pub mod transaction_events;
+pub mod transaction_indexes;
 pub mod transfer;
 pub mod types;
 pub(crate) mod utils;
+pub mod watchlist;
 
 pub struct TransactionsListener {
-    pub sender: Sender<Event>,
-    pub grpc_urls: HashMap<String, (String, Option<String>)>,
+    pub sender: ListenerSender,
+    pub source_configs: HashMap<String, SourceConfig>,
     pub pipeline_thread: Option<(CancellationToken, JoinHandle<CarbonResult<()>>)>,
-    pub events_cache: HashMap<String, Arc<RwLock<HashSet<Event>>>>,
+    /// Events already emitted, shared by every monitor and pipeline
+    pub events_cache: Arc<EventsCache>,
+    /// Datasource of the pipeline, `source_configs` are started and stopped in it
+    /// without rebuilding the pipeline
+    pub sources: Sources,
+    /// Platforms, filters and toggles, taking effect on the next `run` or
+    /// `add_source`
+    pub config: ListenerConfig,
+    /// Set by `enable_confirmations`
+    pub confirmations: Option<Arc<Confirmations>>,
+    /// Receivers of `track_mint`
+    pub tracked_mints: TrackedMints,
+    /// Transaction filters of `config`, every gRPC source follows them
+    pub transaction_filters: watch::Sender<HashMap<String, SubscribeRequestFilterTransactions>>,
+    /// Set by `enable_recording`
+    pub recorder: Option<Arc<Recorder>>,
+    /// Reported by the gRPC sources, tagged on the events
+    pub transaction_indexes: TransactionIndexes,
 }
 
 impl TransactionsListener {
     pub fn new(
         buffer_size: usize,
         cache_capacity: usize,
-        grpc_urls: HashMap<String, (String, Option<String>)>,
+        source_configs: HashMap<String, SourceConfig>,
     ) -> CarbonResult<(Self, Receiver<Event>)> {
         let (sender, receiver) = mpsc::channel(buffer_size);
 
-        let mut events_cache = HashMap::new();
-        for platform in SwapPlatform::iter() {
-            events_cache.insert(
-                platform.to_string(),
-                Arc::new(RwLock::new(HashSet::with_capacity(cache_capacity))),
-            );
-        }
+        Ok((
+            Self::from_sender(
+                ListenerSender::Event(sender),
+                cache_capacity,
+                source_configs,
+            )?,
+            receiver,
+        ))
+    }
+
+    /// Same as `new`, but emits every event of a transaction at once, after
+    /// all monitors are done with it
+    pub fn new_with_transaction_events(
+        buffer_size: usize,
+        cache_capacity: usize,
+        source_configs: HashMap<String, SourceConfig>,
+    ) -> CarbonResult<(Self, Receiver<TransactionEvents>)> {
+        let (sender, receiver) = mpsc::channel(buffer_size);
 
         Ok((
-            Self {
-                sender,
-                grpc_urls,
-                pipeline_thread: None,
-                events_cache,
-            },
+            Self::from_sender(
+                ListenerSender::TransactionEvents(sender),
+                cache_capacity,
+                source_configs,
+            )?,
             receiver,
         ))
     }
 
+    fn from_sender(
+        sender: ListenerSender,
+        cache_capacity: usize,
+        source_configs: HashMap<String, SourceConfig>,
+    ) -> CarbonResult<Self> {
+        for source_config in source_configs.values() {
+            source_config
+                .validate()
+                .map_err(|error| Error::Custom(error.to_string()))?;
+        }
+
+        Ok(Self {
+            sender,
+            source_configs,
+            pipeline_thread: None,
+            events_cache: Arc::new(EventsCache::new(cache_capacity, Some(EVENTS_CACHE_TTL))),
+            sources: Sources::default(),
+            config: ListenerConfig::default(),
+            confirmations: None,
+            tracked_mints: TrackedMints::default(),
+            transaction_filters: watch::channel(HashMap::new()).0,
+            recorder: None,
+            transaction_indexes: TransactionIndexes::default(),
+        })
+    }
+
+    /// Reports each event emitted at processed commitment on the returned
+    /// receiver as its slot is confirmed, finalized or dropped, see
+    /// `Confirmations`. Takes effect on the next `run`, which fails unless the
+    /// commitment is processed.
+    pub fn enable_confirmations(&mut self, buffer_size: usize) -> Receiver<EventStatusUpdate> {
+        let (sender, receiver) = mpsc::channel(buffer_size);
+        self.confirmations = Some(Arc::new(Confirmations::new(sender)));
+
+        receiver
+    }
+
+    /// Writes every transaction update the gRPC sources receive to rotating
+    /// files in `directory`, for `replay`. Takes effect on the next `run` or
+    /// `add_source`.
+    pub fn enable_recording(&mut self, directory: PathBuf, max_file_size: u64) -> CarbonResult<()> {
+        self.recorder = Some(Arc::new(Recorder::new(directory, max_file_size)?));
+
+        Ok(())
+    }
+
     pub fn get_pipeline(
         &self,
-        sender: Sender<Event>,
+        sender: ListenerSender,
     ) -> CarbonResult<(CancellationToken, Pipeline)> {
+        if self.confirmations.is_some() && self.config.commitment != CommitmentLevel::Processed {
+            return Err(Error::Custom(format!(
+                "confirmations need processed commitment, not {}",
+                self.config.commitment.as_str_name()
+            )));
+        }
+
+        let (cancellation_token, pipeline, _) = self.get_pipeline_builder(
+            sender,
+            self.events_cache.clone(),
+            self.confirmations.clone(),
+            self.tracked_mints.clone(),
+            self.transaction_indexes.clone(),
+            None,
+        );
+
+        self.update_transaction_filters();
+        for (id, source_config) in self.source_configs.iter() {
+            let health = Arc::new(SourceHealth::default());
+            self.sources.add(
+                id.clone(),
+                self.get_source(source_config, health.clone()),
+                health,
+            );
+        }
+
+        Ok((
+            cancellation_token,
+            pipeline
+                .datasource(self.sources.clone())
+                .build()
+                .map_err(|error| Error::Custom(format!("build pipeline: {}", error)))?,
+        ))
+    }
+
+    /// Pipeline with the monitors of the enabled platforms, without datasource.
+    /// Instruction processors added to it run once the monitors are done with
+    /// a transaction, and find their errors on it in the returned
+    /// `MonitorErrors`. The replay clock, when set, runs before the monitors.
+    pub fn get_pipeline_builder(
+        &self,
+        sender: ListenerSender,
+        events_cache: Arc<EventsCache>,
+        confirmations: Option<Arc<Confirmations>>,
+        tracked_mints: TrackedMints,
+        transaction_indexes: TransactionIndexes,
+        replay_clock: Option<Arc<ReplayClock>>,
+    ) -> (CancellationToken, PipelineBuilder, MonitorErrors) {
+        // Every pipeline gets its own buffer
+        let (sender, transaction_events_monitor) = match sender {
+            ListenerSender::Event(sender) => (
+                EventSender::Event {
+                    sender,
+                    confirmations: confirmations.clone(),
+                    watchlist: self.config.wallets.clone(),
+                    tracked_mints: tracked_mints.clone(),
+                    transaction_indexes: transaction_indexes.clone(),
+                    replay_clock: replay_clock.clone(),
+                },
+                None,
+            ),
+            ListenerSender::TransactionEvents(sender) => {
+                let events = Arc::new(Mutex::new(Vec::new()));
+
+                (
+                    EventSender::Transaction {
+                        events: events.clone(),
+                        watchlist: self.config.wallets.clone(),
+                        tracked_mints: tracked_mints.clone(),
+                        transaction_indexes: transaction_indexes.clone(),
+                        replay_clock: replay_clock.clone(),
+                    },
+                    Some(TransactionEventsMonitor {
+                        sender,
+                        events,
+                        confirmations: confirmations.clone(),
+                    }),
+                )
+            }
+        };
+
         let cancellation_token = CancellationToken::new();
+        let monitor_errors = MonitorErrors::default();
         let mut pipeline = carbon_core::pipeline::Pipeline::builder()
             .datasource_cancellation_token(cancellation_token.clone())
-            .shutdown_strategy(carbon_core::pipeline::ShutdownStrategy::Immediate)
-            .instruction(
-                PumpfunDecoder,
-                pumpfun::PumpFunMonitor {
-                    sender: sender.clone(),
-                    parsed_events: self
-                        .events_cache
-                        .get(&SwapPlatform::PumpFun.to_string())
-                        .unwrap()
-                        .clone(),
-                },
-            )
-            .instruction(
-                RaydiumCpmmDecoder,
-                raydium_cpmm::RaydiumCpmmMonitor {
-                    sender: sender.clone(),
-                    parsed_events: self
-                        .events_cache
-                        .get(&SwapPlatform::RaydiumCpmm.to_string())
-                        .unwrap()
-                        .clone(),
+            .shutdown_strategy(carbon_core::pipeline::ShutdownStrategy::Immediate);
+
+        // Must come before every other monitor
+        if let Some(replay_clock) = replay_clock {
+            pipeline = pipeline.instruction(
+                ReplayClockDecoder,
+                ReplayClockMonitor {
+                    clock: replay_clock,
+                    instruction_positions: InstructionPositions::default(),
                 },
             );
+        }
 
-        for (_id, (url, x_token)) in self.grpc_urls.iter() {
-            let client = YellowstoneGrpcGeyserClient::new(
-                url.clone(),
-                x_token.clone(),
-                None,
-                HashMap::new(),
-                {
-                    let mut map = HashMap::new();
-                    map.insert(
-                        "subscribe_transactions".to_string(),
-                        SubscribeRequestFilterTransactions {
-                            vote: Some(false),
-                            failed: None,
-                            signature: None,
-                            account_include: vec![
-                                PUMPFUN.to_string(),
-                                RAYDIUM.to_string(),
-                                RAYDIUM_CPMM.to_string(),
-                                RAYDIUM_CLMM.to_string(),
-                            ],
-                            account_exclude: vec![],
-                            account_required: vec![],
+        let failed_swap_attempts = self.config.failed_swap_attempts;
+        for platform in SwapPlatform::iter() {
+            if !self.config.platforms.contains(&platform) {
+                continue;
+            }
+
+            pipeline = match platform {
+                SwapPlatform::PumpFun => pipeline.instruction(
+                    PumpfunDecoder,
+                    IsolatedMonitor {
+                        monitor: pumpfun::PumpFunMonitor {
+                            sender: sender.clone(),
+                            events_cache: events_cache.clone(),
+                            instruction_positions: InstructionPositions::default(),
+                            failed_swap_attempts,
+                        },
+                        errors: monitor_errors.clone(),
+                    },
+                ),
+                SwapPlatform::Raydium => pipeline.instruction(
+                    RaydiumAmmV4Decoder,
+                    IsolatedMonitor {
+                        monitor: raydium_amm_v4::RaydiumAmmV4Monitor {
+                            sender: sender.clone(),
+                            events_cache: events_cache.clone(),
+                            instruction_positions: InstructionPositions::default(),
+                            failed_swap_attempts,
+                            fee_rates: self.config.raydium_amm_v4_fee_rates,
+                        },
+                        errors: monitor_errors.clone(),
+                    },
+                ),
+                SwapPlatform::RaydiumCpmm => pipeline.instruction(
+                    RaydiumCpmmDecoder,
+                    IsolatedMonitor {
+                        monitor: raydium_cpmm::RaydiumCpmmMonitor {
+                            sender: sender.clone(),
+                            events_cache: events_cache.clone(),
+                            instruction_positions: InstructionPositions::default(),
+                            failed_swap_attempts,
+                            amm_configs: self.config.raydium_cpmm_amm_configs.clone(),
+                        },
+                        errors: monitor_errors.clone(),
+                    },
+                ),
+                SwapPlatform::RaydiumClmm => pipeline.instruction(
+                    RaydiumClmmDecoder,
+                    IsolatedMonitor {
+                        monitor: raydium_clmm::RaydiumClmmMonitor {
+                            sender: sender.clone(),
+                            events_cache: events_cache.clone(),
+                            instruction_positions: InstructionPositions::default(),
+                            failed_swap_attempts,
+                            amm_configs: self.config.raydium_clmm_amm_configs.clone(),
+                        },
+                        errors: monitor_errors.clone(),
+                    },
+                ),
+                SwapPlatform::MeteoraDlmm => pipeline.instruction(
+                    MeteoraDlmmDecoder,
+                    IsolatedMonitor {
+                        monitor: meteora_dlmm::MeteoraDlmmMonitor {
+                            sender: sender.clone(),
+                            events_cache: events_cache.clone(),
+                            instruction_positions: InstructionPositions::default(),
+                            failed_swap_attempts,
+                            bin_steps: HashMap::new(),
+                            bin_steps_order: VecDeque::new(),
+                            rpc_client: self.config.rpc_url.as_ref().map(|rpc_url| {
+                                Arc::new(RpcClient::new_with_timeout_and_commitment(
+                                    rpc_url.clone(),
+                                    METEORA_DLMM_LB_PAIR_FETCH_TIMEOUT,
+                                    get_commitment_config(self.config.commitment),
+                                ))
+                            }),
                         },
-                    );
+                        errors: monitor_errors.clone(),
+                    },
+                ),
+            };
+        }
 
-                    map
+        if self.config.associated_accounts_monitor {
+            pipeline = pipeline.instruction(
+                SplAssociatedTokenAccountDecoder,
+                IsolatedMonitor {
+                    monitor: ata::AssociatedTokenAccountMonitor {
+                        sender: sender.clone(),
+                        events_cache: events_cache.clone(),
+                        instruction_positions: InstructionPositions::default(),
+                    },
+                    errors: monitor_errors.clone(),
                 },
-                Arc::new(RwLock::new(HashSet::new())),
             );
+        }
 
-            pipeline = pipeline.datasource(client);
+        // Must come after every other monitor
+        if let Some(transaction_events_monitor) = transaction_events_monitor {
+            pipeline = pipeline.instruction(
+                TransactionEndDecoder,
+                IsolatedMonitor {
+                    monitor: transaction_events_monitor,
+                    errors: monitor_errors.clone(),
+                },
+            );
         }
 
-        Ok((
-            cancellation_token,
-            pipeline
-                .build()
-                .map_err(|error| Error::Custom(format!("build pipeline: {}", error)))?,
-        ))
+        (cancellation_token, pipeline, monitor_errors)
+    }
+
+    pub fn get_source(
+        &self,
+        source_config: &SourceConfig,
+        health: Arc<SourceHealth>,
+    ) -> Arc<dyn Datasource> {
+        match source_config {
+            SourceConfig::Grpc { url, x_token } => {
+                Arc::new(self.get_grpc_source(url.clone(), x_token.clone(), health))
+            }
+            SourceConfig::Rpc { rpc_url, ws_url } => {
+                Arc::new(self.get_rpc_source(rpc_url.clone(), ws_url.clone(), health))
+            }
+        }
+    }
+
+    pub fn get_rpc_source(
+        &self,
+        rpc_url: String,
+        ws_url: String,
+        health: Arc<SourceHealth>,
+    ) -> RpcSource {
+        RpcSource {
+            rpc_url,
+            ws_url,
+            programs: self.config.get_programs(),
+            transaction_filters: self.transaction_filters.subscribe(),
+            commitment: get_commitment_config(self.config.commitment),
+            failed_transactions: self.config.failed_swap_attempts,
+            health,
+        }
+    }
+
+    pub fn get_grpc_source(
+        &self,
+        url: String,
+        x_token: Option<String>,
+        health: Arc<SourceHealth>,
+    ) -> GrpcSource {
+        GrpcSource {
+            url,
+            x_token,
+            transaction_filters: self.transaction_filters.subscribe(),
+            commitment: self.config.commitment,
+            confirmations: self.confirmations.clone(),
+            health,
+            recorder: self.recorder.clone(),
+            transaction_indexes: self.transaction_indexes.clone(),
+            block_time_wait: self.config.block_time_wait,
+        }
     }
 
     pub fn get_pipeline_thread(
@@ -156,6 +484,9 @@ impl TransactionsListener {
     }
 
     pub fn stop(&mut self) {
+        // Reset right away, aborting the pipeline can drop its own cleanup
+        self.sources.stop();
+
         if let Some((cancellation_token, thread)) = self.pipeline_thread.take() {
             cancellation_token.cancel();
             thread.abort();
@@ -164,26 +495,309 @@ impl TransactionsListener {
         self.pipeline_thread = None;
     }
 
-    pub fn delete_grpc_url(&mut self, id: String) -> CarbonResult<()> {
-        self.grpc_urls.remove(&id);
-        let new_pipeline_thread = self.get_pipeline_thread()?;
-        self.stop();
-        self.pipeline_thread = Some(new_pipeline_thread);
+    /// Duplicates dropped and keys evicted by the events cache so far
+    pub fn events_cache_metrics(&self) -> EventsCacheMetrics {
+        self.events_cache.metrics()
+    }
+
+    /// Health of every source, by id
+    pub fn source_status(&self) -> HashMap<String, SourceStatus> {
+        self.sources.status()
+    }
+
+    /// Runs the monitors of the enabled platforms over the history of
+    /// `backfill.address`, newest first, in a pipeline of its own. Events come
+    /// out as live ones do, tagged from the watchlist but not sent to the
+    /// tracked mints. The receiver closes once the backfill is over, the
+    /// handle tells how it ended. Transactions a monitor fails on are logged
+    /// and checkpointed like the others, with the other monitors' events of
+    /// them sent, as the live pipeline does.
+    pub fn backfill(
+        &self,
+        buffer_size: usize,
+        backfill: Backfill,
+    ) -> CarbonResult<(Receiver<Event>, JoinHandle<CarbonResult<()>>)> {
+        let (sender, receiver) = mpsc::channel(buffer_size);
+        let (processed_sender, processed_receiver) = watch::channel(None);
+        let (result_sender, result_receiver) = oneshot::channel();
+
+        let (cancellation_token, pipeline, monitor_errors) = self.get_pipeline_builder(
+            ListenerSender::Event(sender.clone()),
+            Arc::new(EventsCache::new(BACKFILL_EVENTS_CACHE_CAPACITY, None)),
+            None,
+            TrackedMints::default(),
+            TransactionIndexes::default(),
+            None,
+        );
+        let mut pipeline = pipeline
+            .instruction(
+                TransactionEndDecoder,
+                BackfillMonitor {
+                    checkpoint_path: backfill.checkpoint_path.clone(),
+                    processed: processed_sender,
+                    monitor_errors,
+                },
+            )
+            .datasource(BackfillSource {
+                backfill,
+                commitment: get_commitment_config(self.config.commitment),
+                failed_transactions: self.config.failed_swap_attempts,
+                processed: processed_receiver,
+                result_sender: Mutex::new(Some(result_sender)),
+            })
+            .build()
+            .map_err(|error| Error::Custom(format!("build backfill pipeline: {}", error)))?;
+
+        Ok((
+            receiver,
+            tokio::spawn(async move {
+                let pipeline_thread = tokio::spawn(async move { pipeline.run().await });
+
+                let result = tokio::select! {
+                    result = result_receiver => match result {
+                        Ok(result) => result,
+                        Err(_) => Err(Error::Custom("backfill source dropped".to_string())),
+                    },
+                    _ = sender.closed() => {
+                        Err(Error::Custom("backfill receiver dropped".to_string()))
+                    }
+                };
+
+                cancellation_token.cancel();
+                pipeline_thread.abort();
+
+                result
+            }),
+        ))
+    }
+
+    /// Runs the monitors of the enabled platforms over recorded updates, in a
+    /// pipeline of its own with a fresh events cache. Events come out dated
+    /// as recorded, neither tagged from the watchlist nor sent to the tracked
+    /// mints or the confirmations, so that a recording always gives the same
+    /// events. The receiver closes once the monitors are done with every
+    /// update, the handle tells how the replay ended.
+    pub fn replay(
+        &self,
+        buffer_size: usize,
+        replay: Replay,
+    ) -> CarbonResult<(Receiver<Event>, JoinHandle<CarbonResult<()>>)> {
+        let (sender, receiver) = mpsc::channel(buffer_size);
+        let (result_sender, result_receiver) = oneshot::channel();
+        let clock = Arc::new(ReplayClock::default());
+        let transaction_indexes = TransactionIndexes::default();
+
+        let (cancellation_token, pipeline, _) = self.get_pipeline_builder(
+            ListenerSender::Event(sender.clone()),
+            Arc::new(EventsCache::new(replay.cache_capacity, None)),
+            None,
+            TrackedMints::default(),
+            transaction_indexes.clone(),
+            Some(clock.clone()),
+        );
+        let mut pipeline = pipeline
+            .instruction(
+                TransactionEndDecoder,
+                ReplayEndMonitor {
+                    clock: clock.clone(),
+                },
+            )
+            .datasource(ReplaySource {
+                paths: replay.paths,
+                speed: replay.speed,
+                clock,
+                transaction_indexes,
+                result_sender: Mutex::new(Some(result_sender)),
+            })
+            .build()
+            .map_err(|error| Error::Custom(format!("build replay pipeline: {}", error)))?;
+
+        Ok((
+            receiver,
+            tokio::spawn(async move {
+                let pipeline_thread = tokio::spawn(async move { pipeline.run().await });
+
+                let result = tokio::select! {
+                    result = result_receiver => match result {
+                        Ok(result) => result,
+                        Err(_) => Err(Error::Custom("replay source dropped".to_string())),
+                    },
+                    _ = sender.closed() => {
+                        Err(Error::Custom("replay receiver dropped".to_string()))
+                    }
+                };
+
+                cancellation_token.cancel();
+                pipeline_thread.abort();
+
+                result
+            }),
+        ))
+    }
+
+    /// Runs the monitors of the enabled platforms over a single transaction,
+    /// see `decode::parse_transaction`, in a pipeline of its own without
+    /// network access. Returns the events along with the transfers matched as
+    /// the pay and receive legs of swaps, and the errors of the monitors that
+    /// failed, which don't keep the others from running.
+    pub async fn decode(&self, update: Update) -> CarbonResult<Decoded> {
+        // Every event of the transaction fits
+        let (sender, mut receiver) = mpsc::channel(1_024);
+        let (processed_sender, mut processed_receiver) = watch::channel(None);
+
+        let (cancellation_token, pipeline, monitor_errors) = self.get_pipeline_builder(
+            ListenerSender::Event(sender),
+            Arc::new(EventsCache::new(BACKFILL_EVENTS_CACHE_CAPACITY, None)),
+            None,
+            TrackedMints::default(),
+            TransactionIndexes::default(),
+            None,
+        );
+        let mut pipeline = pipeline
+            .instruction(
+                TransactionEndDecoder,
+                BackfillMonitor {
+                    checkpoint_path: None,
+                    processed: processed_sender,
+                    monitor_errors,
+                },
+            )
+            .datasource(DecodeSource {
+                update: Mutex::new(Some(update)),
+            })
+            .build()
+            .map_err(|error| Error::Custom(format!("build decode pipeline: {}", error)))?;
+
+        let swap_transfers = Arc::new(Mutex::new(Vec::new()));
+        let result = SWAP_TRANSFERS_TRACE
+            .scope(swap_transfers.clone(), async {
+                tokio::select! {
+                    result = pipeline.run() => match result {
+                        Ok(()) => Err(Error::Custom("decode pipeline stopped".to_string())),
+                        Err(error) => Err(error),
+                    },
+                    processed = tokio::time::timeout(
+                        DECODE_TIMEOUT,
+                        processed_receiver.wait_for(|processed| processed.is_some()),
+                    ) => match processed {
+                        Ok(Ok(processed)) => Ok(processed
+                            .as_ref()
+                            .map(|processed| processed.errors.clone())
+                            .unwrap_or_default()),
+                        // Carbon drops the updates it can't parse
+                        _ => Err(Error::Custom("transaction not processed".to_string())),
+                    },
+                }
+            })
+            .await;
+        cancellation_token.cancel();
+        let errors = result?;
+
+        let mut events = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            events.push(event);
+        }
+
+        let swap_transfers = std::mem::take(&mut *swap_transfers.lock().unwrap());
+
+        Ok(Decoded {
+            events,
+            swap_transfers,
+            errors,
+        })
+    }
+
+    /// Stops the source, the pipeline and the other sources keep running
+    pub fn delete_source(&mut self, id: String) -> Result<(), SourceError> {
+        if self.source_configs.remove(&id).is_none() {
+            return Err(SourceError::UnknownSource(id));
+        }
+
+        // Not registered yet if the listener never ran
+        let _ = self.sources.remove(&id);
 
         Ok(())
     }
 
-    pub fn add_grpc_url(
+    /// Starts the source right away if the listener is running, replacing the
+    /// one with the same id
+    pub fn add_source(
         &mut self,
         id: String,
-        url: String,
-        x_token: Option<String>,
-    ) -> CarbonResult<()> {
-        self.grpc_urls.insert(id, (url, x_token));
-        let new_pipeline_thread = self.get_pipeline_thread()?;
-        self.stop();
-        self.pipeline_thread = Some(new_pipeline_thread);
+        source_config: SourceConfig,
+    ) -> Result<(), SourceError> {
+        source_config.validate()?;
+
+        self.update_transaction_filters();
+        let health = Arc::new(SourceHealth::default());
+        let source = self.get_source(&source_config, health.clone());
+        self.source_configs.insert(id.clone(), source_config);
+        self.sources.add(id, source, health);
 
         Ok(())
     }
+
+    /// Sends the transaction filters of `config` to the running gRPC sources,
+    /// which update their subscription without reconnecting
+    pub fn update_transaction_filters(&self) {
+        self.transaction_filters
+            .send_replace(self.config.get_transaction_filters());
+    }
+
+    /// Only transactions involving watched wallets or tracked mints are
+    /// subscribed to while the watchlist isn't empty, and events of a watched
+    /// user are tagged with its entry. Replaces the entry of the same wallet, if any.
+    pub fn add_watched_wallet(
+        &self,
+        wallet: Pubkey,
+        label: Option<String>,
+    ) -> Option<WatchlistEntry> {
+        let previous_entry = self.config.wallets.add(WatchlistEntry { wallet, label });
+        if previous_entry.is_none() {
+            self.update_transaction_filters();
+        }
+
+        previous_entry
+    }
+
+    pub fn remove_watched_wallet(&self, wallet: &Pubkey) -> Option<WatchlistEntry> {
+        let entry = self.config.wallets.remove(wallet);
+        if entry.is_some() {
+            self.update_transaction_filters();
+        }
+
+        entry
+    }
+
+    pub fn watched_wallets(&self) -> Vec<WatchlistEntry> {
+        self.config.wallets.list()
+    }
+
+    /// Subscribes to the mint's transactions on every enabled platform, and
+    /// sends its events to the returned receiver on top of the listener's.
+    /// Once a mint is tracked, only transactions involving tracked mints or
+    /// watched wallets are subscribed to.
+    /// Tracking the mint again replaces its receiver. Events are dropped while
+    /// the receiver is full.
+    pub fn track_mint(&mut self, mint: Pubkey) -> Receiver<Event> {
+        let (sender, receiver) = mpsc::channel(TRACKED_MINT_BUFFER_SIZE);
+        self.tracked_mints.add(mint, sender);
+
+        if self.config.mints.insert(mint) {
+            self.update_transaction_filters();
+        }
+
+        receiver
+    }
+
+    /// Closes the mint's receiver and stops subscribing to it
+    pub fn untrack_mint(&mut self, mint: &Pubkey) -> bool {
+        let tracked = self.tracked_mints.remove(mint);
+
+        if self.config.mints.remove(mint) {
+            self.update_transaction_filters();
+        }
+
+        tracked
+    }
 }
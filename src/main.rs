@@ -1,15 +1,181 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    path::PathBuf,
+};
 
-use tracing::info;
+use clap::{Parser, Subcommand, ValueEnum};
+use strum::IntoEnumIterator;
 use tracing::level_filters::LevelFilter;
-use tracing_subscriber::{EnvFilter, layer::SubscriberExt, util::SubscriberInitExt};
-use transactions_listener::{TransactionsListener, types::EventType};
+use tracing_subscriber::{EnvFilter, Layer, layer::SubscriberExt, util::SubscriberInitExt};
+use transactions_listener::{
+    TransactionsListener,
+    decode::{Decoded, parse_transaction},
+    sources::SourceConfig,
+    types::{Event, SwapPlatform},
+};
+use yellowstone_grpc_proto::geyser::CommitmentLevel;
+
+#[derive(Parser)]
+#[command(subcommand_negates_reqs = true)]
+struct Cli {
+    /// Listens to the sources when omitted
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Source, repeatable: Yellowstone gRPC as `id=url[,x_token]` or
+    /// `id=grpc:url[,x_token]`, JSON-RPC as `id=rpc:url[,ws_url]`, the
+    /// websocket url defaulting to the RPC one
+    #[arg(
+        long = "source",
+        value_name = "ID=[grpc:|rpc:]URL[,TOKEN|,WS_URL]",
+        value_parser = parse_source,
+        required = true
+    )]
+    sources: Vec<(String, SourceConfig)>,
+
+    /// Platform to monitor, repeatable, every platform when omitted: pump_fun,
+    /// raydium, raydium_cpmm, raydium_clmm or meteora_dlmm
+    #[arg(
+        long = "platform",
+        value_name = "PLATFORM",
+        value_parser = parse_platform,
+        global = true
+    )]
+    platforms: Vec<SwapPlatform>,
+
+    /// processed, confirmed or finalized
+    #[arg(
+        long,
+        value_parser = parse_commitment,
+        default_value = "processed",
+        global = true
+    )]
+    commitment: CommitmentLevel,
+
+    /// RPC node to look up the bin step of Meteora DLMM pairs created before
+    /// the start, which have no bin price otherwise
+    #[arg(long, global = true)]
+    rpc_url: Option<String>,
+
+    /// Events buffered for the output
+    #[arg(long, default_value_t = 128, global = true)]
+    buffer_size: usize,
+
+    /// Events remembered to drop duplicates across sources
+    #[arg(long, default_value_t = 255, global = true)]
+    cache_size: usize,
+
+    /// Logs go to stderr
+    #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+
+    /// Events go to stdout. Protobuf, where every event is length-delimited,
+    /// needs a build with `--features proto`
+    #[arg(long, value_enum, default_value_t = OutputFormat::Pretty)]
+    output: OutputFormat,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Prints what the monitors emit for a single transaction, offline
+    Decode {
+        /// `getTransaction` response, or only its result, json, base64 or base58
+        /// encoded. Read from stdin when omitted
+        path: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Pretty,
+    JsonLines,
+    #[cfg(feature = "proto")]
+    Protobuf,
+}
+
+fn parse_source(source: &str) -> Result<(String, SourceConfig), String> {
+    let (id, url) = match source.split_once('=') {
+        Some((id, url)) if !id.is_empty() && !url.is_empty() => (id, url),
+        _ => {
+            return Err("expected id=[grpc:|rpc:]url[,token|,ws_url]".to_string());
+        }
+    };
+
+    let source_config = match url.strip_prefix("rpc:") {
+        Some(rpc_url) => {
+            let (rpc_url, ws_url) = match rpc_url.split_once(',') {
+                Some((rpc_url, ws_url)) => (rpc_url, ws_url.to_string()),
+                None => (rpc_url, get_ws_url(rpc_url)),
+            };
+
+            SourceConfig::Rpc {
+                rpc_url: rpc_url.to_string(),
+                ws_url,
+            }
+        }
+        None => {
+            let url = url.strip_prefix("grpc:").unwrap_or(url);
+            let (url, x_token) = match url.split_once(',') {
+                Some((url, x_token)) => (url, Some(x_token.to_string())),
+                None => (url, None),
+            };
+
+            SourceConfig::Grpc {
+                url: url.to_string(),
+                x_token,
+            }
+        }
+    };
+    source_config
+        .validate()
+        .map_err(|error| error.to_string())?;
+
+    Ok((id.to_string(), source_config))
+}
+
+/// Same host and path, over websocket
+fn get_ws_url(rpc_url: &str) -> String {
+    match rpc_url.strip_prefix("http") {
+        Some(rest) => format!("ws{}", rest),
+        None => rpc_url.to_string(),
+    }
+}
+
+fn parse_platform(platform: &str) -> Result<SwapPlatform, String> {
+    let name = platform.replace('_', "");
+    match SwapPlatform::iter().find(|platform| platform.to_string().eq_ignore_ascii_case(&name)) {
+        Some(platform) => Ok(platform),
+        None => Err(format!("unknown platform {}", platform)),
+    }
+}
+
+fn parse_commitment(commitment: &str) -> Result<CommitmentLevel, String> {
+    match CommitmentLevel::from_str_name(&commitment.to_uppercase()) {
+        Some(commitment) => Ok(commitment),
+        None => Err(format!("unknown commitment {}", commitment)),
+    }
+}
 
 #[tokio::main]
 async fn main() {
+    let mut cli = Cli::parse();
+
+    // Output goes to stdout
     let layer = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
         .with_line_number(true)
         .with_target(true);
+    let layer = match cli.log_format {
+        LogFormat::Text => layer.boxed(),
+        LogFormat::Json => layer.json().boxed(),
+    };
 
     tracing_subscriber::registry()
         .with(layer)
@@ -20,38 +186,125 @@ async fn main() {
         )
         .init();
 
-    // TODO: read using cli args
-    let grpc_url = "https://nyc.grpc.gadflynode.com:443";
-    let x_token = None;
+    match cli.command.take() {
+        Some(Command::Decode { path }) => decode(path, &cli).await,
+        None => listen(cli).await,
+    }
+}
 
-    let grpc_urls = {
-        let mut map = HashMap::new();
-        map.insert(
-            "example gRPC id".to_string(),
-            (grpc_url.to_string(), x_token),
+/// Exits with an error when the transaction can't be read or a monitor failed
+/// on it, after printing what the others made of it
+async fn decode(path: Option<PathBuf>, cli: &Cli) {
+    let decoded = match get_decoded(path, cli).await {
+        Ok(decoded) => decoded,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+
+    println!("Events: {:#?}", decoded.events);
+    for swap_transfers in decoded.swap_transfers {
+        println!(
+            "Transfers from {} to {}:",
+            swap_transfers.input_token_account, swap_transfers.output_token_account
         );
-        map
+        for (index, transfer) in swap_transfers.transfers.iter().enumerate() {
+            let leg = if swap_transfers.pay_transfer == Some(index) {
+                "pay"
+            } else if swap_transfers.receive_transfer == Some(index) {
+                "receive"
+            } else {
+                "-"
+            };
+
+            println!(
+                "  {:<7} {} {} from {} to {} by {}",
+                leg,
+                transfer.info.amount,
+                transfer.mint,
+                transfer.info.source,
+                transfer.info.destination,
+                transfer.info.authority
+            );
+        }
+    }
+
+    if !decoded.errors.is_empty() {
+        for error in decoded.errors {
+            eprintln!("monitor failed: {}", error);
+        }
+        std::process::exit(1);
+    }
+}
+
+/// Every monitor of the platforms runs, those of failed transactions included
+async fn get_decoded(path: Option<PathBuf>, cli: &Cli) -> Result<Decoded, String> {
+    let json = match path {
+        Some(path) => std::fs::read_to_string(&path)
+            .map_err(|error| format!("read {}: {}", path.display(), error))?,
+        None => {
+            let mut json = String::new();
+            std::io::stdin()
+                .read_to_string(&mut json)
+                .map_err(|error| format!("read stdin: {}", error))?;
+            json
+        }
     };
 
-    let (mut transactions_listener, mut events_receiver) =
-        TransactionsListener::new(128, 255, grpc_urls).unwrap();
+    let update = parse_transaction(&json).map_err(|error| error.to_string())?;
+    let (mut transactions_listener, _events_receiver) =
+        TransactionsListener::new(128, 255, HashMap::new()).map_err(|error| error.to_string())?;
+    configure(&mut transactions_listener, cli);
+    transactions_listener.config.associated_accounts_monitor = true;
+    transactions_listener.config.failed_swap_attempts = true;
+
+    transactions_listener
+        .decode(update)
+        .await
+        .map_err(|error| error.to_string())
+}
+
+/// Exits with an error when the listener can't start
+async fn listen(mut cli: Cli) {
+    let (mut transactions_listener, mut events_receiver) = match TransactionsListener::new(
+        cli.buffer_size,
+        cli.cache_size,
+        std::mem::take(&mut cli.sources).into_iter().collect(),
+    ) {
+        Ok(transactions_listener) => transactions_listener,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
 
-    transactions_listener.run().unwrap();
+    configure(&mut transactions_listener, &cli);
 
+    if let Err(error) = transactions_listener.run() {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+
+    let mut stdout = std::io::stdout().lock();
     loop {
         tokio::select! {
             event = events_receiver.recv() => {
-                if let Some(event) = event {
-                    match event.event_type {
-                        EventType::Swap(swap) => {
-                            info!("Received swap event: {:#?}", swap);
-                        }
-                        EventType::PoolCreation { .. } => {
-                            info!("Received pool creation event: {:#?}", event);
+                match event {
+                    Some(event) => match write_event(&mut stdout, cli.output, event) {
+                        Ok(()) => {}
+                        // The reader of the output is gone, as with `| head`
+                        Err(error) if error.kind() == io::ErrorKind::BrokenPipe => {
+                            break;
                         }
-                        EventType::AssociatedAccountCreation { .. } => {
-                            info!("Received associated account creation event: {:#?}", event);
+                        Err(error) => {
+                            eprintln!("write event: {}", error);
+                            transactions_listener.stop();
+                            std::process::exit(1);
                         }
+                    },
+                    None => {
+                        break;
                     }
                 }
             }
@@ -60,4 +313,149 @@ async fn main() {
             }
         }
     }
+
+    transactions_listener.stop();
+}
+
+fn configure(transactions_listener: &mut TransactionsListener, cli: &Cli) {
+    if !cli.platforms.is_empty() {
+        transactions_listener.config.platforms = cli.platforms.iter().cloned().collect();
+    }
+    transactions_listener.config.commitment = cli.commitment;
+    transactions_listener.config.rpc_url = cli.rpc_url.clone();
+}
+
+fn write_event(stdout: &mut impl Write, output: OutputFormat, event: Event) -> io::Result<()> {
+    match output {
+        OutputFormat::Pretty => {
+            writeln!(stdout, "{:#?}", event)?;
+        }
+        OutputFormat::JsonLines => {
+            writeln!(stdout, "{}", serde_json::to_string(&event)?)?;
+        }
+        #[cfg(feature = "proto")]
+        OutputFormat::Protobuf => {
+            use prost::Message;
+
+            let event = transactions_listener::proto::Event::from(event);
+            stdout.write_all(&event.encode_length_delimited_to_vec())?;
+        }
+    }
+
+    stdout.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_source() {
+        let cases = [
+            (
+                "a=https://grpc.example.com",
+                SourceConfig::Grpc {
+                    url: "https://grpc.example.com".to_string(),
+                    x_token: None,
+                },
+            ),
+            (
+                "a=https://grpc.example.com,token",
+                SourceConfig::Grpc {
+                    url: "https://grpc.example.com".to_string(),
+                    x_token: Some("token".to_string()),
+                },
+            ),
+            (
+                "a=grpc:http://127.0.0.1:10000,token",
+                SourceConfig::Grpc {
+                    url: "http://127.0.0.1:10000".to_string(),
+                    x_token: Some("token".to_string()),
+                },
+            ),
+            (
+                "a=rpc:https://rpc.example.com/key",
+                SourceConfig::Rpc {
+                    rpc_url: "https://rpc.example.com/key".to_string(),
+                    ws_url: "wss://rpc.example.com/key".to_string(),
+                },
+            ),
+            (
+                "a=rpc:http://127.0.0.1:8899,ws://127.0.0.1:8900",
+                SourceConfig::Rpc {
+                    rpc_url: "http://127.0.0.1:8899".to_string(),
+                    ws_url: "ws://127.0.0.1:8900".to_string(),
+                },
+            ),
+        ];
+        for (source, source_config) in cases {
+            assert_eq!(
+                parse_source(source),
+                Ok(("a".to_string(), source_config)),
+                "{}",
+                source
+            );
+        }
+
+        for source in [
+            "https://grpc.example.com",
+            "=https://grpc.example.com",
+            "a=",
+            "a=grpc.example.com",
+            "a=rpc:rpc.example.com",
+            "a=rpc:https://rpc.example.com,https://rpc.example.com",
+        ] {
+            assert!(parse_source(source).is_err(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_parse_platform() {
+        let cases = [
+            ("pump_fun", SwapPlatform::PumpFun),
+            ("raydium", SwapPlatform::Raydium),
+            ("raydium_cpmm", SwapPlatform::RaydiumCpmm),
+            ("raydium_clmm", SwapPlatform::RaydiumClmm),
+            ("meteora_dlmm", SwapPlatform::MeteoraDlmm),
+            ("MeteoraDlmm", SwapPlatform::MeteoraDlmm),
+        ];
+        for (name, platform) in cases {
+            assert_eq!(parse_platform(name), Ok(platform), "{}", name);
+        }
+
+        assert!(parse_platform("orca").is_err());
+    }
+
+    #[test]
+    fn test_parse_commitment() {
+        assert_eq!(
+            parse_commitment("processed"),
+            Ok(CommitmentLevel::Processed)
+        );
+        assert_eq!(
+            parse_commitment("Confirmed"),
+            Ok(CommitmentLevel::Confirmed)
+        );
+        assert_eq!(
+            parse_commitment("finalized"),
+            Ok(CommitmentLevel::Finalized)
+        );
+        assert!(parse_commitment("final").is_err());
+    }
+
+    #[test]
+    fn test_global_options_follow_subcommand() {
+        let cli = Cli::try_parse_from([
+            "transactions-listener",
+            "decode",
+            "--buffer-size",
+            "16",
+            "--cache-size",
+            "32",
+        ])
+        .unwrap();
+
+        assert!(matches!(cli.command, Some(Command::Decode { path: None })));
+        assert_eq!((cli.buffer_size, cli.cache_size), (16, 32));
+    }
 }
@@ -0,0 +1,524 @@
+use std::collections::VecDeque;
+
+use carbon_core::{
+    deserialize::{ArrangeAccounts, CarbonDeserialize},
+    error::Error,
+    instruction::{DecodedInstruction, InstructionDecoder},
+    transaction::TransactionMetadata,
+};
+use carbon_meteora_dlmm_decoder::{
+    MeteoraDlmmDecoder,
+    accounts::lb_pair::LbPair,
+    instructions::{
+        MeteoraDlmmInstruction, initialize_lb_pair::InitializeLbPair, swap::Swap as DlmmSwap,
+        swap_event::SwapEvent,
+    },
+};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    constants::{METEORA_DLMM_BIN_STEPS_CAPACITY, SOLANA_PUBKEY},
+    transfer::{SwapTransfers, extract_swap_transfers, get_token_account_mint},
+    types::{FailedSwapAttempt, Fee, PoolState, Swap, SwapAmount, SwapFees},
+    utils::get_now_timestamp_millis,
+};
+
+use super::*;
+
+pub struct MeteoraDlmmMonitor {
+    pub sender: EventSender,
+    pub events_cache: Arc<EventsCache>,
+    pub instruction_positions: InstructionPositions,
+    /// Emit `FailedSwapAttempt` events for failed transactions
+    pub failed_swap_attempts: bool,
+    /// Bin step of the latest pairs seen created or looked up. Swaps carry
+    /// neither the pair account data nor its bin step
+    pub bin_steps: HashMap<Pubkey, u16>,
+    /// Insertion order of `bin_steps`, oldest evicted first
+    pub bin_steps_order: VecDeque<Pubkey>,
+    /// Looks up the pairs created before the listener started, once each. They
+    /// have no bin price without it
+    pub rpc_client: Option<Arc<RpcClient>>,
+}
+
+#[tonic::async_trait]
+impl Processor for MeteoraDlmmMonitor {
+    type InputType = InstructionProcessorInputType<MeteoraDlmmInstruction>;
+
+    async fn process(
+        &mut self,
+        (metadata, instruction, nested_instructions): Self::InputType,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let received_at = get_now_timestamp_millis();
+
+        let failed = metadata.transaction_metadata.meta.status.is_err();
+        if failed && !self.failed_swap_attempts {
+            return Ok(());
+        }
+
+        let (event_type, user) = match instruction.data {
+            // Failed transactions leave nothing but the instruction behind
+            _ if failed => {
+                match get_failed_swap_attempt(&metadata.transaction_metadata, &instruction)? {
+                    Some((failed_swap_attempt, user)) => (
+                        EventType::FailedSwapAttempt(Box::new(failed_swap_attempt)),
+                        user,
+                    ),
+                    None => {
+                        return Ok(());
+                    }
+                }
+            }
+            MeteoraDlmmInstruction::Swap(_)
+            | MeteoraDlmmInstruction::SwapExactOut(_)
+            | MeteoraDlmmInstruction::SwapWithPriceImpact(_) => {
+                // All swap variants share the same account layout
+                let accounts = match DlmmSwap::arrange_accounts(&instruction.accounts) {
+                    Some(accounts) => accounts,
+                    None => {
+                        return Err(Error::Custom(
+                            "meteora dlmm swap accounts not found".to_string(),
+                        ));
+                    }
+                };
+
+                let swap_event = match nested_instructions.iter().find_map(|nested_instruction| {
+                    match MeteoraDlmmDecoder.decode_instruction(&nested_instruction.instruction) {
+                        Some(decoded_instruction) => match decoded_instruction.data {
+                            MeteoraDlmmInstruction::SwapEvent(swap_event) => Some(swap_event),
+                            _ => None,
+                        },
+                        None => None,
+                    }
+                }) {
+                    Some(swap_event) => swap_event,
+                    None => {
+                        return Err(Error::Custom(
+                            "meteora dlmm swap event not found".to_string(),
+                        ));
+                    }
+                };
+
+                let SwapTransfers {
+                    pay_transfer,
+                    receive_transfer,
+                    ..
+                } = extract_swap_transfers(
+                    &metadata.transaction_metadata,
+                    nested_instructions.iter(),
+                    &accounts.user_token_in,
+                    &accounts.user_token_out,
+                )?;
+
+                let token_in_mint = pay_transfer.get_mint()?;
+
+                (
+                    EventType::Swap(Box::new(Swap {
+                        token_in_amount: pay_transfer.info.amount,
+                        token_in_decimals: pay_transfer.decimals,
+                        token_in_mint,
+                        token_out_amount: receive_transfer.info.amount,
+                        token_out_decimals: receive_transfer.decimals,
+                        token_out_mint: receive_transfer.get_mint()?,
+                        platform: SwapPlatform::MeteoraDlmm,
+                        pool: accounts.lb_pair,
+                        base_vault: accounts.reserve_x,
+                        quote_vault: accounts.reserve_y,
+                        pool_state: self.get_pool_state(&swap_event).await,
+                        fees: Some(get_swap_fees(&swap_event, token_in_mint)),
+                    })),
+                    accounts.user,
+                )
+            }
+            MeteoraDlmmInstruction::InitializeLbPair(initialize_lb_pair) => {
+                let accounts = match InitializeLbPair::arrange_accounts(&instruction.accounts) {
+                    Some(accounts) => accounts,
+                    None => {
+                        return Err(Error::Custom(
+                            "meteora dlmm initialize lb pair accounts not found".to_string(),
+                        ));
+                    }
+                };
+
+                self.insert_bin_step(accounts.lb_pair, initialize_lb_pair.bin_step);
+
+                // Most pairs are quoted in wrapped SOL, report the other side
+                let mint = if accounts.token_mint_x == SOLANA_PUBKEY {
+                    accounts.token_mint_y
+                } else {
+                    accounts.token_mint_x
+                };
+
+                (
+                    EventType::PoolCreation {
+                        mint,
+                        platform: SwapPlatform::MeteoraDlmm,
+                    },
+                    accounts.funder,
+                )
+            }
+            // Emitted by every pair initialization flavour, permissioned ones included
+            MeteoraDlmmInstruction::LbPairCreateEvent(lb_pair_create_event) => {
+                self.insert_bin_step(lb_pair_create_event.lb_pair, lb_pair_create_event.bin_step);
+
+                return Ok(());
+            }
+            _ => {
+                return Ok(());
+            }
+        };
+
+        let position = self.instruction_positions.resolve(
+            &metadata,
+            &instruction.program_id,
+            &instruction.accounts,
+        );
+        let event = Event::new(
+            &metadata.transaction_metadata,
+            position,
+            event_type,
+            user,
+            received_at,
+        );
+
+        if !self.events_cache.insert(&event) {
+            return Ok(());
+        }
+
+        self.sender.send(event).await.map_err(|error| {
+            Error::Custom(format!("send meteora dlmm event to receiver: {}", error))
+        })
+    }
+}
+
+impl MeteoraDlmmMonitor {
+    fn insert_bin_step(&mut self, lb_pair: Pubkey, bin_step: u16) {
+        if self.bin_steps.insert(lb_pair, bin_step).is_some() {
+            return;
+        }
+
+        self.bin_steps_order.push_back(lb_pair);
+        if self.bin_steps_order.len() > METEORA_DLMM_BIN_STEPS_CAPACITY
+            && let Some(lb_pair) = self.bin_steps_order.pop_front()
+        {
+            self.bin_steps.remove(&lb_pair);
+        }
+    }
+
+    /// Failed lookups aren't remembered, the next swap of the pair tries again
+    async fn get_bin_step(&mut self, lb_pair: &Pubkey) -> Option<u16> {
+        if let Some(bin_step) = self.bin_steps.get(lb_pair) {
+            return Some(*bin_step);
+        }
+
+        let rpc_client = self.rpc_client.as_ref()?;
+        let data = match rpc_client.get_account_data(lb_pair).await {
+            Ok(data) => data,
+            Err(error) => {
+                tracing::debug!("fetch meteora dlmm lb pair {}: {}", lb_pair, error);
+                return None;
+            }
+        };
+        let bin_step = match LbPair::deserialize(&data) {
+            Some(lb_pair_account) => lb_pair_account.bin_step,
+            None => {
+                tracing::debug!("decode meteora dlmm lb pair {}", lb_pair);
+                return None;
+            }
+        };
+
+        self.insert_bin_step(*lb_pair, bin_step);
+        Some(bin_step)
+    }
+
+    async fn get_pool_state(&mut self, swap_event: &SwapEvent) -> PoolState {
+        let bin_price_x64 = self
+            .get_bin_step(&swap_event.lb_pair)
+            .await
+            .and_then(|bin_step| get_bin_price_x64(swap_event.end_bin_id, bin_step));
+
+        PoolState::LiquidityBins {
+            active_bin_id: swap_event.end_bin_id,
+            bin_price_x64,
+        }
+    }
+}
+
+fn get_failed_swap_attempt(
+    transaction_metadata: &TransactionMetadata,
+    instruction: &DecodedInstruction<MeteoraDlmmInstruction>,
+) -> CarbonResult<Option<(FailedSwapAttempt, Pubkey)>> {
+    let amount = match &instruction.data {
+        MeteoraDlmmInstruction::Swap(swap) => SwapAmount::ExactIn {
+            amount_in: swap.amount_in,
+            minimum_amount_out: Some(swap.min_amount_out),
+        },
+        MeteoraDlmmInstruction::SwapExactOut(swap_exact_out) => SwapAmount::ExactOut {
+            amount_out: swap_exact_out.out_amount,
+            maximum_amount_in: swap_exact_out.max_in_amount,
+        },
+        MeteoraDlmmInstruction::SwapWithPriceImpact(swap_with_price_impact) => {
+            SwapAmount::ExactIn {
+                amount_in: swap_with_price_impact.amount_in,
+                minimum_amount_out: None,
+            }
+        }
+        _ => {
+            return Ok(None);
+        }
+    };
+
+    let accounts = match DlmmSwap::arrange_accounts(&instruction.accounts) {
+        Some(accounts) => accounts,
+        None => {
+            return Err(Error::Custom(
+                "meteora dlmm swap accounts not found".to_string(),
+            ));
+        }
+    };
+
+    Ok(Some((
+        FailedSwapAttempt::new(
+            transaction_metadata,
+            SwapPlatform::MeteoraDlmm,
+            accounts.lb_pair,
+            get_token_account_mint(transaction_metadata, &accounts.user_token_in),
+            get_token_account_mint(transaction_metadata, &accounts.user_token_out),
+            amount,
+        ),
+        accounts.user,
+    )))
+}
+
+/// Fees are charged on the input token, the protocol fee is part of the total
+/// fee and includes the host fee
+fn get_swap_fees(swap_event: &SwapEvent, mint: Pubkey) -> SwapFees {
+    SwapFees {
+        lp_fee: Some(Fee {
+            amount: swap_event.fee.saturating_sub(swap_event.protocol_fee),
+            mint,
+        }),
+        protocol_fee: Some(Fee {
+            amount: swap_event.protocol_fee,
+            mint,
+        }),
+        creator_fee: None,
+        fund_fee: None,
+    }
+}
+
+/// Price of the bin as `(1 + bin_step / 10_000) ^ bin_id` in Q64.64, computed
+/// in fixed point the way the program does so that prices match to the bit.
+/// `None` where the program would overflow
+fn get_bin_price_x64(bin_id: i32, bin_step: u16) -> Option<u128> {
+    const ONE: u128 = 1 << 64;
+    const BASIS_POINT_MAX: u128 = 10_000;
+    const MAX_EXPONENTIAL: u32 = 0x80000;
+
+    let base = ONE + ((bin_step as u128) << 64) / BASIS_POINT_MAX;
+    if bin_id == 0 {
+        return Some(ONE);
+    }
+
+    let exponent = bin_id.unsigned_abs();
+    if exponent >= MAX_EXPONENTIAL {
+        return None;
+    }
+
+    // Raised below one so that squaring never overflows, inverted back after
+    let mut invert = bin_id < 0;
+    let mut squared_base = base;
+    if squared_base >= ONE {
+        squared_base = u128::MAX / squared_base;
+        invert = !invert;
+    }
+
+    let mut result = ONE;
+    for bit in 0..MAX_EXPONENTIAL.trailing_zeros() {
+        if exponent & (1 << bit) > 0 {
+            result = result.checked_mul(squared_base)? >> 64;
+        }
+        squared_base = squared_base.checked_mul(squared_base)? >> 64;
+    }
+
+    if result == 0 {
+        return None;
+    }
+    if invert {
+        result = u128::MAX / result;
+    }
+
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{Engine, engine::general_purpose::STANDARD};
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::{
+        TransactionsListener,
+        constants::programs,
+        decode::parse_transaction,
+        mock_rpc::{MockRpc, load_fixture},
+    };
+
+    const ONE: u128 = 1 << 64;
+
+    const USER: Pubkey = solana_sdk::pubkey!("2KKcpJe4zmYdYHL8aKDcEt9KpxJ6ZTbY1zUnZgnnVEst");
+    const MINT: Pubkey = solana_sdk::pubkey!("g7jKqK1D8dgc965tTMm31FKxa4o7HXGbdLNfCw7r3Rq");
+    const LB_PAIR: Pubkey = solana_sdk::pubkey!("HoBacFacrxx3PCSZREbSKVCNt3Zw9ut5a4VpwRyesy1y");
+    const RESERVE_X: Pubkey = solana_sdk::pubkey!("HmU1NKgeHPXy79ZeNwNtC7p4sAGDBXYpwt5PoeBgSNw2");
+    const RESERVE_Y: Pubkey = solana_sdk::pubkey!("EEXtkueoYE9NtQCQyYACt8yEzCJDKXcGbPCHYeNcpNey");
+
+    #[test]
+    fn test_bin_price_of_bin_zero_is_one() {
+        assert_eq!(get_bin_price_x64(0, 1), Some(ONE));
+        assert_eq!(get_bin_price_x64(0, 100), Some(ONE));
+    }
+
+    #[test]
+    fn test_bin_price_of_positive_bins() {
+        // 1.001
+        assert_eq!(get_bin_price_x64(1, 10), Some(18_465_190_817_783_261_167));
+        // 1.0025 ^ 100 = 1.2836248887...
+        assert_eq!(get_bin_price_x64(100, 25), Some(23_678_699_809_202_413_098));
+        // 1.0001 ^ 5000 = 1.6486800559...
+        assert_eq!(
+            get_bin_price_x64(5_000, 1),
+            Some(30_412_779_051_191_554_362)
+        );
+    }
+
+    #[test]
+    fn test_bin_price_of_negative_bins() {
+        // 1 / 1.001
+        assert_eq!(get_bin_price_x64(-1, 10), Some(18_428_315_757_951_600_016));
+        // 1.0025 ^ -100 = 0.7790437913...
+        assert_eq!(
+            get_bin_price_x64(-100, 25),
+            Some(14_370_821_441_331_513_819)
+        );
+        // 1.0001 ^ -5000 = 0.6065458221...
+        assert_eq!(
+            get_bin_price_x64(-5_000, 1),
+            Some(11_188_795_550_323_323_883)
+        );
+    }
+
+    #[test]
+    fn test_bin_price_overflow() {
+        // 1.01 ^ 4456 is just below 2 ^ 64, the largest Q64.64 integer part
+        assert_eq!(get_bin_price_x64(4_456, 100), Some(u128::MAX));
+        assert_eq!(get_bin_price_x64(4_457, 100), None);
+        assert_eq!(get_bin_price_x64(-4_456, 100), Some(1));
+        assert_eq!(get_bin_price_x64(-4_457, 100), None);
+
+        // The program rejects exponents from 0x80000 on, whatever the bin step
+        assert_eq!(get_bin_price_x64(0x80000, 1), None);
+        assert_eq!(get_bin_price_x64(-0x80000, 1), None);
+    }
+
+    async fn decode_swap(rpc_url: Option<String>) -> Swap {
+        let update = parse_transaction(&load_fixture("meteora_dlmm_swap").to_string()).unwrap();
+        let (mut transactions_listener, _receiver) =
+            TransactionsListener::new(16, 255, HashMap::new()).unwrap();
+        transactions_listener.config.rpc_url = rpc_url;
+
+        let decoded = transactions_listener.decode(update).await.unwrap();
+        assert!(decoded.errors.is_empty(), "{:?}", decoded.errors);
+        assert_eq!(decoded.events.len(), 1);
+
+        let event = decoded.events.into_iter().next().unwrap();
+        assert_eq!(event.user, USER);
+        match event.event_type {
+            EventType::Swap(swap) => *swap,
+            event_type => panic!("expected a swap, got {:?}", event_type),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decodes_swap() {
+        let swap = decode_swap(None).await;
+
+        // 1 SOL for 4,567.890123 tokens, token X being the token
+        assert_eq!(swap.pool, LB_PAIR);
+        assert_eq!(swap.base_vault, RESERVE_X);
+        assert_eq!(swap.quote_vault, RESERVE_Y);
+        assert_eq!(swap.token_in_mint, SOLANA_PUBKEY);
+        assert_eq!(swap.token_in_amount, 1_000_000_000);
+        assert_eq!(swap.token_in_decimals, 9);
+        assert_eq!(swap.token_out_mint, MINT);
+        assert_eq!(swap.token_out_amount, 4_567_890_123);
+        assert_eq!(swap.token_out_decimals, 6);
+        // The pair's creation wasn't seen and it can't be looked up, so its bin
+        // step isn't known
+        assert_eq!(
+            swap.pool_state,
+            PoolState::LiquidityBins {
+                active_bin_id: -1_205,
+                bin_price_x64: None,
+            }
+        );
+        // The protocol's share comes out of the total fee
+        assert_eq!(
+            swap.fees,
+            Some(SwapFees {
+                lp_fee: Some(Fee {
+                    amount: 1_900_000,
+                    mint: SOLANA_PUBKEY,
+                }),
+                protocol_fee: Some(Fee {
+                    amount: 100_000,
+                    mint: SOLANA_PUBKEY,
+                }),
+                creator_fee: None,
+                fund_fee: None,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_looks_up_bin_step_of_pair_created_before_start() {
+        // `LbPair` account of the mainnet size, the bin step after the static
+        // and variable parameters, the bump and bin step seeds, the pair type
+        // and the active id
+        let mut lb_pair = vec![0; 904];
+        lb_pair[..8].copy_from_slice(&[33, 11, 49, 98, 181, 101, 177, 13]);
+        lb_pair[80..82].copy_from_slice(&10u16.to_le_bytes());
+        let account = json!({
+            "context": { "slot": 340_000_500 },
+            "value": {
+                "data": [STANDARD.encode(&lb_pair), "base64"],
+                "executable": false,
+                "lamports": 7_182_720,
+                "owner": programs::METEORA_DLMM.to_string(),
+                "rentEpoch": 0,
+                "space": 904,
+            },
+        });
+        let mock_rpc = MockRpc::start(
+            Arc::new(move |method, params| match method {
+                "getAccountInfo" if params[0] == LB_PAIR.to_string() => account.clone(),
+                _ => Value::Null,
+            }),
+            Vec::new(),
+        )
+        .await;
+
+        let swap = decode_swap(Some(mock_rpc.url.clone())).await;
+
+        // 1.001 ^ -1205 = 0.2997...
+        assert_eq!(
+            swap.pool_state,
+            PoolState::LiquidityBins {
+                active_bin_id: -1_205,
+                bin_price_x64: Some(5_531_671_225_172_282_356),
+            }
+        );
+        assert_eq!(mock_rpc.requests("getAccountInfo").len(), 1);
+    }
+}
@@ -0,0 +1,248 @@
+//! JSON-RPC node answering from the fixtures in `tests/fixtures`, for the
+//! tests of the datasources fetching from RPC
+
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use carbon_core::datasource::Update;
+use futures::{SinkExt, StreamExt};
+use serde_json::{Value, json};
+use solana_sdk::{signature::Signature, transaction::SanitizedTransaction};
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+use tokio_tungstenite::tungstenite::Message;
+use yellowstone_grpc_proto::{
+    convert_to::{create_transaction, create_transaction_meta},
+    geyser::{SubscribeUpdateTransaction, SubscribeUpdateTransactionInfo},
+};
+
+use crate::rpc_source::get_update;
+
+/// Result of a JSON-RPC method, from its name and params
+pub(crate) type Handler = Arc<dyn Fn(&str, &Value) -> Value + Send + Sync>;
+
+pub(crate) struct MockRpc {
+    pub url: String,
+    pub ws_url: String,
+    /// Method and params of every HTTP request received
+    pub requests: Arc<Mutex<Vec<(String, Value)>>>,
+}
+
+impl MockRpc {
+    /// Every logs subscription gets `notifications`, the `value` of each
+    /// `logsNotification`
+    pub async fn start(handler: Handler, notifications: Vec<Value>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ws_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mock_rpc = Self {
+            url: format!("http://{}", listener.local_addr().unwrap()),
+            ws_url: format!("ws://{}", ws_listener.local_addr().unwrap()),
+            requests: Arc::default(),
+        };
+
+        let requests = mock_rpc.requests.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve_http(stream, handler.clone(), requests.clone()));
+            }
+        });
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = ws_listener.accept().await {
+                tokio::spawn(serve_ws(stream, notifications.clone()));
+            }
+        });
+
+        mock_rpc
+    }
+
+    pub fn requests(&self, method: &str) -> Vec<Value> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(request_method, _)| request_method == method)
+            .map(|(_, params)| params.clone())
+            .collect()
+    }
+}
+
+/// `getTransaction` result, base64 encoded
+pub(crate) fn load_fixture(name: &str) -> Value {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(format!("{}.json", name));
+
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+pub(crate) fn get_fixture_signature(fixture: &Value) -> Signature {
+    let transaction: EncodedConfirmedTransactionWithStatusMeta =
+        serde_json::from_value(fixture.clone()).unwrap();
+
+    transaction
+        .transaction
+        .transaction
+        .decode()
+        .unwrap()
+        .signatures[0]
+}
+
+/// The fixture as a gRPC source streams it, at `index` within its block
+pub(crate) fn get_subscribe_update_transaction(
+    fixture: &Value,
+    index: u64,
+) -> SubscribeUpdateTransaction {
+    let transaction: EncodedConfirmedTransactionWithStatusMeta =
+        serde_json::from_value(fixture.clone()).unwrap();
+    let transaction_update = match get_update(transaction).unwrap() {
+        Update::Transaction(transaction_update) => transaction_update,
+        _ => unreachable!(),
+    };
+    let sanitized_transaction = SanitizedTransaction::from_transaction_for_tests(
+        transaction_update
+            .transaction
+            .into_legacy_transaction()
+            .unwrap(),
+    );
+
+    SubscribeUpdateTransaction {
+        slot: transaction_update.slot,
+        transaction: Some(SubscribeUpdateTransactionInfo {
+            signature: transaction_update.signature.as_ref().to_vec(),
+            is_vote: false,
+            transaction: Some(create_transaction(&sanitized_transaction)),
+            meta: Some(create_transaction_meta(&transaction_update.meta)),
+            index,
+        }),
+    }
+}
+
+/// Serves the fixtures by signature to `getTransaction`, `null` for the others
+pub(crate) fn get_transaction_handler(fixtures: Vec<Value>) -> Handler {
+    let fixtures = fixtures
+        .into_iter()
+        .map(|fixture| (get_fixture_signature(&fixture).to_string(), fixture))
+        .collect::<Vec<_>>();
+
+    Arc::new(move |method, params| match method {
+        "getTransaction" => fixtures
+            .iter()
+            .find(|(signature, _)| params[0] == *signature)
+            .map(|(_, fixture)| fixture.clone())
+            .unwrap_or(Value::Null),
+        _ => Value::Null,
+    })
+}
+
+/// Keeps the connection alive across requests, as the RPC client does
+async fn serve_http(
+    stream: TcpStream,
+    handler: Handler,
+    requests: Arc<Mutex<Vec<(String, Value)>>>,
+) {
+    let mut stream = BufReader::new(stream);
+    loop {
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            if stream.read_line(&mut line).await.unwrap_or(0) == 0 {
+                return;
+            }
+            if line == "\r\n" {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':')
+                && name.eq_ignore_ascii_case("content-length")
+            {
+                content_length = value.trim().parse().unwrap();
+            }
+        }
+
+        let mut body = vec![0; content_length];
+        if stream.read_exact(&mut body).await.is_err() {
+            return;
+        }
+        let request: Value = serde_json::from_slice(&body).unwrap();
+        let method = request["method"].as_str().unwrap_or_default().to_string();
+
+        let result = handler(&method, &request["params"]);
+        requests
+            .lock()
+            .unwrap()
+            .push((method, request["params"].clone()));
+
+        let response =
+            json!({ "jsonrpc": "2.0", "result": result, "id": request["id"] }).to_string();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            response.len(),
+            response
+        );
+        if stream
+            .get_mut()
+            .write_all(response.as_bytes())
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+async fn serve_ws(stream: TcpStream, notifications: Vec<Value>) {
+    let mut websocket = match tokio_tungstenite::accept_async(stream).await {
+        Ok(websocket) => websocket,
+        Err(_) => {
+            return;
+        }
+    };
+
+    let mut subscription = 0;
+    while let Some(Ok(message)) = websocket.next().await {
+        let request: Value = match message {
+            Message::Text(text) => serde_json::from_str(&text).unwrap(),
+            Message::Close(_) => {
+                return;
+            }
+            _ => {
+                continue;
+            }
+        };
+        if request["method"] != "logsSubscribe" {
+            continue;
+        }
+
+        subscription += 1;
+        let response = json!({ "jsonrpc": "2.0", "result": subscription, "id": request["id"] });
+        if websocket
+            .send(Message::Text(response.to_string()))
+            .await
+            .is_err()
+        {
+            return;
+        }
+
+        for (slot, value) in notifications.iter().enumerate() {
+            let notification = json!({
+                "jsonrpc": "2.0",
+                "method": "logsNotification",
+                "params": {
+                    "result": { "context": { "slot": slot }, "value": value },
+                    "subscription": subscription,
+                },
+            });
+            if websocket
+                .send(Message::Text(notification.to_string()))
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+    }
+}
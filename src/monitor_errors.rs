@@ -0,0 +1,74 @@
+use std::sync::{Arc, Mutex};
+
+use solana_sdk::signature::Signature;
+
+use crate::prelude::*;
+
+/// Errors of the monitors on the latest transaction. Carbon skips every
+/// processor after a failing one on the whole transaction, so the monitors are
+/// wrapped in `IsolatedMonitor` and report here instead, for the processors
+/// registered after them to tell.
+#[derive(Debug, Clone, Default)]
+pub struct MonitorErrors {
+    errors: Arc<Mutex<TransactionErrors>>,
+}
+
+#[derive(Debug, Default)]
+struct TransactionErrors {
+    signature: Option<Signature>,
+    errors: Vec<Error>,
+}
+
+impl MonitorErrors {
+    /// Forgets the errors of the previous transaction
+    pub fn push(&self, signature: Signature, error: Error) {
+        let mut errors = self.errors.lock().unwrap();
+        if errors.signature != Some(signature) {
+            errors.signature = Some(signature);
+            errors.errors.clear();
+        }
+
+        errors.errors.push(error);
+    }
+
+    /// Errors of the monitors on the transaction so far
+    pub fn take(&self, signature: &Signature) -> Vec<Error> {
+        let mut errors = self.errors.lock().unwrap();
+        if errors.signature != Some(*signature) {
+            return Vec::new();
+        }
+
+        std::mem::take(&mut errors.errors)
+    }
+}
+
+/// Logs the errors of the monitor and reports them to `errors`, so that the
+/// pipeline goes on with the next processors on the transaction
+pub struct IsolatedMonitor<P> {
+    pub monitor: P,
+    pub errors: MonitorErrors,
+}
+
+#[tonic::async_trait]
+impl<T, P> Processor for IsolatedMonitor<P>
+where
+    T: Send + 'static,
+    P: Processor<InputType = InstructionProcessorInputType<T>> + Send + Sync,
+{
+    type InputType = InstructionProcessorInputType<T>;
+
+    async fn process(
+        &mut self,
+        data: Self::InputType,
+        metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let signature = data.0.transaction_metadata.signature;
+
+        if let Err(error) = self.monitor.process(data, metrics).await {
+            tracing::error!("monitor on transaction {}: {}", signature, error);
+            self.errors.push(signature, error);
+        }
+
+        Ok(())
+    }
+}
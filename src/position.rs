@@ -0,0 +1,112 @@
+use carbon_core::{instruction::InstructionMetadata, transaction::TransactionMetadata};
+use solana_sdk::{
+    instruction::{AccountMeta, CompiledInstruction},
+    pubkey::Pubkey,
+    signature::Signature,
+};
+
+use crate::transfer::get_account_keys;
+
+/// Position of an instruction within its transaction, ordered by execution
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InstructionPosition {
+    /// Index of the top-level instruction
+    pub index: u32,
+    /// Index within the top-level instruction's inner instructions, `None` for
+    /// the top-level instruction itself
+    pub inner_index: Option<u32>,
+}
+
+/// Carbon only hands processors the stack height of an instruction, so its
+/// position is recovered by matching the program and accounts against the
+/// transaction. A processor sees instructions in execution order, which tells
+/// identical instructions of one transaction apart.
+#[derive(Debug, Default)]
+pub struct InstructionPositions {
+    last: Option<(Signature, InstructionPosition)>,
+}
+
+impl InstructionPositions {
+    pub fn resolve(
+        &mut self,
+        metadata: &InstructionMetadata,
+        program_id: &Pubkey,
+        accounts: &[AccountMeta],
+    ) -> InstructionPosition {
+        let transaction_metadata = &metadata.transaction_metadata;
+        let candidates = find_positions(transaction_metadata, program_id, accounts);
+
+        let last = match self.last {
+            Some((signature, last)) if signature == transaction_metadata.signature => Some(last),
+            _ => None,
+        };
+
+        // Past the last candidate means the same transaction came in again,
+        // e.g. from another source
+        let position = candidates
+            .iter()
+            .find(|candidate| last.is_none_or(|last| **candidate > last))
+            .or(candidates.first())
+            .copied()
+            .unwrap_or_default();
+
+        self.last = Some((transaction_metadata.signature, position));
+
+        position
+    }
+}
+
+/// Every position of the transaction running `program_id` with exactly
+/// `accounts`, in execution order
+fn find_positions(
+    transaction_metadata: &TransactionMetadata,
+    program_id: &Pubkey,
+    accounts: &[AccountMeta],
+) -> Vec<InstructionPosition> {
+    let account_keys = get_account_keys(transaction_metadata);
+
+    let is_match =
+        |compiled_instruction: &CompiledInstruction| {
+            account_keys.get(compiled_instruction.program_id_index as usize) == Some(program_id)
+                && compiled_instruction.accounts.len() == accounts.len()
+                && compiled_instruction.accounts.iter().zip(accounts).all(
+                    |(account_index, account)| {
+                        account_keys.get(*account_index as usize) == Some(&account.pubkey)
+                    },
+                )
+        };
+
+    let mut positions = Vec::new();
+    for (index, compiled_instruction) in transaction_metadata
+        .message
+        .instructions()
+        .iter()
+        .enumerate()
+    {
+        if is_match(compiled_instruction) {
+            positions.push(InstructionPosition {
+                index: index as u32,
+                inner_index: None,
+            });
+        }
+
+        let inner_instructions = transaction_metadata
+            .meta
+            .inner_instructions
+            .iter()
+            .flatten()
+            .filter(|inner_instructions| inner_instructions.index as usize == index)
+            .flat_map(|inner_instructions| inner_instructions.instructions.iter());
+
+        for (inner_index, inner_instruction) in inner_instructions.enumerate() {
+            if is_match(&inner_instruction.instruction) {
+                positions.push(InstructionPosition {
+                    index: index as u32,
+                    inner_index: Some(inner_index as u32),
+                });
+            }
+        }
+    }
+
+    positions
+}
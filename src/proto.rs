@@ -0,0 +1,683 @@
+//! Protobuf encoding of the events, generated by prost from
+//! `proto/events.proto`. Encode and decode with `prost::Message`, then convert
+//! from and into the `types` module.
+
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+
+use crate::{prelude::*, types};
+
+include!(concat!(env!("OUT_DIR"), "/transactions_listener.rs"));
+
+impl From<types::Event> for Event {
+    fn from(event: types::Event) -> Self {
+        Self {
+            schema_version: types::SchemaVersion::CURRENT,
+            signature: event.signature.as_ref().to_vec(),
+            event_type: Some(event.event_type.into()),
+            user: event.user.to_bytes().to_vec(),
+            slot: event.slot,
+            block_time: event.block_time,
+            transaction_index: event.transaction_index,
+            instruction_index: event.instruction_index,
+            inner_instruction_index: event.inner_instruction_index,
+            received_at: event.received_at,
+            transaction_fees: Some(event.transaction_fees.into()),
+            watchlist_entry: event.watchlist_entry.map(Into::into),
+        }
+    }
+}
+
+impl TryFrom<Event> for types::Event {
+    type Error = Error;
+
+    fn try_from(event: Event) -> CarbonResult<Self> {
+        Ok(Self {
+            schema_version: schema_version(event.schema_version)?,
+            signature: signature_from_bytes(&event.signature)?,
+            event_type: required(event.event_type, "event_type")?.try_into()?,
+            user: pubkey_from_bytes(&event.user)?,
+            slot: event.slot,
+            block_time: event.block_time,
+            transaction_index: event.transaction_index,
+            instruction_index: event.instruction_index,
+            inner_instruction_index: event.inner_instruction_index,
+            received_at: event.received_at,
+            transaction_fees: required(event.transaction_fees, "transaction_fees")?.into(),
+            watchlist_entry: event.watchlist_entry.map(TryInto::try_into).transpose()?,
+        })
+    }
+}
+
+impl From<types::TransactionEvents> for TransactionEvents {
+    fn from(transaction_events: types::TransactionEvents) -> Self {
+        Self {
+            schema_version: types::SchemaVersion::CURRENT,
+            signature: transaction_events.signature.as_ref().to_vec(),
+            fee_payer: transaction_events.fee_payer.to_bytes().to_vec(),
+            slot: transaction_events.slot,
+            block_time: transaction_events.block_time,
+            transaction_index: transaction_events.transaction_index,
+            transaction_fees: Some(transaction_events.transaction_fees.into()),
+            events: transaction_events
+                .events
+                .into_iter()
+                .map(Event::from)
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<TransactionEvents> for types::TransactionEvents {
+    type Error = Error;
+
+    fn try_from(transaction_events: TransactionEvents) -> CarbonResult<Self> {
+        Ok(Self {
+            schema_version: schema_version(transaction_events.schema_version)?,
+            signature: signature_from_bytes(&transaction_events.signature)?,
+            fee_payer: pubkey_from_bytes(&transaction_events.fee_payer)?,
+            slot: transaction_events.slot,
+            block_time: transaction_events.block_time,
+            transaction_index: transaction_events.transaction_index,
+            transaction_fees: required(transaction_events.transaction_fees, "transaction_fees")?
+                .into(),
+            events: transaction_events
+                .events
+                .into_iter()
+                .map(types::Event::try_from)
+                .collect::<CarbonResult<_>>()?,
+        })
+    }
+}
+
+impl From<types::TransactionFees> for TransactionFees {
+    fn from(transaction_fees: types::TransactionFees) -> Self {
+        Self {
+            total_fee: transaction_fees.total_fee,
+            priority_fee: transaction_fees.priority_fee,
+            compute_units_consumed: transaction_fees.compute_units_consumed,
+            jito_tip: transaction_fees.jito_tip,
+        }
+    }
+}
+
+impl From<TransactionFees> for types::TransactionFees {
+    fn from(transaction_fees: TransactionFees) -> Self {
+        Self {
+            total_fee: transaction_fees.total_fee,
+            priority_fee: transaction_fees.priority_fee,
+            compute_units_consumed: transaction_fees.compute_units_consumed,
+            jito_tip: transaction_fees.jito_tip,
+        }
+    }
+}
+
+impl From<types::EventType> for EventType {
+    fn from(event_type: types::EventType) -> Self {
+        let event_type = match event_type {
+            types::EventType::Swap(swap) => event_type::EventType::Swap(Box::new((*swap).into())),
+            types::EventType::FailedSwapAttempt(failed_swap_attempt) => {
+                event_type::EventType::FailedSwapAttempt((*failed_swap_attempt).into())
+            }
+            types::EventType::PoolCreation { mint, platform } => {
+                event_type::EventType::PoolCreation(PoolCreation {
+                    mint: mint.to_bytes().to_vec(),
+                    platform: SwapPlatform::from(platform).into(),
+                })
+            }
+            types::EventType::AssociatedAccountCreation {
+                mint,
+                account,
+                idempotent,
+            } => event_type::EventType::AssociatedAccountCreation(AssociatedAccountCreation {
+                mint: mint.to_bytes().to_vec(),
+                account: account.to_bytes().to_vec(),
+                idempotent,
+            }),
+        };
+
+        Self {
+            event_type: Some(event_type),
+        }
+    }
+}
+
+impl TryFrom<EventType> for types::EventType {
+    type Error = Error;
+
+    fn try_from(event_type: EventType) -> CarbonResult<Self> {
+        Ok(match required(event_type.event_type, "event_type")? {
+            event_type::EventType::Swap(swap) => Self::Swap(Box::new((*swap).try_into()?)),
+            event_type::EventType::FailedSwapAttempt(failed_swap_attempt) => {
+                Self::FailedSwapAttempt(Box::new(failed_swap_attempt.try_into()?))
+            }
+            event_type::EventType::PoolCreation(pool_creation) => Self::PoolCreation {
+                mint: pubkey_from_bytes(&pool_creation.mint)?,
+                platform: swap_platform(pool_creation.platform)?,
+            },
+            event_type::EventType::AssociatedAccountCreation(associated_account_creation) => {
+                Self::AssociatedAccountCreation {
+                    mint: pubkey_from_bytes(&associated_account_creation.mint)?,
+                    account: pubkey_from_bytes(&associated_account_creation.account)?,
+                    idempotent: associated_account_creation.idempotent,
+                }
+            }
+        })
+    }
+}
+
+impl From<types::Swap> for Swap {
+    fn from(swap: types::Swap) -> Self {
+        Self {
+            token_in_amount: swap.token_in_amount,
+            token_in_decimals: swap.token_in_decimals.into(),
+            token_in_mint: swap.token_in_mint.to_bytes().to_vec(),
+            token_out_amount: swap.token_out_amount,
+            token_out_decimals: swap.token_out_decimals.into(),
+            token_out_mint: swap.token_out_mint.to_bytes().to_vec(),
+            platform: SwapPlatform::from(swap.platform).into(),
+            pool: swap.pool.to_bytes().to_vec(),
+            base_vault: swap.base_vault.to_bytes().to_vec(),
+            quote_vault: swap.quote_vault.to_bytes().to_vec(),
+            pool_state: Some(swap.pool_state.into()),
+            fees: swap.fees.map(SwapFees::from),
+        }
+    }
+}
+
+impl TryFrom<Swap> for types::Swap {
+    type Error = Error;
+
+    fn try_from(swap: Swap) -> CarbonResult<Self> {
+        Ok(Self {
+            token_in_amount: swap.token_in_amount,
+            token_in_decimals: decimals(swap.token_in_decimals)?,
+            token_in_mint: pubkey_from_bytes(&swap.token_in_mint)?,
+            token_out_amount: swap.token_out_amount,
+            token_out_decimals: decimals(swap.token_out_decimals)?,
+            token_out_mint: pubkey_from_bytes(&swap.token_out_mint)?,
+            platform: swap_platform(swap.platform)?,
+            pool: pubkey_from_bytes(&swap.pool)?,
+            base_vault: pubkey_from_bytes(&swap.base_vault)?,
+            quote_vault: pubkey_from_bytes(&swap.quote_vault)?,
+            pool_state: required(swap.pool_state, "pool_state")?.try_into()?,
+            fees: swap.fees.map(types::SwapFees::try_from).transpose()?,
+        })
+    }
+}
+
+impl From<types::FailedSwapAttempt> for FailedSwapAttempt {
+    fn from(failed_swap_attempt: types::FailedSwapAttempt) -> Self {
+        Self {
+            platform: SwapPlatform::from(failed_swap_attempt.platform).into(),
+            pool: failed_swap_attempt.pool.to_bytes().to_vec(),
+            token_in_mint: failed_swap_attempt
+                .token_in_mint
+                .map(|mint| mint.to_bytes().to_vec()),
+            token_out_mint: failed_swap_attempt
+                .token_out_mint
+                .map(|mint| mint.to_bytes().to_vec()),
+            amount: Some(failed_swap_attempt.amount.into()),
+            failed_instruction_index: failed_swap_attempt.failed_instruction_index,
+            error_code: failed_swap_attempt.error_code,
+        }
+    }
+}
+
+impl TryFrom<FailedSwapAttempt> for types::FailedSwapAttempt {
+    type Error = Error;
+
+    fn try_from(failed_swap_attempt: FailedSwapAttempt) -> CarbonResult<Self> {
+        Ok(Self {
+            platform: swap_platform(failed_swap_attempt.platform)?,
+            pool: pubkey_from_bytes(&failed_swap_attempt.pool)?,
+            token_in_mint: failed_swap_attempt
+                .token_in_mint
+                .as_deref()
+                .map(pubkey_from_bytes)
+                .transpose()?,
+            token_out_mint: failed_swap_attempt
+                .token_out_mint
+                .as_deref()
+                .map(pubkey_from_bytes)
+                .transpose()?,
+            amount: required(failed_swap_attempt.amount, "amount")?.try_into()?,
+            failed_instruction_index: failed_swap_attempt.failed_instruction_index,
+            error_code: failed_swap_attempt.error_code,
+        })
+    }
+}
+
+impl From<types::SwapAmount> for SwapAmount {
+    fn from(swap_amount: types::SwapAmount) -> Self {
+        let swap_amount = match swap_amount {
+            types::SwapAmount::ExactIn {
+                amount_in,
+                minimum_amount_out,
+            } => swap_amount::SwapAmount::ExactIn(ExactIn {
+                amount_in,
+                minimum_amount_out,
+            }),
+            types::SwapAmount::ExactOut {
+                amount_out,
+                maximum_amount_in,
+            } => swap_amount::SwapAmount::ExactOut(ExactOut {
+                amount_out,
+                maximum_amount_in,
+            }),
+        };
+
+        Self {
+            swap_amount: Some(swap_amount),
+        }
+    }
+}
+
+impl TryFrom<SwapAmount> for types::SwapAmount {
+    type Error = Error;
+
+    fn try_from(swap_amount: SwapAmount) -> CarbonResult<Self> {
+        Ok(match required(swap_amount.swap_amount, "swap_amount")? {
+            swap_amount::SwapAmount::ExactIn(exact_in) => Self::ExactIn {
+                amount_in: exact_in.amount_in,
+                minimum_amount_out: exact_in.minimum_amount_out,
+            },
+            swap_amount::SwapAmount::ExactOut(exact_out) => Self::ExactOut {
+                amount_out: exact_out.amount_out,
+                maximum_amount_in: exact_out.maximum_amount_in,
+            },
+        })
+    }
+}
+
+impl From<types::SwapFees> for SwapFees {
+    fn from(swap_fees: types::SwapFees) -> Self {
+        Self {
+            lp_fee: swap_fees.lp_fee.map(Fee::from),
+            protocol_fee: swap_fees.protocol_fee.map(Fee::from),
+            creator_fee: swap_fees.creator_fee.map(Fee::from),
+            fund_fee: swap_fees.fund_fee.map(Fee::from),
+        }
+    }
+}
+
+impl TryFrom<SwapFees> for types::SwapFees {
+    type Error = Error;
+
+    fn try_from(swap_fees: SwapFees) -> CarbonResult<Self> {
+        Ok(Self {
+            lp_fee: swap_fees.lp_fee.map(types::Fee::try_from).transpose()?,
+            protocol_fee: swap_fees
+                .protocol_fee
+                .map(types::Fee::try_from)
+                .transpose()?,
+            creator_fee: swap_fees
+                .creator_fee
+                .map(types::Fee::try_from)
+                .transpose()?,
+            fund_fee: swap_fees.fund_fee.map(types::Fee::try_from).transpose()?,
+        })
+    }
+}
+
+impl From<types::Fee> for Fee {
+    fn from(fee: types::Fee) -> Self {
+        Self {
+            amount: fee.amount,
+            mint: fee.mint.to_bytes().to_vec(),
+        }
+    }
+}
+
+impl TryFrom<Fee> for types::Fee {
+    type Error = Error;
+
+    fn try_from(fee: Fee) -> CarbonResult<Self> {
+        Ok(Self {
+            amount: fee.amount,
+            mint: pubkey_from_bytes(&fee.mint)?,
+        })
+    }
+}
+
+impl From<types::WatchlistEntry> for WatchlistEntry {
+    fn from(watchlist_entry: types::WatchlistEntry) -> Self {
+        Self {
+            wallet: watchlist_entry.wallet.to_bytes().to_vec(),
+            label: watchlist_entry.label,
+        }
+    }
+}
+
+impl TryFrom<WatchlistEntry> for types::WatchlistEntry {
+    type Error = Error;
+
+    fn try_from(watchlist_entry: WatchlistEntry) -> CarbonResult<Self> {
+        Ok(Self {
+            wallet: pubkey_from_bytes(&watchlist_entry.wallet)?,
+            label: watchlist_entry.label,
+        })
+    }
+}
+
+impl From<types::PoolState> for PoolState {
+    fn from(pool_state: types::PoolState) -> Self {
+        let pool_state = match pool_state {
+            types::PoolState::ConstantProduct {
+                token_in_reserve,
+                token_out_reserve,
+            } => pool_state::PoolState::ConstantProduct(ConstantProduct {
+                token_in_reserve,
+                token_out_reserve,
+            }),
+            types::PoolState::ConcentratedLiquidity {
+                sqrt_price_x64,
+                tick,
+                liquidity,
+            } => pool_state::PoolState::ConcentratedLiquidity(ConcentratedLiquidity {
+                sqrt_price_x64: sqrt_price_x64.to_string(),
+                tick,
+                liquidity: liquidity.to_string(),
+            }),
+            types::PoolState::LiquidityBins {
+                active_bin_id,
+                bin_price_x64,
+            } => pool_state::PoolState::LiquidityBins(LiquidityBins {
+                active_bin_id,
+                bin_price_x64: bin_price_x64.map(|bin_price_x64| bin_price_x64.to_string()),
+            }),
+        };
+
+        Self {
+            pool_state: Some(pool_state),
+        }
+    }
+}
+
+impl TryFrom<PoolState> for types::PoolState {
+    type Error = Error;
+
+    fn try_from(pool_state: PoolState) -> CarbonResult<Self> {
+        Ok(match required(pool_state.pool_state, "pool_state")? {
+            pool_state::PoolState::ConstantProduct(constant_product) => Self::ConstantProduct {
+                token_in_reserve: constant_product.token_in_reserve,
+                token_out_reserve: constant_product.token_out_reserve,
+            },
+            pool_state::PoolState::ConcentratedLiquidity(concentrated_liquidity) => {
+                Self::ConcentratedLiquidity {
+                    sqrt_price_x64: u128_from_string(&concentrated_liquidity.sqrt_price_x64)?,
+                    tick: concentrated_liquidity.tick,
+                    liquidity: u128_from_string(&concentrated_liquidity.liquidity)?,
+                }
+            }
+            pool_state::PoolState::LiquidityBins(liquidity_bins) => Self::LiquidityBins {
+                active_bin_id: liquidity_bins.active_bin_id,
+                bin_price_x64: liquidity_bins
+                    .bin_price_x64
+                    .as_deref()
+                    .map(u128_from_string)
+                    .transpose()?,
+            },
+        })
+    }
+}
+
+impl From<types::SwapPlatform> for SwapPlatform {
+    fn from(platform: types::SwapPlatform) -> Self {
+        match platform {
+            types::SwapPlatform::PumpFun => Self::PumpFun,
+            types::SwapPlatform::Raydium => Self::Raydium,
+            types::SwapPlatform::RaydiumCpmm => Self::RaydiumCpmm,
+            types::SwapPlatform::RaydiumClmm => Self::RaydiumClmm,
+            types::SwapPlatform::MeteoraDlmm => Self::MeteoraDlmm,
+        }
+    }
+}
+
+impl TryFrom<SwapPlatform> for types::SwapPlatform {
+    type Error = Error;
+
+    fn try_from(platform: SwapPlatform) -> CarbonResult<Self> {
+        match platform {
+            SwapPlatform::Unspecified => {
+                Err(Error::Custom("unspecified swap platform".to_string()))
+            }
+            SwapPlatform::PumpFun => Ok(Self::PumpFun),
+            SwapPlatform::Raydium => Ok(Self::Raydium),
+            SwapPlatform::RaydiumCpmm => Ok(Self::RaydiumCpmm),
+            SwapPlatform::RaydiumClmm => Ok(Self::RaydiumClmm),
+            SwapPlatform::MeteoraDlmm => Ok(Self::MeteoraDlmm),
+        }
+    }
+}
+
+fn schema_version(schema_version: u32) -> CarbonResult<types::SchemaVersion> {
+    if schema_version != types::SchemaVersion::CURRENT {
+        return Err(Error::Custom(format!(
+            "unsupported schema version {}, expected {}",
+            schema_version,
+            types::SchemaVersion::CURRENT
+        )));
+    }
+
+    Ok(types::SchemaVersion)
+}
+
+fn swap_platform(platform: i32) -> CarbonResult<types::SwapPlatform> {
+    match SwapPlatform::try_from(platform) {
+        Ok(platform) => platform.try_into(),
+        Err(_) => Err(Error::Custom(format!("unknown swap platform {}", platform))),
+    }
+}
+
+fn pubkey_from_bytes(bytes: &[u8]) -> CarbonResult<Pubkey> {
+    Pubkey::try_from(bytes)
+        .map_err(|_| Error::Custom(format!("invalid pubkey of {} bytes", bytes.len())))
+}
+
+fn signature_from_bytes(bytes: &[u8]) -> CarbonResult<Signature> {
+    Signature::try_from(bytes)
+        .map_err(|_| Error::Custom(format!("invalid signature of {} bytes", bytes.len())))
+}
+
+fn u128_from_string(value: &str) -> CarbonResult<u128> {
+    value
+        .parse()
+        .map_err(|_| Error::Custom(format!("invalid u128 {}", value)))
+}
+
+fn decimals(decimals: u32) -> CarbonResult<u8> {
+    u8::try_from(decimals).map_err(|_| Error::Custom(format!("invalid decimals {}", decimals)))
+}
+
+fn required<T>(value: Option<T>, field: &str) -> CarbonResult<T> {
+    match value {
+        Some(value) => Ok(value),
+        None => Err(Error::Custom(format!("missing field {}", field))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prost::Message;
+    use strum::IntoEnumIterator;
+
+    use super::*;
+    use crate::test_utils::{
+        get_event, get_failed_swap_attempt, get_pool_creation, get_swap, get_swap_fees,
+    };
+
+    fn round_trip(event: &types::Event) -> types::Event {
+        let bytes = Event::from(event.clone()).encode_to_vec();
+
+        Event::decode(bytes.as_slice()).unwrap().try_into().unwrap()
+    }
+
+    fn assert_round_trip(event: types::Event) {
+        let decoded = round_trip(&event);
+
+        // `PartialEq` leaves these two out
+        assert_eq!(decoded.received_at, event.received_at);
+        assert_eq!(decoded.watchlist_entry, event.watchlist_entry);
+        assert_eq!(decoded, event);
+    }
+
+    fn assert_error<T: std::fmt::Debug>(result: CarbonResult<T>, expected: &str) {
+        match result {
+            Err(Error::Custom(message)) => assert_eq!(message, expected),
+            result => panic!("expected error {}, got {:?}", expected, result),
+        }
+    }
+
+    #[test]
+    fn test_event_types_round_trip() {
+        let event_types = [
+            get_swap(
+                types::SwapPlatform::Raydium,
+                types::PoolState::ConstantProduct {
+                    token_in_reserve: 80_000_000_000,
+                    token_out_reserve: 2_800_000_000_000,
+                },
+                Some(get_swap_fees()),
+            ),
+            get_failed_swap_attempt(types::SwapAmount::ExactIn {
+                amount_in: 1_000_000,
+                minimum_amount_out: Some(990_000),
+            }),
+            get_pool_creation(types::SwapPlatform::PumpFun),
+            types::EventType::AssociatedAccountCreation {
+                mint: Pubkey::new_unique(),
+                account: Pubkey::new_unique(),
+                idempotent: true,
+            },
+        ];
+
+        for event_type in event_types {
+            assert_round_trip(get_event(event_type));
+        }
+    }
+
+    #[test]
+    fn test_pool_states_round_trip() {
+        let pool_states = [
+            types::PoolState::ConstantProduct {
+                token_in_reserve: u64::MAX,
+                token_out_reserve: 0,
+            },
+            types::PoolState::ConcentratedLiquidity {
+                sqrt_price_x64: u128::MAX,
+                tick: -443_636,
+                liquidity: 1 << 100,
+            },
+            types::PoolState::LiquidityBins {
+                active_bin_id: -1_234,
+                bin_price_x64: Some(1 << 64),
+            },
+            types::PoolState::LiquidityBins {
+                active_bin_id: 5_678,
+                bin_price_x64: None,
+            },
+        ];
+
+        for pool_state in pool_states {
+            assert_round_trip(get_event(get_swap(
+                types::SwapPlatform::MeteoraDlmm,
+                pool_state,
+                Some(get_swap_fees()),
+            )));
+        }
+    }
+
+    #[test]
+    fn test_swap_amounts_round_trip() {
+        let swap_amounts = [
+            types::SwapAmount::ExactIn {
+                amount_in: 1_000_000,
+                minimum_amount_out: Some(990_000),
+            },
+            types::SwapAmount::ExactIn {
+                amount_in: 1_000_000,
+                minimum_amount_out: None,
+            },
+            types::SwapAmount::ExactOut {
+                amount_out: 990_000,
+                maximum_amount_in: 1_000_000,
+            },
+        ];
+
+        for swap_amount in swap_amounts {
+            assert_round_trip(get_event(get_failed_swap_attempt(swap_amount)));
+        }
+    }
+
+    #[test]
+    fn test_swap_platforms_round_trip() {
+        for platform in types::SwapPlatform::iter() {
+            assert_round_trip(get_event(get_pool_creation(platform)));
+        }
+    }
+
+    #[test]
+    fn test_transaction_events_round_trip() {
+        let event = get_event(get_pool_creation(types::SwapPlatform::RaydiumClmm));
+        let transaction_events = types::TransactionEvents {
+            schema_version: types::SchemaVersion,
+            signature: event.signature,
+            fee_payer: event.user,
+            slot: event.slot,
+            block_time: event.block_time,
+            transaction_index: event.transaction_index,
+            transaction_fees: event.transaction_fees.clone(),
+            events: vec![event],
+        };
+
+        let bytes = TransactionEvents::from(transaction_events.clone()).encode_to_vec();
+        let decoded: types::TransactionEvents = TransactionEvents::decode(bytes.as_slice())
+            .unwrap()
+            .try_into()
+            .unwrap();
+
+        assert_eq!(decoded, transaction_events);
+    }
+
+    #[test]
+    fn test_missing_field() {
+        let mut event = Event::from(get_event(get_pool_creation(types::SwapPlatform::PumpFun)));
+        event.transaction_fees = None;
+
+        assert_error(
+            types::Event::try_from(event),
+            "missing field transaction_fees",
+        );
+        assert_error(
+            types::EventType::try_from(EventType { event_type: None }),
+            "missing field event_type",
+        );
+        assert_error(
+            types::SwapAmount::try_from(SwapAmount { swap_amount: None }),
+            "missing field swap_amount",
+        );
+    }
+
+    #[test]
+    fn test_invalid_pubkey() {
+        let mut event = Event::from(get_event(types::EventType::AssociatedAccountCreation {
+            mint: Pubkey::new_unique(),
+            account: Pubkey::new_unique(),
+            idempotent: false,
+        }));
+        event.user.pop();
+
+        assert_error(types::Event::try_from(event), "invalid pubkey of 31 bytes");
+        assert_error(pubkey_from_bytes(&[]), "invalid pubkey of 0 bytes");
+        assert_error(pubkey_from_bytes(&[0; 33]), "invalid pubkey of 33 bytes");
+    }
+
+    #[test]
+    fn test_invalid_swap_platform() {
+        assert_error(
+            swap_platform(SwapPlatform::Unspecified.into()),
+            "unspecified swap platform",
+        );
+        assert_error(swap_platform(99), "unknown swap platform 99");
+    }
+}
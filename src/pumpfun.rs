@@ -1,13 +1,29 @@
-use crate::{constants::SOLANA_PUBKEY, types::Swap, utils::get_now_timestamp};
+use crate::{
+    constants::SOLANA_PUBKEY,
+    types::{FailedSwapAttempt, Fee, PoolState, Swap, SwapAmount, SwapFees},
+    utils::get_now_timestamp_millis,
+};
 
 use super::*;
 
-use carbon_core::error::Error;
-use carbon_pumpfun_decoder::instructions::PumpfunInstruction;
+use carbon_core::{
+    deserialize::ArrangeAccounts,
+    error::Error,
+    instruction::{DecodedInstruction, InstructionDecoder},
+    transaction::TransactionMetadata,
+};
+use carbon_pumpfun_decoder::{
+    PumpfunDecoder,
+    instructions::{PumpfunInstruction, buy::Buy, trade_event::TradeEvent},
+};
+use solana_sdk::pubkey::Pubkey;
 
 pub struct PumpFunMonitor {
-    pub sender: Sender<Event>,
-    pub parsed_events: Arc<RwLock<HashSet<Event>>>,
+    pub sender: EventSender,
+    pub events_cache: Arc<EventsCache>,
+    pub instruction_positions: InstructionPositions,
+    /// Emit `FailedSwapAttempt` events for failed transactions
+    pub failed_swap_attempts: bool,
 }
 
 #[tonic::async_trait]
@@ -16,19 +32,31 @@ impl Processor for PumpFunMonitor {
 
     async fn process(
         &mut self,
-        (metadata, instruction, _nested_instructions): Self::InputType,
+        (metadata, instruction, nested_instructions): Self::InputType,
         _metrics: Arc<MetricsCollection>,
     ) -> CarbonResult<()> {
-        let signature = metadata.transaction_metadata.signature;
-        let now_timestamp = get_now_timestamp();
+        let received_at = get_now_timestamp_millis();
+
+        let failed = metadata.transaction_metadata.meta.status.is_err();
+        if failed && !self.failed_swap_attempts {
+            return Ok(());
+        }
+
         let (event_type, user) = match instruction.data {
-            PumpfunInstruction::CreateEvent(create_event) => (
-                EventType::PoolCreation {
-                    mint: create_event.mint,
-                    platform: SwapPlatform::PumpFun,
-                },
-                create_event.user,
-            ),
+            // Failed transactions leave nothing but the instruction behind
+            _ if failed => {
+                match get_failed_swap_attempt(&metadata.transaction_metadata, &instruction)? {
+                    Some((failed_swap_attempt, user)) => (
+                        EventType::FailedSwapAttempt(Box::new(failed_swap_attempt)),
+                        user,
+                    ),
+                    None => {
+                        return Ok(());
+                    }
+                }
+            }
+            // The `CreateEvent` the program logs through a self-CPI describes
+            // the same launch, and would be a second event at its own position
             PumpfunInstruction::Create(create) => (
                 EventType::PoolCreation {
                     mint: instruction.accounts[0].pubkey,
@@ -36,7 +64,32 @@ impl Processor for PumpFunMonitor {
                 },
                 create.creator,
             ),
-            PumpfunInstruction::TradeEvent(trade_event) => {
+            PumpfunInstruction::Buy(_) | PumpfunInstruction::Sell(_) => {
+                // Buy and Sell share the same leading accounts, bonding curve included
+                let accounts = match Buy::arrange_accounts(&instruction.accounts) {
+                    Some(accounts) => accounts,
+                    None => {
+                        return Err(Error::Custom(
+                            "pumpfun trade accounts not found".to_string(),
+                        ));
+                    }
+                };
+
+                let trade_event = match nested_instructions.iter().find_map(|nested_instruction| {
+                    match PumpfunDecoder.decode_instruction(&nested_instruction.instruction) {
+                        Some(decoded_instruction) => match decoded_instruction.data {
+                            PumpfunInstruction::TradeEvent(trade_event) => Some(trade_event),
+                            _ => None,
+                        },
+                        None => None,
+                    }
+                }) {
+                    Some(trade_event) => trade_event,
+                    None => {
+                        return Err(Error::Custom("pumpfun trade event not found".to_string()));
+                    }
+                };
+
                 let (
                     token_in_amount,
                     token_in_decimals,
@@ -77,7 +130,7 @@ impl Processor for PumpFunMonitor {
                 };
 
                 (
-                    EventType::Swap(Swap {
+                    EventType::Swap(Box::new(Swap {
                         token_in_amount,
                         token_in_decimals,
                         token_in_mint,
@@ -85,9 +138,15 @@ impl Processor for PumpFunMonitor {
                         token_out_decimals,
                         token_out_mint,
                         platform: SwapPlatform::PumpFun,
-                        token_in_reserve,
-                        token_out_reserve,
-                    }),
+                        pool: accounts.bonding_curve,
+                        base_vault: accounts.associated_bonding_curve,
+                        quote_vault: accounts.bonding_curve,
+                        pool_state: PoolState::ConstantProduct {
+                            token_in_reserve,
+                            token_out_reserve,
+                        },
+                        fees: Some(get_swap_fees(&trade_event)),
+                    })),
                     trade_event.user,
                 )
             }
@@ -96,18 +155,22 @@ impl Processor for PumpFunMonitor {
             }
         };
 
-        let event = Event {
-            signature,
+        let position = self.instruction_positions.resolve(
+            &metadata,
+            &instruction.program_id,
+            &instruction.accounts,
+        );
+        let event = Event::new(
+            &metadata.transaction_metadata,
+            position,
             event_type,
             user,
-            timestamp: now_timestamp,
-        };
+            received_at,
+        );
 
-        let parsed_events_cache = self.parsed_events.read().await.clone();
-        if parsed_events_cache.contains(&event) {
+        if !self.events_cache.insert(&event) {
             return Ok(());
         }
-        self.parsed_events.write().await.insert(event.clone());
 
         self.sender
             .send(event)
@@ -115,3 +178,188 @@ impl Processor for PumpFunMonitor {
             .map_err(|error| Error::Custom(format!("send pumpfun event to receiver: {}", error)))
     }
 }
+
+/// Fees are charged in SOL on top of a buy and out of a sell, as the trade
+/// event reports them, the bonding curve has no liquidity providers
+fn get_swap_fees(trade_event: &TradeEvent) -> SwapFees {
+    SwapFees {
+        lp_fee: None,
+        protocol_fee: Some(Fee {
+            amount: trade_event.fee,
+            mint: SOLANA_PUBKEY,
+        }),
+        creator_fee: Some(Fee {
+            amount: trade_event.creator_fee,
+            mint: SOLANA_PUBKEY,
+        }),
+        fund_fee: None,
+    }
+}
+
+fn get_failed_swap_attempt(
+    transaction_metadata: &TransactionMetadata,
+    instruction: &DecodedInstruction<PumpfunInstruction>,
+) -> CarbonResult<Option<(FailedSwapAttempt, Pubkey)>> {
+    let (is_buy, amount) = match &instruction.data {
+        PumpfunInstruction::Buy(buy) => (
+            true,
+            SwapAmount::ExactOut {
+                amount_out: buy.amount,
+                maximum_amount_in: buy.max_sol_cost,
+            },
+        ),
+        PumpfunInstruction::Sell(sell) => (
+            false,
+            SwapAmount::ExactIn {
+                amount_in: sell.amount,
+                minimum_amount_out: Some(sell.min_sol_output),
+            },
+        ),
+        _ => {
+            return Ok(None);
+        }
+    };
+
+    let accounts = match Buy::arrange_accounts(&instruction.accounts) {
+        Some(accounts) => accounts,
+        None => {
+            return Err(Error::Custom(
+                "pumpfun trade accounts not found".to_string(),
+            ));
+        }
+    };
+
+    let (token_in_mint, token_out_mint) = if is_buy {
+        (SOLANA_PUBKEY, accounts.mint)
+    } else {
+        (accounts.mint, SOLANA_PUBKEY)
+    };
+
+    Ok(Some((
+        FailedSwapAttempt::new(
+            transaction_metadata,
+            SwapPlatform::PumpFun,
+            accounts.bonding_curve,
+            Some(token_in_mint),
+            Some(token_out_mint),
+            amount,
+        ),
+        accounts.user,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{
+        TransactionsListener, decode::parse_transaction, mock_rpc::load_fixture, types::Fee,
+    };
+
+    const USER: Pubkey = solana_sdk::pubkey!("JC3FaLRvSF92bf3j27VzUyb82cqZxxPnjik5to9qqtC3");
+    const MINT: Pubkey = solana_sdk::pubkey!("CpjxMcNsK3DDuw44TbRzbXbi4Bn6vUrgjoq6aGnqwtsd");
+    const BONDING_CURVE: Pubkey =
+        solana_sdk::pubkey!("6wsq6Eux8HvruaX7zBq8dhVEqC9Fu6ScNwEGQAUYg2Gi");
+    const ASSOCIATED_BONDING_CURVE: Pubkey =
+        solana_sdk::pubkey!("DR3nwWbU2y3vTwbgcTDyt938um6a78oevtpfh2d7QUAJ");
+
+    async fn decode_swap(fixture: &str) -> (Swap, Pubkey) {
+        let update = parse_transaction(&load_fixture(fixture).to_string()).unwrap();
+        let (transactions_listener, _receiver) =
+            TransactionsListener::new(16, 255, HashMap::new()).unwrap();
+
+        let decoded = transactions_listener.decode(update).await.unwrap();
+        assert!(decoded.errors.is_empty(), "{:?}", decoded.errors);
+        assert_eq!(decoded.events.len(), 1);
+
+        let event = decoded.events.into_iter().next().unwrap();
+        match event.event_type {
+            EventType::Swap(swap) => (*swap, event.user),
+            event_type => panic!("expected a swap, got {:?}", event_type),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decodes_buy() {
+        let (swap, user) = decode_swap("pumpfun_buy").await;
+
+        // 1 SOL for 34,000 tokens
+        assert_eq!(user, USER);
+        assert_eq!(swap.platform, SwapPlatform::PumpFun);
+        // The bonding curve holds the SOL itself, its associated account the
+        // tokens
+        assert_eq!(swap.pool, BONDING_CURVE);
+        assert_eq!(swap.base_vault, ASSOCIATED_BONDING_CURVE);
+        assert_eq!(swap.quote_vault, BONDING_CURVE);
+        assert_eq!(swap.token_in_mint, SOLANA_PUBKEY);
+        assert_eq!(swap.token_in_amount, 1_000_000_000);
+        assert_eq!(swap.token_in_decimals, 9);
+        assert_eq!(swap.token_out_mint, MINT);
+        assert_eq!(swap.token_out_amount, 34_000_000_000);
+        assert_eq!(swap.token_out_decimals, 6);
+        assert_eq!(
+            swap.pool_state,
+            PoolState::ConstantProduct {
+                token_in_reserve: 31_000_000_000,
+                token_out_reserve: 1_038_000_000_000_000,
+            }
+        );
+        // 0.95% to the protocol and 0.05% to the creator, on top of the SOL in
+        assert_eq!(
+            swap.fees,
+            Some(SwapFees {
+                lp_fee: None,
+                protocol_fee: Some(Fee {
+                    amount: 9_500_000,
+                    mint: SOLANA_PUBKEY,
+                }),
+                creator_fee: Some(Fee {
+                    amount: 500_000,
+                    mint: SOLANA_PUBKEY,
+                }),
+                fund_fee: None,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_decodes_sell() {
+        let (swap, user) = decode_swap("pumpfun_sell").await;
+
+        // 16,000 tokens for 0.5 SOL
+        assert_eq!(user, USER);
+        assert_eq!(swap.pool, BONDING_CURVE);
+        assert_eq!(swap.base_vault, ASSOCIATED_BONDING_CURVE);
+        assert_eq!(swap.quote_vault, BONDING_CURVE);
+        assert_eq!(swap.token_in_mint, MINT);
+        assert_eq!(swap.token_in_amount, 16_000_000_000);
+        assert_eq!(swap.token_in_decimals, 6);
+        assert_eq!(swap.token_out_mint, SOLANA_PUBKEY);
+        assert_eq!(swap.token_out_amount, 500_000_000);
+        assert_eq!(swap.token_out_decimals, 9);
+        assert_eq!(
+            swap.pool_state,
+            PoolState::ConstantProduct {
+                token_in_reserve: 1_054_000_000_000_000,
+                token_out_reserve: 30_500_000_000,
+            }
+        );
+        // Out of the SOL out
+        assert_eq!(
+            swap.fees,
+            Some(SwapFees {
+                lp_fee: None,
+                protocol_fee: Some(Fee {
+                    amount: 4_750_000,
+                    mint: SOLANA_PUBKEY,
+                }),
+                creator_fee: Some(Fee {
+                    amount: 250_000,
+                    mint: SOLANA_PUBKEY,
+                }),
+                fund_fee: None,
+            })
+        );
+    }
+}
@@ -0,0 +1,365 @@
+use carbon_core::{
+    deserialize::ArrangeAccounts, error::Error, instruction::DecodedInstruction,
+    transaction::TransactionMetadata,
+};
+use carbon_raydium_amm_v4_decoder::instructions::{
+    RaydiumAmmV4Instruction, initialize2::Initialize2,
+};
+use solana_sdk::{instruction::AccountMeta, pubkey::Pubkey};
+
+use crate::{
+    constants::SOLANA_PUBKEY,
+    fees::FeeRates,
+    transfer::{SwapTransfers, extract_swap_transfers, get_token_account_mint},
+    types::{FailedSwapAttempt, PoolState, Swap, SwapAmount},
+    utils::get_now_timestamp_millis,
+};
+
+use super::*;
+
+pub struct RaydiumAmmV4Monitor {
+    pub sender: EventSender,
+    pub events_cache: Arc<EventsCache>,
+    pub instruction_positions: InstructionPositions,
+    /// Emit `FailedSwapAttempt` events for failed transactions
+    pub failed_swap_attempts: bool,
+    pub fee_rates: FeeRates,
+}
+
+#[tonic::async_trait]
+impl Processor for RaydiumAmmV4Monitor {
+    type InputType = InstructionProcessorInputType<RaydiumAmmV4Instruction>;
+
+    async fn process(
+        &mut self,
+        (metadata, instruction, nested_instructions): Self::InputType,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let received_at = get_now_timestamp_millis();
+
+        let failed = metadata.transaction_metadata.meta.status.is_err();
+        if failed && !self.failed_swap_attempts {
+            return Ok(());
+        }
+
+        let (event_type, user) = match instruction.data {
+            // Failed transactions leave nothing but the instruction behind
+            _ if failed => {
+                match get_failed_swap_attempt(&metadata.transaction_metadata, &instruction)? {
+                    Some((failed_swap_attempt, user)) => (
+                        EventType::FailedSwapAttempt(Box::new(failed_swap_attempt)),
+                        user,
+                    ),
+                    None => {
+                        return Ok(());
+                    }
+                }
+            }
+            RaydiumAmmV4Instruction::SwapBaseIn(_) | RaydiumAmmV4Instruction::SwapBaseOut(_) => {
+                let accounts = match SwapAccounts::arrange(&instruction.accounts) {
+                    Some(accounts) => accounts,
+                    None => {
+                        return Err(Error::Custom(
+                            "raydium amm v4 swap accounts not found".to_string(),
+                        ));
+                    }
+                };
+
+                let SwapTransfers {
+                    pay_transfer,
+                    receive_transfer,
+                    token_in_reserve,
+                    token_out_reserve,
+                } = extract_swap_transfers(
+                    &metadata.transaction_metadata,
+                    nested_instructions.iter(),
+                    &accounts.user_source_token_account,
+                    &accounts.user_destination_token_account,
+                )?;
+
+                let token_in_mint = pay_transfer.get_mint()?;
+
+                (
+                    EventType::Swap(Box::new(Swap {
+                        token_in_amount: pay_transfer.info.amount,
+                        token_in_decimals: pay_transfer.decimals,
+                        token_in_mint,
+                        token_out_amount: receive_transfer.info.amount,
+                        token_out_decimals: receive_transfer.decimals,
+                        token_out_mint: receive_transfer.get_mint()?,
+                        platform: SwapPlatform::Raydium,
+                        pool: accounts.amm,
+                        base_vault: accounts.pool_coin_token_account,
+                        quote_vault: accounts.pool_pc_token_account,
+                        pool_state: PoolState::ConstantProduct {
+                            token_in_reserve,
+                            token_out_reserve,
+                        },
+                        fees: Some(
+                            self.fee_rates
+                                .get_swap_fees(pay_transfer.info.amount, token_in_mint),
+                        ),
+                    })),
+                    accounts.user_source_owner,
+                )
+            }
+            RaydiumAmmV4Instruction::Initialize2(_initialize2) => {
+                let accounts = match Initialize2::arrange_accounts(&instruction.accounts) {
+                    Some(accounts) => accounts,
+                    None => {
+                        return Err(Error::Custom(
+                            "raydium amm v4 initialize2 accounts not found".to_string(),
+                        ));
+                    }
+                };
+
+                // Most pools are paired against wrapped SOL, report the other side
+                let mint = if accounts.coin_mint == SOLANA_PUBKEY {
+                    accounts.pc_mint
+                } else {
+                    accounts.coin_mint
+                };
+
+                (
+                    EventType::PoolCreation {
+                        mint,
+                        platform: SwapPlatform::Raydium,
+                    },
+                    accounts.user_wallet,
+                )
+            }
+            _ => {
+                return Ok(());
+            }
+        };
+
+        let position = self.instruction_positions.resolve(
+            &metadata,
+            &instruction.program_id,
+            &instruction.accounts,
+        );
+        let event = Event::new(
+            &metadata.transaction_metadata,
+            position,
+            event_type,
+            user,
+            received_at,
+        );
+
+        if !self.events_cache.insert(&event) {
+            return Ok(());
+        }
+
+        self.sender.send(event).await.map_err(|error| {
+            Error::Custom(format!("send raydium amm v4 event to receiver: {}", error))
+        })
+    }
+}
+
+/// Accounts of SwapBaseIn and SwapBaseOut, which share the same layout. The
+/// amm target orders account comes right after the open orders one, or not at
+/// all in the 17 accounts layout most swaps use now
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SwapAccounts {
+    amm: Pubkey,
+    pool_coin_token_account: Pubkey,
+    pool_pc_token_account: Pubkey,
+    user_source_token_account: Pubkey,
+    user_destination_token_account: Pubkey,
+    user_source_owner: Pubkey,
+}
+
+impl SwapAccounts {
+    fn arrange(accounts: &[AccountMeta]) -> Option<Self> {
+        let offset = match accounts.len() {
+            17 => 0,
+            18 => 1,
+            _ => {
+                return None;
+            }
+        };
+
+        Some(Self {
+            amm: accounts[1].pubkey,
+            pool_coin_token_account: accounts[4 + offset].pubkey,
+            pool_pc_token_account: accounts[5 + offset].pubkey,
+            user_source_token_account: accounts[14 + offset].pubkey,
+            user_destination_token_account: accounts[15 + offset].pubkey,
+            user_source_owner: accounts[16 + offset].pubkey,
+        })
+    }
+}
+
+fn get_failed_swap_attempt(
+    transaction_metadata: &TransactionMetadata,
+    instruction: &DecodedInstruction<RaydiumAmmV4Instruction>,
+) -> CarbonResult<Option<(FailedSwapAttempt, Pubkey)>> {
+    let amount = match &instruction.data {
+        RaydiumAmmV4Instruction::SwapBaseIn(swap_base_in) => SwapAmount::ExactIn {
+            amount_in: swap_base_in.amount_in,
+            minimum_amount_out: Some(swap_base_in.minimum_amount_out),
+        },
+        RaydiumAmmV4Instruction::SwapBaseOut(swap_base_out) => SwapAmount::ExactOut {
+            amount_out: swap_base_out.amount_out,
+            maximum_amount_in: swap_base_out.max_amount_in,
+        },
+        _ => {
+            return Ok(None);
+        }
+    };
+
+    let accounts = match SwapAccounts::arrange(&instruction.accounts) {
+        Some(accounts) => accounts,
+        None => {
+            return Err(Error::Custom(
+                "raydium amm v4 swap accounts not found".to_string(),
+            ));
+        }
+    };
+
+    Ok(Some((
+        FailedSwapAttempt::new(
+            transaction_metadata,
+            SwapPlatform::Raydium,
+            accounts.amm,
+            get_token_account_mint(transaction_metadata, &accounts.user_source_token_account),
+            get_token_account_mint(
+                transaction_metadata,
+                &accounts.user_destination_token_account,
+            ),
+            amount,
+        ),
+        accounts.user_source_owner,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{
+        TransactionsListener,
+        decode::parse_transaction,
+        mock_rpc::load_fixture,
+        types::{Fee, SwapFees},
+    };
+
+    const USER: Pubkey = solana_sdk::pubkey!("2w6AppJQDBbxC6A22v5RE9oST2CibSf3DgxgDXGpd8nL");
+    const MINT: Pubkey = solana_sdk::pubkey!("G9cTfCXXqkRFt3iHCbntyD2n2hcRc61YwqdewLQ5dm5X");
+    const POOL: Pubkey = solana_sdk::pubkey!("HveSWbD1xyYk7Lbjz1U9NwvoNiPAeccj8ri98j6ZZxp5");
+    const POOL_COIN_VAULT: Pubkey =
+        solana_sdk::pubkey!("2puAQE8yxf3HZXMCpKHLaEHGVUTvHk8KG9uBXmAA4pQz");
+    const POOL_PC_VAULT: Pubkey =
+        solana_sdk::pubkey!("p5JT7coFkmnF7LECvfszzmKGS6eA5ak8t9iXi1PPXZJ");
+
+    async fn decode_swaps(name: &str) -> Vec<(Swap, Pubkey)> {
+        let update = parse_transaction(&load_fixture(name).to_string()).unwrap();
+        let (transactions_listener, _receiver) =
+            TransactionsListener::new(16, 255, HashMap::new()).unwrap();
+
+        let decoded = transactions_listener.decode(update).await.unwrap();
+        assert!(decoded.errors.is_empty(), "{}: {:?}", name, decoded.errors);
+
+        decoded
+            .events
+            .into_iter()
+            .map(|event| match event.event_type {
+                EventType::Swap(swap) => (*swap, event.user),
+                event_type => panic!("{}: expected a swap, got {:?}", name, event_type),
+            })
+            .collect()
+    }
+
+    /// 1 SOL for 34,623.467612 tokens, with and without the amm target orders
+    /// account
+    async fn assert_swap(name: &str) {
+        let swaps = decode_swaps(name).await;
+        assert_eq!(swaps.len(), 1, "{}", name);
+
+        let (swap, user) = &swaps[0];
+        assert_eq!(*user, USER, "{}", name);
+        assert_eq!(swap.pool, POOL, "{}", name);
+        assert_eq!(swap.base_vault, POOL_COIN_VAULT, "{}", name);
+        assert_eq!(swap.quote_vault, POOL_PC_VAULT, "{}", name);
+        assert_eq!(swap.token_in_mint, SOLANA_PUBKEY, "{}", name);
+        assert_eq!(swap.token_in_amount, 1_000_000_000, "{}", name);
+        assert_eq!(swap.token_in_decimals, 9, "{}", name);
+        assert_eq!(swap.token_out_mint, MINT, "{}", name);
+        assert_eq!(swap.token_out_amount, 34_623_467_612, "{}", name);
+        assert_eq!(swap.token_out_decimals, 6, "{}", name);
+        assert_eq!(
+            swap.pool_state,
+            PoolState::ConstantProduct {
+                token_in_reserve: 81_000_000_000,
+                token_out_reserve: 2_765_376_532_388,
+            },
+            "{}",
+            name
+        );
+        // 25 bps of the input, 12% of which goes to the protocol
+        assert_eq!(
+            swap.fees,
+            Some(SwapFees {
+                lp_fee: Some(Fee {
+                    amount: 2_200_000,
+                    mint: SOLANA_PUBKEY,
+                }),
+                protocol_fee: Some(Fee {
+                    amount: 300_000,
+                    mint: SOLANA_PUBKEY,
+                }),
+                creator_fee: None,
+                fund_fee: None,
+            }),
+            "{}",
+            name
+        );
+    }
+
+    #[tokio::test]
+    async fn test_decodes_swap_base_in_of_both_layouts() {
+        assert_swap("raydium_amm_v4_swap_base_in").await;
+        assert_swap("raydium_amm_v4_swap_base_in_target_orders").await;
+    }
+
+    #[tokio::test]
+    async fn test_decodes_swap_base_out_of_both_layouts() {
+        assert_swap("raydium_amm_v4_swap_base_out").await;
+        assert_swap("raydium_amm_v4_swap_base_out_target_orders").await;
+    }
+
+    #[tokio::test]
+    async fn test_decodes_hops_paying_from_the_same_account() {
+        let swaps = decode_swaps("raydium_amm_v4_split_route").await;
+
+        assert_eq!(
+            swaps
+                .iter()
+                .map(|(swap, _)| (
+                    swap.token_in_amount,
+                    swap.token_out_amount,
+                    swap.pool_state.clone()
+                ))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    600_000_000,
+                    20_848_626_534,
+                    PoolState::ConstantProduct {
+                        token_in_reserve: 80_600_000_000,
+                        token_out_reserve: 2_779_151_373_466,
+                    }
+                ),
+                (
+                    400_000_000,
+                    3_960_396_039,
+                    PoolState::ConstantProduct {
+                        token_in_reserve: 40_400_000_000,
+                        token_out_reserve: 396_039_603_961,
+                    }
+                ),
+            ]
+        );
+    }
+}
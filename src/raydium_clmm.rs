@@ -0,0 +1,345 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use carbon_core::{
+    deserialize::{ArrangeAccounts, CarbonDeserialize},
+    error::Error,
+};
+use carbon_raydium_clmm_decoder::instructions::{
+    RaydiumClmmInstruction, swap::Swap as ClmmSwap, swap_event::SwapEvent, swap_v2::SwapV2,
+};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    fees::{FeeRates, get_amm_config_swap_fees},
+    transfer::{SwapTransfers, extract_swap_transfers, get_token_account_mint},
+    types::{FailedSwapAttempt, PoolState, Swap, SwapAmount},
+    utils::get_now_timestamp_millis,
+};
+
+use super::*;
+
+/// Anchor prefixes events emitted through self-CPI with this tag, carbon
+/// expects it in front of the event discriminator.
+const EVENT_IX_TAG: [u8; 8] = [0xe4, 0x45, 0xa5, 0x2e, 0x51, 0xcb, 0x9a, 0x1d];
+const PROGRAM_DATA_LOG_PREFIX: &str = "Program data: ";
+
+pub struct RaydiumClmmMonitor {
+    pub sender: EventSender,
+    pub events_cache: Arc<EventsCache>,
+    pub instruction_positions: InstructionPositions,
+    /// Emit `FailedSwapAttempt` events for failed transactions
+    pub failed_swap_attempts: bool,
+    /// Fee rates by amm config
+    pub amm_configs: HashMap<Pubkey, FeeRates>,
+}
+
+#[tonic::async_trait]
+impl Processor for RaydiumClmmMonitor {
+    type InputType = InstructionProcessorInputType<RaydiumClmmInstruction>;
+
+    async fn process(
+        &mut self,
+        (metadata, instruction, nested_instructions): Self::InputType,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let received_at = get_now_timestamp_millis();
+
+        let failed = metadata.transaction_metadata.meta.status.is_err();
+        if failed && !self.failed_swap_attempts {
+            return Ok(());
+        }
+
+        let (
+            payer,
+            amm_config,
+            pool,
+            input_token_account,
+            output_token_account,
+            input_vault,
+            output_vault,
+            amount,
+        ) = match instruction.data {
+            RaydiumClmmInstruction::Swap(swap) => {
+                match ClmmSwap::arrange_accounts(&instruction.accounts) {
+                    Some(accounts) => (
+                        accounts.payer,
+                        accounts.amm_config,
+                        accounts.pool_state,
+                        accounts.input_token_account,
+                        accounts.output_token_account,
+                        accounts.input_vault,
+                        accounts.output_vault,
+                        get_swap_amount(
+                            swap.amount,
+                            swap.other_amount_threshold,
+                            swap.is_base_input,
+                        ),
+                    ),
+                    None => {
+                        return Err(Error::Custom(
+                            "raydium clmm swap accounts not found".to_string(),
+                        ));
+                    }
+                }
+            }
+            RaydiumClmmInstruction::SwapV2(swap_v2) => {
+                match SwapV2::arrange_accounts(&instruction.accounts) {
+                    Some(accounts) => (
+                        accounts.payer,
+                        accounts.amm_config,
+                        accounts.pool_state,
+                        accounts.input_token_account,
+                        accounts.output_token_account,
+                        accounts.input_vault,
+                        accounts.output_vault,
+                        get_swap_amount(
+                            swap_v2.amount,
+                            swap_v2.other_amount_threshold,
+                            swap_v2.is_base_input,
+                        ),
+                    ),
+                    None => {
+                        return Err(Error::Custom(
+                            "raydium clmm swap v2 accounts not found".to_string(),
+                        ));
+                    }
+                }
+            }
+            _ => {
+                return Ok(());
+            }
+        };
+
+        let event_type = if failed {
+            EventType::FailedSwapAttempt(Box::new(FailedSwapAttempt::new(
+                &metadata.transaction_metadata,
+                SwapPlatform::RaydiumClmm,
+                pool,
+                get_token_account_mint(&metadata.transaction_metadata, &input_token_account),
+                get_token_account_mint(&metadata.transaction_metadata, &output_token_account),
+                amount,
+            )))
+        } else {
+            let SwapTransfers {
+                pay_transfer,
+                receive_transfer,
+                ..
+            } = extract_swap_transfers(
+                &metadata.transaction_metadata,
+                nested_instructions.iter(),
+                &input_token_account,
+                &output_token_account,
+            )?;
+
+            let token_in_mint = pay_transfer.get_mint()?;
+            let token_out_mint = receive_transfer.get_mint()?;
+
+            // Pools are created with token 0 being the smaller mint
+            let (base_vault, quote_vault) = if token_in_mint < token_out_mint {
+                (input_vault, output_vault)
+            } else {
+                (output_vault, input_vault)
+            };
+
+            let log_messages = match &metadata.transaction_metadata.meta.log_messages {
+                Some(log_messages) => log_messages,
+                None => {
+                    return Err(Error::Custom("log messages not found".to_string()));
+                }
+            };
+
+            let swap_event =
+                match find_swap_event(log_messages, &pool, &payer, pay_transfer.info.amount) {
+                    Some(swap_event) => swap_event,
+                    None => {
+                        return Err(Error::Custom(
+                            "raydium clmm swap event not found".to_string(),
+                        ));
+                    }
+                };
+
+            EventType::Swap(Box::new(Swap {
+                token_in_amount: pay_transfer.info.amount,
+                token_in_decimals: pay_transfer.decimals,
+                token_in_mint,
+                token_out_amount: receive_transfer.info.amount,
+                token_out_decimals: receive_transfer.decimals,
+                token_out_mint,
+                platform: SwapPlatform::RaydiumClmm,
+                pool,
+                base_vault,
+                quote_vault,
+                pool_state: PoolState::ConcentratedLiquidity {
+                    sqrt_price_x64: swap_event.sqrt_price_x64,
+                    tick: swap_event.tick,
+                    liquidity: swap_event.liquidity,
+                },
+                fees: get_amm_config_swap_fees(
+                    &self.amm_configs,
+                    &amm_config,
+                    pay_transfer.info.amount,
+                    token_in_mint,
+                ),
+            }))
+        };
+
+        let position = self.instruction_positions.resolve(
+            &metadata,
+            &instruction.program_id,
+            &instruction.accounts,
+        );
+        let event = Event::new(
+            &metadata.transaction_metadata,
+            position,
+            event_type,
+            payer,
+            received_at,
+        );
+
+        if !self.events_cache.insert(&event) {
+            return Ok(());
+        }
+
+        self.sender.send(event).await.map_err(|error| {
+            Error::Custom(format!("send raydium clmm event to receiver: {}", error))
+        })
+    }
+}
+
+fn get_swap_amount(amount: u64, other_amount_threshold: u64, is_base_input: bool) -> SwapAmount {
+    if is_base_input {
+        SwapAmount::ExactIn {
+            amount_in: amount,
+            minimum_amount_out: Some(other_amount_threshold),
+        }
+    } else {
+        SwapAmount::ExactOut {
+            amount_out: amount,
+            maximum_amount_in: other_amount_threshold,
+        }
+    }
+}
+
+/// CLMM emits `SwapEvent` through program logs rather than self-CPI, so it
+/// never reaches the pipeline as an instruction. Match it to the swap by
+/// pool, sender and input amount.
+fn find_swap_event(
+    log_messages: &[String],
+    pool: &Pubkey,
+    sender: &Pubkey,
+    amount_in: u64,
+) -> Option<SwapEvent> {
+    log_messages
+        .iter()
+        .filter_map(|log_message| log_message.strip_prefix(PROGRAM_DATA_LOG_PREFIX))
+        .filter_map(|data| STANDARD.decode(data).ok())
+        .filter_map(|data| SwapEvent::deserialize(&[EVENT_IX_TAG.as_slice(), &data].concat()))
+        .find(|swap_event| {
+            let event_amount_in = if swap_event.zero_for_one {
+                swap_event.amount0
+            } else {
+                swap_event.amount1
+            };
+
+            swap_event.pool_state == *pool
+                && swap_event.sender == *sender
+                && event_amount_in == amount_in
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{
+        TransactionsListener,
+        constants::SOLANA_PUBKEY,
+        decode::parse_transaction,
+        mock_rpc::load_fixture,
+        types::{Fee, SwapFees},
+    };
+
+    const USER: Pubkey = solana_sdk::pubkey!("DfArQg3uydcGgbVbtGGfNEYumHKdDfakZijaPyCbPngC");
+    const MINT: Pubkey = solana_sdk::pubkey!("2PJ1G2SF8UKEmKdczro3nfa4Fj1SetGLHGK5WxFgL5Xp");
+    const POOL: Pubkey = solana_sdk::pubkey!("7JhqbWvXazPam8zHbSzZejfNQFW8cvW5rBEyG4GrbFj5");
+    const AMM_CONFIG: Pubkey = solana_sdk::pubkey!("D8CF8Rw1fX8kKdNFF3vc7SdYbdnba6cFgzo4H6o1MWwr");
+    const WSOL_VAULT: Pubkey = solana_sdk::pubkey!("D5N9fZs9g2BCF1kUV6UR6tduRtu7A16CRiuNeEj14pPq");
+    const TOKEN_VAULT: Pubkey = solana_sdk::pubkey!("9y46NDwUWGigT45zumMpPxrDhqPhw2xzTotMr9mMtrJj");
+
+    async fn decode_swap(amm_configs: HashMap<Pubkey, FeeRates>) -> (Swap, Pubkey) {
+        let update = parse_transaction(&load_fixture("raydium_clmm_swap").to_string()).unwrap();
+        let (mut transactions_listener, _receiver) =
+            TransactionsListener::new(16, 255, HashMap::new()).unwrap();
+        transactions_listener.config.raydium_clmm_amm_configs = amm_configs;
+
+        let decoded = transactions_listener.decode(update).await.unwrap();
+        assert!(decoded.errors.is_empty(), "{:?}", decoded.errors);
+        assert_eq!(decoded.events.len(), 1);
+
+        let event = decoded.events.into_iter().next().unwrap();
+        match event.event_type {
+            EventType::Swap(swap) => (*swap, event.user),
+            event_type => panic!("expected a swap, got {:?}", event_type),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decodes_swap() {
+        let amm_configs = HashMap::from([(
+            AMM_CONFIG,
+            FeeRates {
+                trade_fee_rate: 2_500,
+                protocol_fee_rate: 120_000,
+                fund_fee_rate: 40_000,
+            },
+        )]);
+        let (swap, user) = decode_swap(amm_configs).await;
+
+        // 1 SOL for 152.345678 tokens, SOL being token 0
+        assert_eq!(user, USER);
+        assert_eq!(swap.pool, POOL);
+        assert_eq!(swap.base_vault, WSOL_VAULT);
+        assert_eq!(swap.quote_vault, TOKEN_VAULT);
+        assert_eq!(swap.token_in_mint, SOLANA_PUBKEY);
+        assert_eq!(swap.token_in_amount, 1_000_000_000);
+        assert_eq!(swap.token_in_decimals, 9);
+        assert_eq!(swap.token_out_mint, MINT);
+        assert_eq!(swap.token_out_amount, 152_345_678);
+        assert_eq!(swap.token_out_decimals, 6);
+        assert_eq!(
+            swap.pool_state,
+            PoolState::ConcentratedLiquidity {
+                sqrt_price_x64: 7_200_000_000_000_000_000,
+                tick: -20_395,
+                liquidity: 98_765_432_100,
+            }
+        );
+        // 0.25% of the input, 12% of which goes to the protocol and 4% to the
+        // fund
+        assert_eq!(
+            swap.fees,
+            Some(SwapFees {
+                lp_fee: Some(Fee {
+                    amount: 2_100_000,
+                    mint: SOLANA_PUBKEY,
+                }),
+                protocol_fee: Some(Fee {
+                    amount: 300_000,
+                    mint: SOLANA_PUBKEY,
+                }),
+                creator_fee: None,
+                fund_fee: Some(Fee {
+                    amount: 100_000,
+                    mint: SOLANA_PUBKEY,
+                }),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unknown_amm_config_has_unknown_fees() {
+        let (swap, _) = decode_swap(HashMap::new()).await;
+
+        assert_eq!(swap.fees, None);
+    }
+}
@@ -1,23 +1,29 @@
-use std::str::FromStr;
-
-use carbon_core::error::Error;
-use carbon_raydium_cpmm_decoder::instructions::RaydiumCpmmInstruction;
+use carbon_core::{
+    deserialize::ArrangeAccounts, error::Error, instruction::DecodedInstruction,
+    transaction::TransactionMetadata,
+};
+use carbon_raydium_cpmm_decoder::instructions::{
+    RaydiumCpmmInstruction, swap_base_input::SwapBaseInput,
+};
 use solana_sdk::pubkey::Pubkey;
 
 use crate::{
-    transfer::{
-        extract_spl_token_info, is_transfer_check_instruction, is_transfer_instruction,
-        process_transfer, process_transfer_check,
-    },
-    types::Swap,
-    utils::get_now_timestamp,
+    fees::{FeeRates, get_amm_config_swap_fees},
+    transfer::{SwapTransfers, extract_swap_transfers},
+    types::{FailedSwapAttempt, PoolState, Swap, SwapAmount},
+    utils::get_now_timestamp_millis,
 };
 
 use super::*;
 
 pub struct RaydiumCpmmMonitor {
-    pub sender: Sender<Event>,
-    pub parsed_events: Arc<RwLock<HashSet<Event>>>,
+    pub sender: EventSender,
+    pub events_cache: Arc<EventsCache>,
+    pub instruction_positions: InstructionPositions,
+    /// Emit `FailedSwapAttempt` events for failed transactions
+    pub failed_swap_attempts: bool,
+    /// Fee rates by amm config
+    pub amm_configs: HashMap<Pubkey, FeeRates>,
 }
 
 #[tonic::async_trait]
@@ -26,184 +32,84 @@ impl Processor for RaydiumCpmmMonitor {
 
     async fn process(
         &mut self,
-        (metadata, instruction, _nested_instructions): Self::InputType,
+        (metadata, instruction, nested_instructions): Self::InputType,
         _metrics: Arc<MetricsCollection>,
     ) -> CarbonResult<()> {
-        let signature = metadata.transaction_metadata.signature;
-        let now_timestamp = get_now_timestamp();
+        let received_at = get_now_timestamp_millis();
 
-        let status = metadata.transaction_metadata.meta.status;
-        if status.is_err() {
+        let failed = metadata.transaction_metadata.meta.status.is_err();
+        if failed && !self.failed_swap_attempts {
             return Ok(());
         }
 
         let event_type = match instruction.data {
-            RaydiumCpmmInstruction::SwapBaseInput(_)
-            | RaydiumCpmmInstruction::SwapBaseOutput(_) => {
-                let input_token_account = instruction.accounts[4].pubkey.to_string();
-                let output_token_account = instruction.accounts[5].pubkey.to_string();
-
-                let post_token_balances = match metadata
-                    .transaction_metadata
-                    .meta
-                    .post_token_balances
-                    .clone()
-                {
-                    Some(post_token_balances) => post_token_balances,
-                    None => {
-                        return Err(Error::Custom("post token balances not found".to_string()));
+            // Failed transactions leave nothing but the instruction behind
+            _ if failed => {
+                match get_failed_swap_attempt(&metadata.transaction_metadata, &instruction)? {
+                    Some(failed_swap_attempt) => {
+                        EventType::FailedSwapAttempt(Box::new(failed_swap_attempt))
                     }
-                };
-
-                let pre_token_balances = match metadata
-                    .transaction_metadata
-                    .meta
-                    .pre_token_balances
-                    .clone()
-                {
-                    Some(pre_token_balances) => pre_token_balances,
                     None => {
-                        return Err(Error::Custom("pre token balances not found".to_string()));
+                        return Ok(());
                     }
-                };
-
-                let inner_instructions = match metadata
-                    .transaction_metadata
-                    .meta
-                    .inner_instructions
-                    .clone()
-                {
-                    Some(inner_instructions) => inner_instructions,
+                }
+            }
+            RaydiumCpmmInstruction::SwapBaseInput(_)
+            | RaydiumCpmmInstruction::SwapBaseOutput(_) => {
+                // SwapBaseInput and SwapBaseOutput share the same account layout
+                let accounts = match SwapBaseInput::arrange_accounts(&instruction.accounts) {
+                    Some(accounts) => accounts,
                     None => {
-                        return Err(Error::Custom("inner instructions not found".to_string()));
+                        return Err(Error::Custom(
+                            "raydium cpmm swap accounts not found".to_string(),
+                        ));
                     }
                 };
 
-                let mut address_used = metadata
-                    .transaction_metadata
-                    .message
-                    .static_account_keys()
-                    .to_vec();
-
-                address_used.extend(
-                    metadata
-                        .transaction_metadata
-                        .meta
-                        .loaded_addresses
-                        .writable
-                        .clone(),
-                );
-
-                address_used.extend(
-                    metadata
-                        .transaction_metadata
-                        .meta
-                        .loaded_addresses
-                        .readonly
-                        .clone(),
-                );
-
-                let spl_tokens_info = extract_spl_token_info(
-                    post_token_balances.clone(),
-                    pre_token_balances,
-                    &address_used,
-                );
-
-                let mut transfer_data = Vec::new();
-                for inner_instruction in inner_instructions {
-                    for inner_instruction in inner_instruction.instructions {
-                        if is_transfer_instruction(&inner_instruction.instruction, &address_used) {
-                            match process_transfer(
-                                &inner_instruction.instruction,
-                                &address_used,
-                                &spl_tokens_info,
-                            ) {
-                                Ok(transfer) => transfer_data.push(transfer),
-                                Err(error) => {
-                                    return Err(error);
-                                }
-                            }
-                        } else if is_transfer_check_instruction(
-                            &inner_instruction.instruction,
-                            &address_used,
-                        ) {
-                            match process_transfer_check(
-                                &inner_instruction.instruction,
-                                &address_used,
-                                &spl_tokens_info,
-                            ) {
-                                Ok(transfer) => transfer_data.push(transfer),
-                                Err(error) => {
-                                    return Err(error);
-                                }
-                            }
-                        }
-                    }
-                }
-
-                if transfer_data.is_empty() {
-                    return Err(Error::Custom("transfer data not found".to_string()));
-                }
-
-                let mut pay_transfer = None;
-                let mut receive_transfer = None;
-
-                for transfer in transfer_data.clone() {
-                    // Transfer in
-                    if transfer.info.source == input_token_account {
-                        pay_transfer = Some(transfer);
-                    }
-                    // Transfer out
-                    else if transfer.info.destination == output_token_account {
-                        receive_transfer = Some(transfer);
-                    }
-                }
-
-                let pay_transfer = if let Some(pay_transfer) = pay_transfer {
-                    pay_transfer
-                } else {
-                    return Err(Error::Custom("pay transfer not found".to_string()));
-                };
+                let SwapTransfers {
+                    pay_transfer,
+                    receive_transfer,
+                    token_in_reserve,
+                    token_out_reserve,
+                } = extract_swap_transfers(
+                    &metadata.transaction_metadata,
+                    nested_instructions.iter(),
+                    &accounts.input_token_account,
+                    &accounts.output_token_account,
+                )?;
 
-                let receive_transfer = if let Some(receive_transfer) = receive_transfer {
-                    receive_transfer
-                } else {
-                    return Err(Error::Custom("receive transfer not found".to_string()));
-                };
+                // Pools are created with token 0 being the smaller mint
+                let (base_vault, quote_vault) =
+                    if accounts.input_token_mint < accounts.output_token_mint {
+                        (accounts.input_vault, accounts.output_vault)
+                    } else {
+                        (accounts.output_vault, accounts.input_vault)
+                    };
 
-                let mut token_in_reserve = 0;
-                let mut token_out_reserve = 0;
-
-                for post_token_balance in post_token_balances {
-                    let account =
-                        address_used[post_token_balance.account_index as usize].to_string();
-
-                    if account == pay_transfer.info.destination {
-                        token_in_reserve = post_token_balance
-                            .ui_token_amount
-                            .amount
-                            .parse::<u64>()
-                            .unwrap();
-                    } else if account == receive_transfer.info.source {
-                        token_out_reserve = post_token_balance
-                            .ui_token_amount
-                            .amount
-                            .parse::<u64>()
-                            .unwrap();
-                    }
-                }
+                let token_in_mint = pay_transfer.get_mint()?;
 
-                EventType::Swap(Swap {
+                EventType::Swap(Box::new(Swap {
                     token_in_amount: pay_transfer.info.amount,
                     token_in_decimals: pay_transfer.decimals,
-                    token_in_mint: Pubkey::from_str(&pay_transfer.mint).unwrap(),
+                    token_in_mint,
                     token_out_amount: receive_transfer.info.amount,
                     token_out_decimals: receive_transfer.decimals,
-                    token_out_mint: Pubkey::from_str(&receive_transfer.mint).unwrap(),
+                    token_out_mint: receive_transfer.get_mint()?,
                     platform: SwapPlatform::RaydiumCpmm,
-                    token_in_reserve,
-                    token_out_reserve,
-                })
+                    pool: accounts.pool_state,
+                    base_vault,
+                    quote_vault,
+                    pool_state: PoolState::ConstantProduct {
+                        token_in_reserve,
+                        token_out_reserve,
+                    },
+                    fees: get_amm_config_swap_fees(
+                        &self.amm_configs,
+                        &accounts.amm_config,
+                        pay_transfer.info.amount,
+                        token_in_mint,
+                    ),
+                }))
             }
             RaydiumCpmmInstruction::Initialize(_initialize) => EventType::PoolCreation {
                 mint: instruction.accounts[0].pubkey,
@@ -214,22 +120,142 @@ impl Processor for RaydiumCpmmMonitor {
             }
         };
 
-        let event = Event {
-            signature,
+        let position = self.instruction_positions.resolve(
+            &metadata,
+            &instruction.program_id,
+            &instruction.accounts,
+        );
+        let event = Event::new(
+            &metadata.transaction_metadata,
+            position,
             event_type,
-            user: Pubkey::from_str(&instruction.accounts[0].pubkey.to_string()).unwrap(),
-            timestamp: now_timestamp,
-        };
+            instruction.accounts[0].pubkey,
+            received_at,
+        );
 
-        let parsed_events_cache = self.parsed_events.read().await.clone();
-        if parsed_events_cache.contains(&event) {
+        if !self.events_cache.insert(&event) {
             return Ok(());
         }
 
-        self.parsed_events.write().await.insert(event.clone());
-
         self.sender.send(event).await.map_err(|error| {
             Error::Custom(format!("send raydium cpmm event to receiver: {}", error))
         })
     }
 }
+
+fn get_failed_swap_attempt(
+    transaction_metadata: &TransactionMetadata,
+    instruction: &DecodedInstruction<RaydiumCpmmInstruction>,
+) -> CarbonResult<Option<FailedSwapAttempt>> {
+    let amount = match &instruction.data {
+        RaydiumCpmmInstruction::SwapBaseInput(swap_base_input) => SwapAmount::ExactIn {
+            amount_in: swap_base_input.amount_in,
+            minimum_amount_out: Some(swap_base_input.minimum_amount_out),
+        },
+        RaydiumCpmmInstruction::SwapBaseOutput(swap_base_output) => SwapAmount::ExactOut {
+            amount_out: swap_base_output.amount_out,
+            maximum_amount_in: swap_base_output.max_amount_in,
+        },
+        _ => {
+            return Ok(None);
+        }
+    };
+
+    let accounts = match SwapBaseInput::arrange_accounts(&instruction.accounts) {
+        Some(accounts) => accounts,
+        None => {
+            return Err(Error::Custom(
+                "raydium cpmm swap accounts not found".to_string(),
+            ));
+        }
+    };
+
+    Ok(Some(FailedSwapAttempt::new(
+        transaction_metadata,
+        SwapPlatform::RaydiumCpmm,
+        accounts.pool_state,
+        Some(accounts.input_token_mint),
+        Some(accounts.output_token_mint),
+        amount,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        TransactionsListener,
+        constants::SOLANA_PUBKEY,
+        decode::parse_transaction,
+        mock_rpc::load_fixture,
+        types::{Fee, SwapFees},
+    };
+
+    const USER: Pubkey = solana_sdk::pubkey!("6KKUTKpAQBBrmUUkS9D38gvQ3ykWqWHgEKPP8rsJWvUq");
+    const MINT: Pubkey = solana_sdk::pubkey!("HA6SvPejkKWdmyCyteV9AGo4muCZCQr6Q7kK8bq7W1qv");
+    const POOL: Pubkey = solana_sdk::pubkey!("rHVoTJYjy2YhMtVYmvYgqqAqyrnjJDKiZ63kyS4Sqdn");
+    const WSOL_VAULT: Pubkey = solana_sdk::pubkey!("7LtaU4VBb5Zu3eLYCuecc66aWKGs8Q2N5Gtnc28AuYqu");
+    const TOKEN_VAULT: Pubkey = solana_sdk::pubkey!("AHr6Nn1en5RcNHsyr6SjKxZj3r7ssypDcAPabGSJZDj1");
+
+    async fn decode_swap() -> (Swap, Pubkey) {
+        let update = parse_transaction(&load_fixture("raydium_cpmm_swap").to_string()).unwrap();
+        let (transactions_listener, _receiver) =
+            TransactionsListener::new(16, 255, HashMap::new()).unwrap();
+
+        let decoded = transactions_listener.decode(update).await.unwrap();
+        assert!(decoded.errors.is_empty(), "{:?}", decoded.errors);
+        assert_eq!(decoded.events.len(), 1);
+
+        let event = decoded.events.into_iter().next().unwrap();
+        match event.event_type {
+            EventType::Swap(swap) => (*swap, event.user),
+            event_type => panic!("expected a swap, got {:?}", event_type),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decodes_swap() {
+        let (swap, user) = decode_swap().await;
+
+        // 1 SOL for 152.345678 tokens
+        assert_eq!(user, USER);
+        assert_eq!(swap.platform, SwapPlatform::RaydiumCpmm);
+        // SOL is token 0, the smaller mint
+        assert_eq!(swap.pool, POOL);
+        assert_eq!(swap.base_vault, WSOL_VAULT);
+        assert_eq!(swap.quote_vault, TOKEN_VAULT);
+        assert_eq!(swap.token_in_mint, SOLANA_PUBKEY);
+        assert_eq!(swap.token_in_amount, 1_000_000_000);
+        assert_eq!(swap.token_in_decimals, 9);
+        assert_eq!(swap.token_out_mint, MINT);
+        assert_eq!(swap.token_out_amount, 152_345_678);
+        assert_eq!(swap.token_out_decimals, 6);
+        assert_eq!(
+            swap.pool_state,
+            PoolState::ConstantProduct {
+                token_in_reserve: 501_000_000_000,
+                token_out_reserve: 79_847_654_322,
+            }
+        );
+        // Through the public 0.25% config: 12% of the trade fee to the
+        // protocol and 4% to the fund
+        assert_eq!(
+            swap.fees,
+            Some(SwapFees {
+                lp_fee: Some(Fee {
+                    amount: 2_100_000,
+                    mint: SOLANA_PUBKEY,
+                }),
+                protocol_fee: Some(Fee {
+                    amount: 300_000,
+                    mint: SOLANA_PUBKEY,
+                }),
+                creator_fee: None,
+                fund_fee: Some(Fee {
+                    amount: 100_000,
+                    mint: SOLANA_PUBKEY,
+                }),
+            })
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc,
+    },
+    thread,
+    time::Instant,
+};
+
+use yellowstone_grpc_proto::{geyser::SubscribeUpdate, prost::Message};
+
+use crate::{
+    constants::{RECORDER_BUFFER_SIZE, RECORDER_FLUSH_INTERVAL},
+    prelude::*,
+    utils::get_now_timestamp_millis,
+};
+
+/// Start of every recording file, once decompressed
+pub(crate) const RECORDING_MAGIC: &[u8; 8] = b"TXLREC02";
+pub(crate) const RECORDING_EXTENSION: &str = "rec.zst";
+
+/// Writes the transaction and block meta updates sources receive to zstd
+/// compressed files in a directory, starting a new file once the current one
+/// holds `max_file_size` uncompressed bytes. Every update is framed by the time it
+/// was received, in milliseconds since the unix epoch as a little endian u64,
+/// then its length as a little endian u32, then its protobuf encoding.
+///
+/// Files are written from a thread of their own, finished when the recorder is
+/// dropped. A file cut short by a crash replays up to its last flush. Sources
+/// never wait for the thread, updates it has no room for are dropped and
+/// counted.
+pub struct Recorder {
+    sender: mpsc::SyncSender<(u64, Vec<u8>)>,
+    dropped: AtomicU64,
+}
+
+impl Recorder {
+    pub fn new(directory: PathBuf, max_file_size: u64) -> CarbonResult<Self> {
+        fs::create_dir_all(&directory)
+            .map_err(|error| Error::Custom(format!("create recording directory: {}", error)))?;
+
+        let (sender, receiver) = mpsc::sync_channel(RECORDER_BUFFER_SIZE);
+        thread::spawn(move || {
+            if let Err(error) = write_recordings(&directory, max_file_size, receiver) {
+                tracing::error!("write recording: {}", error);
+            }
+        });
+
+        Ok(Self {
+            sender,
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    /// Updates dropped so far because the writing thread fell behind
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Updates are lost once the writing thread stopped on an error, without
+    /// stopping the sources
+    pub fn record(&self, update: &SubscribeUpdate) {
+        if let Err(mpsc::TrySendError::Full(_)) = self
+            .sender
+            .try_send((get_now_timestamp_millis(), update.encode_to_vec()))
+            && self.dropped.fetch_add(1, Ordering::Relaxed) == 0
+        {
+            tracing::warn!("recorder full, dropping updates");
+        }
+    }
+}
+
+type RecordingEncoder = zstd::Encoder<'static, BufWriter<File>>;
+
+fn write_recordings(
+    directory: &Path,
+    max_file_size: u64,
+    receiver: mpsc::Receiver<(u64, Vec<u8>)>,
+) -> io::Result<()> {
+    let mut encoder: Option<(RecordingEncoder, u64)> = None;
+    let mut sequence = 0;
+    let mut flushed_at = Instant::now();
+
+    loop {
+        let timeout = RECORDER_FLUSH_INTERVAL.saturating_sub(flushed_at.elapsed());
+        match receiver.recv_timeout(timeout) {
+            Ok((received_at, update)) => {
+                if encoder
+                    .as_ref()
+                    .is_some_and(|(_, file_size)| *file_size >= max_file_size)
+                    && let Some((encoder, _)) = encoder.take()
+                {
+                    encoder.finish()?.flush()?;
+                }
+
+                let (encoder, file_size) = match &mut encoder {
+                    Some(encoder) => encoder,
+                    None => {
+                        let path = directory.join(format!(
+                            "{:020}-{:06}.{}",
+                            received_at, sequence, RECORDING_EXTENSION
+                        ));
+                        sequence += 1;
+
+                        let mut new_encoder =
+                            zstd::Encoder::new(BufWriter::new(File::create(path)?), 0)?;
+                        new_encoder.write_all(RECORDING_MAGIC)?;
+                        encoder.insert((new_encoder, RECORDING_MAGIC.len() as u64))
+                    }
+                };
+
+                encoder.write_all(&received_at.to_le_bytes())?;
+                encoder.write_all(&(update.len() as u32).to_le_bytes())?;
+                encoder.write_all(&update)?;
+                *file_size += 12 + update.len() as u64;
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                if let Some((encoder, _)) = encoder.take() {
+                    encoder.finish()?.flush()?;
+                }
+
+                return Ok(());
+            }
+        }
+
+        if flushed_at.elapsed() >= RECORDER_FLUSH_INTERVAL {
+            if let Some((encoder, _)) = &mut encoder {
+                encoder.flush()?;
+            }
+            flushed_at = Instant::now();
+        }
+    }
+}
+
+/// Reads the updates of a recording file in order, stopping at the end of the
+/// last complete one
+pub struct RecordingReader {
+    decoder: zstd::Decoder<'static, io::BufReader<File>>,
+}
+
+impl RecordingReader {
+    pub fn open(path: &Path) -> CarbonResult<Self> {
+        let file = File::open(path).map_err(|error| {
+            Error::Custom(format!("open recording {}: {}", path.display(), error))
+        })?;
+        let mut decoder = zstd::Decoder::new(file).map_err(|error| {
+            Error::Custom(format!("open recording {}: {}", path.display(), error))
+        })?;
+
+        let mut magic = [0; 8];
+        match decoder.read_exact(&mut magic) {
+            Ok(()) if &magic == RECORDING_MAGIC => {}
+            _ => {
+                return Err(Error::Custom(format!(
+                    "{} is not a recording",
+                    path.display()
+                )));
+            }
+        }
+
+        Ok(Self { decoder })
+    }
+
+    /// Time the update was received, and the update. `None` at the end of the
+    /// file, or of what was flushed of it
+    pub fn next_update(&mut self) -> CarbonResult<Option<(u64, SubscribeUpdate)>> {
+        let mut header = [0; 12];
+        if self.decoder.read_exact(&mut header).is_err() {
+            return Ok(None);
+        }
+
+        let received_at = u64::from_le_bytes(header[..8].try_into().unwrap());
+        let length = u32::from_le_bytes(header[8..].try_into().unwrap());
+
+        let mut update = vec![0; length as usize];
+        if self.decoder.read_exact(&mut update).is_err() {
+            return Ok(None);
+        }
+
+        let update = SubscribeUpdate::decode(update.as_slice())
+            .map_err(|error| Error::Custom(format!("decode recorded update: {}", error)))?;
+
+        Ok(Some((received_at, update)))
+    }
+}
+
+/// Recording files of a directory, oldest first
+pub fn get_recording_paths(directory: &Path) -> CarbonResult<Vec<PathBuf>> {
+    let entries = fs::read_dir(directory)
+        .map_err(|error| Error::Custom(format!("read recording directory: {}", error)))?;
+
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry
+            .map_err(|error| Error::Custom(format!("read recording directory: {}", error)))?
+            .path();
+
+        if path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .is_some_and(|file_name| file_name.ends_with(RECORDING_EXTENSION))
+        {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_dropped_updates() {
+        // Nothing drains the receiver
+        let (sender, _receiver) = mpsc::sync_channel(1);
+        let recorder = Recorder {
+            sender,
+            dropped: AtomicU64::new(0),
+        };
+
+        for _ in 0..3 {
+            recorder.record(&SubscribeUpdate::default());
+        }
+
+        assert_eq!(recorder.dropped(), 2);
+    }
+}
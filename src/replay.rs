@@ -0,0 +1,478 @@
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use carbon_core::{
+    datasource::{Datasource, Update, UpdateType},
+    instruction::{DecodedInstruction, InstructionDecoder},
+};
+use solana_sdk::{instruction::Instruction, signature::Signature};
+use tokio::{
+    sync::{
+        mpsc::{self, UnboundedSender},
+        oneshot, watch,
+    },
+    time::Instant,
+};
+use tokio_util::sync::CancellationToken;
+use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
+
+use crate::{
+    constants::{REPLAY_DRAIN_TIMEOUT, REPLAY_MAX_PENDING},
+    grpc_source::{BlockTimes, get_transaction_update},
+    position::{InstructionPosition, InstructionPositions},
+    prelude::*,
+    recorder::{RecordingReader, get_recording_paths},
+    transaction_indexes::TransactionIndexes,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplaySpeed {
+    /// Waits between updates as long as they were apart when recorded
+    RealTime,
+    AsFastAsPossible,
+}
+
+/// Recordings to run through the monitors, see `TransactionsListener::replay`
+#[derive(Debug, Clone)]
+pub struct Replay {
+    /// Recording files, or directories of them, replayed in order
+    pub paths: Vec<PathBuf>,
+    pub speed: ReplaySpeed,
+    /// Capacity of the events cache, the live one's to reproduce its
+    /// deduplication
+    pub cache_capacity: usize,
+}
+
+/// Times the replayed updates were received, so that their events get the
+/// recorded `received_at` instead of the time of the replay
+#[derive(Debug)]
+pub struct ReplayClock {
+    /// Updates sent to the pipeline and not processed yet, in order
+    pending: Mutex<VecDeque<(Signature, u64)>>,
+    progress: watch::Sender<ReplayProgress>,
+    /// Recorded time of the update the pipeline is processing
+    current: Mutex<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ReplayProgress {
+    pending: usize,
+    /// Whether the monitors are on the first pending update
+    in_flight: bool,
+}
+
+impl Default for ReplayClock {
+    fn default() -> Self {
+        Self {
+            pending: Mutex::new(VecDeque::new()),
+            progress: watch::channel(ReplayProgress::default()).0,
+            current: Mutex::new(0),
+        }
+    }
+}
+
+impl ReplayClock {
+    pub fn now(&self) -> u64 {
+        *self.current.lock().unwrap()
+    }
+
+    fn push(&self, signature: Signature, received_at: u64) {
+        let mut pending = self.pending.lock().unwrap();
+        pending.push_back((signature, received_at));
+        self.progress
+            .send_modify(|progress| progress.pending = pending.len());
+    }
+
+    /// Updates carbon couldn't process never reach `ReplayClockMonitor`, and
+    /// are skipped on the way to the next one
+    fn start(&self, signature: &Signature) {
+        let mut pending = self.pending.lock().unwrap();
+        while let Some((pending_signature, received_at)) = pending.front() {
+            if pending_signature == signature {
+                *self.current.lock().unwrap() = *received_at;
+                break;
+            }
+
+            pending.pop_front();
+        }
+
+        self.progress.send_modify(|progress| {
+            progress.pending = pending.len();
+            progress.in_flight = !pending.is_empty();
+        });
+    }
+
+    /// Called on every instruction, the same transaction may be recorded from
+    /// several sources
+    fn finish(&self, signature: &Signature) {
+        let mut pending = self.pending.lock().unwrap();
+        if !self.progress.borrow().in_flight
+            || pending
+                .front()
+                .is_none_or(|(pending_signature, _)| pending_signature != signature)
+        {
+            return;
+        }
+
+        pending.pop_front();
+        self.progress.send_modify(|progress| {
+            progress.pending = pending.len();
+            progress.in_flight = false;
+        });
+    }
+}
+
+/// Decodes every instruction with its accounts, for `ReplayClockMonitor` to
+/// tell which one starts a transaction
+pub struct ReplayClockDecoder;
+
+impl<'a> InstructionDecoder<'a> for ReplayClockDecoder {
+    type InstructionType = ();
+
+    fn decode_instruction(
+        &self,
+        instruction: &'a Instruction,
+    ) -> Option<DecodedInstruction<Self::InstructionType>> {
+        Some(DecodedInstruction {
+            program_id: instruction.program_id,
+            data: (),
+            accounts: instruction.accounts.clone(),
+        })
+    }
+}
+
+/// Registered before every monitor, moves the clock to the recorded time of
+/// each update as the pipeline starts on it
+pub struct ReplayClockMonitor {
+    pub clock: Arc<ReplayClock>,
+    pub instruction_positions: InstructionPositions,
+}
+
+#[tonic::async_trait]
+impl Processor for ReplayClockMonitor {
+    type InputType = InstructionProcessorInputType<()>;
+
+    async fn process(
+        &mut self,
+        (metadata, instruction, _nested_instructions): Self::InputType,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let position = self.instruction_positions.resolve(
+            &metadata,
+            &instruction.program_id,
+            &instruction.accounts,
+        );
+
+        if position == InstructionPosition::default() {
+            self.clock.start(&metadata.transaction_metadata.signature);
+        }
+
+        Ok(())
+    }
+}
+
+/// Registered after every monitor, tells the clock the pipeline is done with
+/// each update
+pub struct ReplayEndMonitor {
+    pub clock: Arc<ReplayClock>,
+}
+
+#[tonic::async_trait]
+impl Processor for ReplayEndMonitor {
+    type InputType = InstructionProcessorInputType<()>;
+
+    async fn process(
+        &mut self,
+        (metadata, _instruction, _nested_instructions): Self::InputType,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        self.clock.finish(&metadata.transaction_metadata.signature);
+
+        Ok(())
+    }
+}
+
+/// Replays recording files, then waits for the pipeline to be done with them
+pub struct ReplaySource {
+    pub paths: Vec<PathBuf>,
+    pub speed: ReplaySpeed,
+    pub clock: Arc<ReplayClock>,
+    pub transaction_indexes: TransactionIndexes,
+    /// How the replay ended, the pipeline only logs datasource errors
+    pub result_sender: Mutex<Option<oneshot::Sender<CarbonResult<()>>>>,
+}
+
+#[tonic::async_trait]
+impl Datasource for ReplaySource {
+    async fn consume(
+        &self,
+        sender: &UnboundedSender<Update>,
+        cancellation_token: CancellationToken,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let result = tokio::select! {
+            _ = cancellation_token.cancelled() => {
+                Err(Error::Custom("replay cancelled".to_string()))
+            }
+            result = self.send_updates(sender) => result,
+        };
+
+        if let Some(result_sender) = self.result_sender.lock().unwrap().take() {
+            let _ = result_sender.send(result);
+        }
+
+        Ok(())
+    }
+
+    fn update_types(&self) -> Vec<UpdateType> {
+        vec![UpdateType::Transaction]
+    }
+}
+
+impl ReplaySource {
+    async fn send_updates(&self, sender: &UnboundedSender<Update>) -> CarbonResult<()> {
+        let mut paths = Vec::new();
+        for path in self.paths.iter() {
+            if path.is_dir() {
+                paths.extend(get_recording_paths(path)?);
+            } else {
+                paths.push(path.clone());
+            }
+        }
+
+        // Files are read off the runtime
+        let (updates_sender, mut updates) = mpsc::channel(REPLAY_MAX_PENDING);
+        let reader = tokio::task::spawn_blocking(move || -> CarbonResult<()> {
+            for path in paths {
+                let mut reader = RecordingReader::open(&path)?;
+                while let Some(update) = reader.next_update()? {
+                    if updates_sender.blocking_send(update).is_err() {
+                        return Ok(());
+                    }
+                }
+            }
+
+            Ok(())
+        });
+
+        let mut progress = self.clock.progress.subscribe();
+        let mut start: Option<(Instant, u64)> = None;
+        let mut block_times = BlockTimes::default();
+        while let Some((received_at, update)) = updates.recv().await {
+            let transaction_update = match update.update_oneof {
+                Some(UpdateOneof::Transaction(transaction_update)) => transaction_update,
+                Some(UpdateOneof::BlockMeta(block_meta)) => {
+                    block_times.insert(&block_meta);
+                    continue;
+                }
+                _ => {
+                    continue;
+                }
+            };
+            let update = match get_transaction_update(
+                transaction_update,
+                &block_times,
+                &self.transaction_indexes,
+            ) {
+                Some(update) => update,
+                None => {
+                    continue;
+                }
+            };
+            let signature = match &update {
+                Update::Transaction(transaction_update) => transaction_update.signature,
+                _ => {
+                    continue;
+                }
+            };
+
+            if self.speed == ReplaySpeed::RealTime {
+                let (started_at, first_received_at) =
+                    *start.get_or_insert((Instant::now(), received_at));
+                tokio::time::sleep_until(
+                    started_at
+                        + Duration::from_millis(received_at.saturating_sub(first_received_at)),
+                )
+                .await;
+            }
+
+            // Keeps the clock from growing with the whole recording
+            let _ = progress
+                .wait_for(|progress| progress.pending < REPLAY_MAX_PENDING)
+                .await;
+
+            self.clock.push(signature, received_at);
+            sender
+                .send(update)
+                .map_err(|error| Error::Custom(format!("send update: {}", error)))?;
+        }
+
+        reader
+            .await
+            .map_err(|error| Error::Custom(format!("read recordings: {}", error)))??;
+
+        // Done once the monitors are through every update. The pipeline may
+        // wait on the receiver for long, but not start on updates it dropped
+        loop {
+            let ReplayProgress { pending, in_flight } = *progress.borrow_and_update();
+            if pending == 0 {
+                return Ok(());
+            }
+
+            let changed = match in_flight {
+                true => Ok(progress.changed().await),
+                false => tokio::time::timeout(REPLAY_DRAIN_TIMEOUT, progress.changed()).await,
+            };
+            match changed {
+                Ok(Ok(())) => {}
+                _ => {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use yellowstone_grpc_proto::{
+        geyser::{SubscribeUpdate, SubscribeUpdateBlockMeta},
+        solana::storage::confirmed_block::UnixTimestamp,
+    };
+
+    use super::*;
+    use crate::{
+        TransactionsListener,
+        mock_rpc::{get_subscribe_update_transaction, load_fixture},
+        recorder::Recorder,
+        types::{Event, EventType},
+    };
+
+    /// Oldest first, the pump.fun buy fails its monitor on the last transaction
+    const FIXTURES: [&str; 3] = [
+        "create_associated_account",
+        "create_associated_account_2",
+        "pumpfun_buy_without_trade_event",
+    ];
+
+    /// The block meta of the fixture's slot, then the fixture as streamed
+    fn get_subscribe_updates(name: &str, index: u64) -> [SubscribeUpdate; 2] {
+        let fixture = load_fixture(name);
+        let transaction_update = get_subscribe_update_transaction(&fixture, index);
+
+        [
+            SubscribeUpdate {
+                filters: Vec::new(),
+                created_at: None,
+                update_oneof: Some(UpdateOneof::BlockMeta(SubscribeUpdateBlockMeta {
+                    slot: transaction_update.slot,
+                    block_time: Some(UnixTimestamp {
+                        timestamp: fixture["blockTime"].as_i64().unwrap(),
+                    }),
+                    ..Default::default()
+                })),
+            },
+            SubscribeUpdate {
+                filters: Vec::new(),
+                created_at: None,
+                update_oneof: Some(UpdateOneof::Transaction(transaction_update)),
+            },
+        ]
+    }
+
+    async fn run_replay(transactions_listener: &TransactionsListener, path: PathBuf) -> Vec<Event> {
+        let (mut receiver, handle) = transactions_listener
+            .replay(
+                16,
+                Replay {
+                    paths: vec![path],
+                    speed: ReplaySpeed::AsFastAsPossible,
+                    cache_capacity: 255,
+                },
+            )
+            .unwrap();
+
+        tokio::time::timeout(Duration::from_secs(10), handle)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        let mut events = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            events.push(event);
+        }
+
+        events
+    }
+
+    #[tokio::test]
+    async fn test_replays_recording_deterministically() {
+        let directory = std::env::temp_dir().join(format!(
+            "transactions-listener-replay-{}",
+            Signature::new_unique()
+        ));
+        let updates = FIXTURES
+            .iter()
+            .enumerate()
+            .flat_map(|(index, name)| get_subscribe_updates(name, index as u64))
+            .collect::<Vec<_>>();
+
+        let recorder = Recorder::new(directory.clone(), u64::MAX).unwrap();
+        for update in updates.iter() {
+            recorder.record(update);
+        }
+        drop(recorder);
+
+        // The file is written off the test's thread, and unreadable until
+        // its header is flushed
+        let path = loop {
+            if let Some(path) = get_recording_paths(&directory).unwrap().pop()
+                && let Ok(mut reader) = RecordingReader::open(&path)
+            {
+                let mut count = 0;
+                while let Ok(Some(_)) = reader.next_update() {
+                    count += 1;
+                }
+                if count == updates.len() {
+                    break path;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+
+        let (mut transactions_listener, _receiver) =
+            TransactionsListener::new(16, 255, HashMap::new()).unwrap();
+        transactions_listener.config.associated_accounts_monitor = true;
+
+        let events = run_replay(&transactions_listener, path.clone()).await;
+
+        // Replayed events ignore the watchlist of the time of the replay
+        for event in events.iter() {
+            transactions_listener.add_watched_wallet(event.user, None);
+        }
+        let replayed_events = run_replay(&transactions_listener, path).await;
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(format!("{:?}", events), format!("{:?}", replayed_events));
+        // The failing pump.fun monitor doesn't keep the last transaction's
+        // event from coming out
+        assert_eq!(events.len(), FIXTURES.len());
+        for (index, event) in events.iter().enumerate() {
+            assert!(matches!(
+                event.event_type,
+                EventType::AssociatedAccountCreation { .. }
+            ));
+            assert_eq!(event.transaction_index, Some(index as u64));
+            assert!(event.block_time.is_some());
+            assert!(event.received_at > 0);
+            assert!(event.watchlist_entry.is_none());
+        }
+    }
+}
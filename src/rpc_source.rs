@@ -0,0 +1,551 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    str::FromStr,
+    sync::Arc,
+};
+
+use carbon_core::{
+    datasource::{Datasource, TransactionUpdate, Update, UpdateType},
+    transformers::transaction_metadata_from_original_meta,
+};
+use futures::StreamExt;
+use solana_client::{
+    nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient},
+    rpc_config::{RpcTransactionConfig, RpcTransactionLogsConfig, RpcTransactionLogsFilter},
+};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature};
+use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, UiTransactionEncoding};
+use tokio::{
+    sync::{mpsc::UnboundedSender, watch},
+    task::JoinSet,
+};
+use tokio_util::sync::CancellationToken;
+use yellowstone_grpc_proto::geyser::{CommitmentLevel, SubscribeRequestFilterTransactions};
+
+use crate::{
+    constants::{
+        RPC_GET_TRANSACTION_ATTEMPTS, RPC_GET_TRANSACTION_RETRY_DELAY, RPC_MAX_CONCURRENT_FETCHES,
+        RPC_RECENT_SIGNATURES,
+    },
+    prelude::*,
+    sources::{SourceHealth, SourceState, Subscription, consume_with_reconnects},
+};
+
+/// Standard JSON-RPC node. Subscribes to the logs mentioning each program, then
+/// fetches every transaction seen with `getTransaction`.
+///
+/// The node only narrows the logs to the programs, the watched wallets, tracked
+/// mints and excluded accounts are filtered on once the transactions are
+/// fetched. It doesn't report slots to the confirmations, nor stalls since
+/// quiet programs may log nothing for long.
+pub struct RpcSource {
+    pub rpc_url: String,
+    pub ws_url: String,
+    /// One logs subscription each, `mentions` takes a single account
+    pub programs: Vec<Pubkey>,
+    /// Those a gRPC source subscribes with, matched against the accounts of
+    /// every transaction fetched
+    pub transaction_filters: watch::Receiver<HashMap<String, SubscribeRequestFilterTransactions>>,
+    /// At least confirmed, nodes don't serve processed transactions
+    pub commitment: CommitmentConfig,
+    pub failed_transactions: bool,
+    pub health: Arc<SourceHealth>,
+}
+
+#[tonic::async_trait]
+impl Datasource for RpcSource {
+    async fn consume(
+        &self,
+        sender: &UnboundedSender<Update>,
+        cancellation_token: CancellationToken,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        consume_with_reconnects(self, sender, cancellation_token).await
+    }
+
+    fn update_types(&self) -> Vec<UpdateType> {
+        vec![UpdateType::Transaction]
+    }
+}
+
+#[tonic::async_trait]
+impl Subscription for RpcSource {
+    fn url(&self) -> &str {
+        &self.ws_url
+    }
+
+    fn health(&self) -> &SourceHealth {
+        &self.health
+    }
+
+    async fn subscribe(
+        &self,
+        sender: &UnboundedSender<Update>,
+        received: &mut bool,
+    ) -> CarbonResult<()> {
+        let pubsub_client = PubsubClient::new(&self.ws_url)
+            .await
+            .map_err(|error| Error::Custom(format!("connect RPC websocket: {}", error)))?;
+        let rpc_client = Arc::new(RpcClient::new_with_commitment(
+            self.rpc_url.clone(),
+            self.commitment,
+        ));
+
+        let mut streams = Vec::new();
+        for program in self.programs.iter() {
+            let (stream, _unsubscribe) = pubsub_client
+                .logs_subscribe(
+                    RpcTransactionLogsFilter::Mentions(vec![program.to_string()]),
+                    RpcTransactionLogsConfig {
+                        commitment: Some(self.commitment),
+                    },
+                )
+                .await
+                .map_err(|error| {
+                    Error::Custom(format!("subscribe to {} logs: {}", program, error))
+                })?;
+
+            streams.push(stream);
+        }
+        let mut logs = futures::stream::select_all(streams);
+
+        self.health.set_state(SourceState::Connected);
+
+        // Fetched concurrently, a slow transaction doesn't hold up the following
+        // ones. Aborted along with the subscription, on cancellation included
+        let mut fetches = JoinSet::new();
+        let mut recent_signatures = HashSet::new();
+        let mut recent_signatures_order = VecDeque::new();
+        while let Some(response) = logs.next().await {
+            while fetches.try_join_next().is_some() {}
+
+            *received = true;
+            self.health.record_message(response.context.slot);
+
+            if response.value.err.is_some() && !self.failed_transactions {
+                continue;
+            }
+
+            let signature = match Signature::from_str(&response.value.signature) {
+                Ok(signature) => signature,
+                Err(_) => {
+                    continue;
+                }
+            };
+
+            if !recent_signatures.insert(signature) {
+                continue;
+            }
+            recent_signatures_order.push_back(signature);
+            if recent_signatures_order.len() > RPC_RECENT_SIGNATURES
+                && let Some(signature) = recent_signatures_order.pop_front()
+            {
+                recent_signatures.remove(&signature);
+            }
+
+            if fetches.len() >= RPC_MAX_CONCURRENT_FETCHES {
+                fetches.join_next().await;
+            }
+
+            let rpc_client = rpc_client.clone();
+            let sender = sender.clone();
+            let commitment = self.commitment;
+            let transaction_filters = self.transaction_filters.clone();
+            fetches.spawn(async move {
+                let update = match get_transaction_update(&rpc_client, signature, commitment).await
+                {
+                    Ok(update) => update,
+                    Err(error) => {
+                        tracing::warn!("fetch transaction {}: {:?}", signature, error);
+                        return;
+                    }
+                };
+
+                if let Update::Transaction(transaction_update) = &update
+                    && !matches_transaction_filters(
+                        &transaction_filters.borrow(),
+                        transaction_update,
+                    )
+                {
+                    return;
+                }
+
+                let _ = sender.send(update);
+            });
+        }
+
+        while fetches.join_next().await.is_some() {}
+
+        Ok(())
+    }
+}
+
+/// Retries while the node doesn't serve the transaction yet
+pub(crate) async fn get_transaction_update(
+    rpc_client: &RpcClient,
+    signature: Signature,
+    commitment: CommitmentConfig,
+) -> CarbonResult<Update> {
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Base64),
+        commitment: Some(commitment),
+        max_supported_transaction_version: Some(0),
+    };
+
+    let mut attempt = 1;
+    let transaction = loop {
+        match rpc_client
+            .get_transaction_with_config(&signature, config)
+            .await
+        {
+            Ok(transaction) => break transaction,
+            Err(error) if attempt >= RPC_GET_TRANSACTION_ATTEMPTS => {
+                return Err(Error::Custom(format!("get transaction: {}", error)));
+            }
+            Err(_) => {
+                attempt += 1;
+                tokio::time::sleep(RPC_GET_TRANSACTION_RETRY_DELAY).await;
+            }
+        }
+    };
+
+    get_update(transaction)
+}
+
+/// Whether Geyser would deliver the transaction: it matches a filter when it
+/// involves any of `account_include`, if set, all of `account_required` and
+/// none of `account_exclude`
+fn matches_transaction_filters(
+    transaction_filters: &HashMap<String, SubscribeRequestFilterTransactions>,
+    transaction_update: &TransactionUpdate,
+) -> bool {
+    let loaded_addresses = &transaction_update.meta.loaded_addresses;
+    let accounts = transaction_update
+        .transaction
+        .message
+        .static_account_keys()
+        .iter()
+        .chain(&loaded_addresses.writable)
+        .chain(&loaded_addresses.readonly)
+        .map(|account| account.to_string())
+        .collect::<HashSet<_>>();
+
+    transaction_filters.values().any(|filter| {
+        (filter.account_include.is_empty()
+            || filter
+                .account_include
+                .iter()
+                .any(|account| accounts.contains(account)))
+            && filter
+                .account_required
+                .iter()
+                .all(|account| accounts.contains(account))
+            && !filter
+                .account_exclude
+                .iter()
+                .any(|account| accounts.contains(account))
+    })
+}
+
+/// Nodes serve transactions from confirmed commitment on
+pub(crate) fn get_commitment_config(commitment: CommitmentLevel) -> CommitmentConfig {
+    match commitment {
+        CommitmentLevel::Finalized => CommitmentConfig::finalized(),
+        _ => CommitmentConfig::confirmed(),
+    }
+}
+
+/// Transaction as served by `getTransaction`, which must be binary encoded
+pub(crate) fn get_update(
+    transaction: EncodedConfirmedTransactionWithStatusMeta,
+) -> CarbonResult<Update> {
+    let meta = match transaction.transaction.meta {
+        Some(meta) => transaction_metadata_from_original_meta(meta)?,
+        None => {
+            return Err(Error::Custom("transaction without meta".to_string()));
+        }
+    };
+    let versioned_transaction = match transaction.transaction.transaction.decode() {
+        Some(versioned_transaction) => versioned_transaction,
+        None => {
+            return Err(Error::Custom("decode transaction".to_string()));
+        }
+    };
+    let signature = match versioned_transaction.signatures.first() {
+        Some(signature) => *signature,
+        None => {
+            return Err(Error::Custom("transaction without signature".to_string()));
+        }
+    };
+
+    Ok(Update::Transaction(Box::new(TransactionUpdate {
+        signature,
+        transaction: versioned_transaction,
+        meta,
+        is_vote: false,
+        slot: transaction.slot,
+        block_time: transaction.block_time,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::{
+        constants::programs::ASSOCIATED_TOKEN_ACCOUNT,
+        mock_rpc::{MockRpc, get_fixture_signature, get_transaction_handler, load_fixture},
+    };
+
+    /// Wallet and mint of the `create_associated_account` fixture, the other
+    /// one being of the same wallet and another mint
+    const WALLET: Pubkey = solana_sdk::pubkey!("AKnL4NNf3DGWZJS6cPknBuEGnVsV4A4m5tgebLHaRSZ9");
+    const MINT: Pubkey = solana_sdk::pubkey!("8qbHbw2BbbTHBW1sbeqakYXVKRQM8Ne7pLK7m6CVfeR");
+
+    fn get_filter(
+        account_include: &[Pubkey],
+        account_exclude: &[Pubkey],
+        account_required: &[Pubkey],
+    ) -> SubscribeRequestFilterTransactions {
+        let get_strings =
+            |accounts: &[Pubkey]| accounts.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+        SubscribeRequestFilterTransactions {
+            account_include: get_strings(account_include),
+            account_exclude: get_strings(account_exclude),
+            account_required: get_strings(account_required),
+            ..Default::default()
+        }
+    }
+
+    fn get_transaction_filters(
+        filters: Vec<SubscribeRequestFilterTransactions>,
+    ) -> watch::Receiver<HashMap<String, SubscribeRequestFilterTransactions>> {
+        let filters = filters
+            .into_iter()
+            .enumerate()
+            .map(|(index, filter)| (index.to_string(), filter))
+            .collect();
+
+        watch::channel(filters).1
+    }
+
+    #[test]
+    fn test_matches_transaction_filters() {
+        let update =
+            get_update(serde_json::from_value(load_fixture("create_associated_account")).unwrap())
+                .unwrap();
+        let transaction_update = match update {
+            Update::Transaction(transaction_update) => transaction_update,
+            _ => panic!("expected a transaction update"),
+        };
+        let other_account = Pubkey::new_unique();
+
+        let cases = [
+            (
+                "program",
+                vec![get_filter(&[ASSOCIATED_TOKEN_ACCOUNT], &[], &[])],
+                true,
+            ),
+            ("every transaction", vec![get_filter(&[], &[], &[])], true),
+            ("no filter", vec![], false),
+            (
+                "excluded mint",
+                vec![get_filter(&[ASSOCIATED_TOKEN_ACCOUNT], &[MINT], &[])],
+                false,
+            ),
+            (
+                "watched wallet",
+                vec![get_filter(
+                    &[other_account, WALLET],
+                    &[],
+                    &[ASSOCIATED_TOKEN_ACCOUNT],
+                )],
+                true,
+            ),
+            (
+                "tracked mint",
+                vec![get_filter(&[MINT], &[], &[ASSOCIATED_TOKEN_ACCOUNT])],
+                true,
+            ),
+            (
+                "other wallet",
+                vec![get_filter(
+                    &[other_account],
+                    &[],
+                    &[ASSOCIATED_TOKEN_ACCOUNT],
+                )],
+                false,
+            ),
+            (
+                "other program",
+                vec![get_filter(&[WALLET], &[], &[other_account])],
+                false,
+            ),
+            (
+                "any filter",
+                vec![
+                    get_filter(&[WALLET], &[], &[other_account]),
+                    get_filter(&[WALLET], &[], &[ASSOCIATED_TOKEN_ACCOUNT]),
+                ],
+                true,
+            ),
+        ];
+        for (name, filters, expected) in cases {
+            assert_eq!(
+                matches_transaction_filters(
+                    &get_transaction_filters(filters).borrow(),
+                    &transaction_update
+                ),
+                expected,
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drops_transactions_not_matching_filters() {
+        let fixtures =
+            ["create_associated_account", "create_associated_account_2"].map(load_fixture);
+        let signatures = fixtures.each_ref().map(get_fixture_signature);
+        let mock_rpc = MockRpc::start(
+            get_transaction_handler(fixtures.to_vec()),
+            signatures
+                .iter()
+                .map(|signature| {
+                    json!({ "signature": signature.to_string(), "err": null, "logs": [] })
+                })
+                .collect(),
+        )
+        .await;
+
+        // Only the first one is of the tracked mint
+        let rpc_source = RpcSource {
+            rpc_url: mock_rpc.url.clone(),
+            ws_url: mock_rpc.ws_url.clone(),
+            programs: vec![ASSOCIATED_TOKEN_ACCOUNT],
+            transaction_filters: get_transaction_filters(vec![get_filter(
+                &[MINT],
+                &[],
+                &[ASSOCIATED_TOKEN_ACCOUNT],
+            )]),
+            commitment: CommitmentConfig::confirmed(),
+            failed_transactions: false,
+            health: Arc::default(),
+        };
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let cancellation_token = CancellationToken::new();
+        let consume = tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move {
+                rpc_source
+                    .consume(
+                        &sender,
+                        cancellation_token,
+                        Arc::new(MetricsCollection::new(vec![])),
+                    )
+                    .await
+            }
+        });
+
+        let update = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        match update {
+            Update::Transaction(transaction_update) => {
+                assert_eq!(transaction_update.signature, signatures[0]);
+            }
+            _ => panic!("expected a transaction update"),
+        }
+
+        // Fetched, then dropped
+        while mock_rpc.requests("getTransaction").len() < 2 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(receiver.try_recv().is_err());
+
+        cancellation_token.cancel();
+        consume.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_fetches_stop_with_the_source() {
+        let fixture = load_fixture("create_associated_account");
+        let signature = get_fixture_signature(&fixture);
+        // Never served, fetched until the attempts run out
+        let unknown_signature = Signature::new_unique();
+        let mock_rpc = MockRpc::start(
+            get_transaction_handler(vec![fixture]),
+            vec![
+                json!({ "signature": signature.to_string(), "err": null, "logs": [] }),
+                json!({ "signature": unknown_signature.to_string(), "err": null, "logs": [] }),
+            ],
+        )
+        .await;
+
+        let rpc_source = RpcSource {
+            rpc_url: mock_rpc.url.clone(),
+            ws_url: mock_rpc.ws_url.clone(),
+            programs: vec![ASSOCIATED_TOKEN_ACCOUNT],
+            transaction_filters: get_transaction_filters(vec![get_filter(
+                &[ASSOCIATED_TOKEN_ACCOUNT],
+                &[],
+                &[],
+            )]),
+            commitment: CommitmentConfig::confirmed(),
+            failed_transactions: false,
+            health: Arc::default(),
+        };
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let cancellation_token = CancellationToken::new();
+        let consume = tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move {
+                rpc_source
+                    .consume(
+                        &sender,
+                        cancellation_token,
+                        Arc::new(MetricsCollection::new(vec![])),
+                    )
+                    .await
+            }
+        });
+
+        let update = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        match update {
+            Update::Transaction(transaction_update) => {
+                assert_eq!(transaction_update.signature, signature);
+            }
+            _ => panic!("expected a transaction update"),
+        }
+
+        let get_unknown_fetches = || {
+            mock_rpc
+                .requests("getTransaction")
+                .iter()
+                .filter(|params| params[0] == unknown_signature.to_string())
+                .count()
+        };
+        while get_unknown_fetches() == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        cancellation_token.cancel();
+        consume.await.unwrap().unwrap();
+        let unknown_fetches = get_unknown_fetches();
+
+        tokio::time::sleep(RPC_GET_TRANSACTION_RETRY_DELAY * 3).await;
+        assert_eq!(get_unknown_fetches(), unknown_fetches);
+        assert!(unknown_fetches < RPC_GET_TRANSACTION_ATTEMPTS as usize);
+    }
+}
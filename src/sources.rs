@@ -0,0 +1,763 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use carbon_core::datasource::{Datasource, Update, UpdateType};
+use tokio::{runtime::Handle, sync::mpsc::UnboundedSender};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    constants::{
+        SOURCE_FAILED_RECONNECT_ATTEMPTS, SOURCE_RECONNECT_INITIAL_BACKOFF,
+        SOURCE_RECONNECT_MAX_BACKOFF,
+    },
+    prelude::*,
+    utils::get_now_timestamp_millis,
+};
+
+/// Period over which `messages_per_second` is averaged
+const RATE_WINDOW: Duration = Duration::from_secs(5);
+
+/// Where a source gets its transactions from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SourceConfig {
+    /// Yellowstone gRPC
+    Grpc {
+        url: String,
+        x_token: Option<String>,
+    },
+    /// Standard JSON-RPC node, see `RpcSource`
+    Rpc { rpc_url: String, ws_url: String },
+}
+
+impl SourceConfig {
+    /// Checks the scheme of the urls, the sources only fail to connect later
+    pub fn validate(&self) -> Result<(), SourceError> {
+        let urls = match self {
+            SourceConfig::Grpc { url, .. } => vec![(url, ["http://", "https://"])],
+            SourceConfig::Rpc { rpc_url, ws_url } => vec![
+                (rpc_url, ["http://", "https://"]),
+                (ws_url, ["ws://", "wss://"]),
+            ],
+        };
+
+        for (url, schemes) in urls {
+            if !schemes.iter().any(|scheme| url.starts_with(scheme)) {
+                return Err(SourceError::InvalidUrl(url.clone()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SourceError {
+    UnknownSource(String),
+    InvalidUrl(String),
+}
+
+impl Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceError::UnknownSource(id) => write!(f, "unknown source {}", id),
+            SourceError::InvalidUrl(url) => write!(f, "invalid source url {}", url),
+        }
+    }
+}
+
+impl std::error::Error for SourceError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SourceState {
+    /// Connecting for the first time
+    Connecting,
+    Connected,
+    /// Waiting to reconnect after `error`
+    Reconnecting {
+        attempt: u32,
+        error: String,
+    },
+    /// Too many failed reconnects in a row, or the source stopped on an
+    /// error. Reconnects go on at the maximum backoff in the first case
+    Failed {
+        error: String,
+    },
+    /// Not consumed, the listener isn't running
+    Stopped,
+}
+
+/// Snapshot of a source's health
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceStatus {
+    pub state: SourceState,
+    pub last_slot: Option<u64>,
+    /// Time of the last message, in milliseconds since the unix epoch
+    pub last_message_at: Option<u64>,
+    pub messages_per_second: f64,
+    /// Slots behind the source that has seen the highest slot
+    pub slot_lag: Option<u64>,
+    pub reconnects: u64,
+}
+
+/// Health a source reports while it's consumed
+#[derive(Debug)]
+pub struct SourceHealth {
+    state: Mutex<HealthState>,
+}
+
+#[derive(Debug)]
+struct HealthState {
+    state: SourceState,
+    last_slot: Option<u64>,
+    last_message_at: Option<u64>,
+    reconnects: u64,
+    window_started_at: Instant,
+    window_messages: u64,
+    messages_per_second: f64,
+}
+
+impl Default for SourceHealth {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(HealthState {
+                state: SourceState::Stopped,
+                last_slot: None,
+                last_message_at: None,
+                reconnects: 0,
+                window_started_at: Instant::now(),
+                window_messages: 0,
+                messages_per_second: 0.0,
+            }),
+        }
+    }
+}
+
+impl SourceHealth {
+    pub fn set_state(&self, state: SourceState) {
+        self.state.lock().unwrap().state = state;
+    }
+
+    /// Sets the state of a source about to reconnect
+    pub(crate) fn set_reconnect_state(&self, state: SourceState) {
+        let mut health = self.state.lock().unwrap();
+        health.reconnects += 1;
+        health.state = state;
+    }
+
+    pub fn record_message(&self, slot: u64) {
+        let now = Instant::now();
+        let mut health = self.state.lock().unwrap();
+
+        health.last_slot = Some(
+            health
+                .last_slot
+                .map_or(slot, |last_slot| last_slot.max(slot)),
+        );
+        health.last_message_at = Some(get_now_timestamp_millis());
+        health.window_messages += 1;
+
+        let elapsed = now.duration_since(health.window_started_at);
+        if elapsed >= RATE_WINDOW {
+            health.messages_per_second = health.window_messages as f64 / elapsed.as_secs_f64();
+            health.window_started_at = now;
+            health.window_messages = 0;
+        }
+    }
+
+    /// Without `slot_lag`, which depends on the other sources
+    pub fn status(&self) -> SourceStatus {
+        let health = self.state.lock().unwrap();
+
+        // Otherwise a source gone silent keeps its last rate
+        let elapsed = health.window_started_at.elapsed();
+        let messages_per_second = if elapsed >= RATE_WINDOW {
+            health.window_messages as f64 / elapsed.as_secs_f64()
+        } else {
+            health.messages_per_second
+        };
+
+        SourceStatus {
+            state: health.state.clone(),
+            last_slot: health.last_slot,
+            last_message_at: health.last_message_at,
+            messages_per_second,
+            slot_lag: None,
+            reconnects: health.reconnects,
+        }
+    }
+}
+
+/// A connection of a source, re-established by `consume_with_reconnects`
+#[tonic::async_trait]
+pub(crate) trait Subscription {
+    fn url(&self) -> &str;
+
+    fn health(&self) -> &SourceHealth;
+
+    /// Streams transactions until the connection fails, setting `received`
+    /// once anything came through
+    async fn subscribe(
+        &self,
+        sender: &UnboundedSender<Update>,
+        received: &mut bool,
+    ) -> CarbonResult<()>;
+}
+
+/// Reconnects with exponential backoff until cancelled. A subscription failing
+/// too many times in a row without delivering anything is reported `Failed`,
+/// and still retried at the maximum backoff
+pub(crate) async fn consume_with_reconnects(
+    subscription: &(impl Subscription + Sync),
+    sender: &UnboundedSender<Update>,
+    cancellation_token: CancellationToken,
+) -> CarbonResult<()> {
+    let health = subscription.health();
+    health.set_state(SourceState::Connecting);
+
+    let mut attempt = 0;
+    loop {
+        let mut received = false;
+        let error = tokio::select! {
+            _ = cancellation_token.cancelled() => {
+                break;
+            }
+            result = subscription.subscribe(sender, &mut received) => match result {
+                Ok(()) => "stream closed".to_string(),
+                Err(error) => error.to_string(),
+            },
+        };
+
+        // Only back off further while the source doesn't deliver anything
+        if received {
+            attempt = 0;
+        }
+        attempt += 1;
+
+        tracing::warn!(
+            "reconnect source {} (attempt {}): {}",
+            subscription.url(),
+            attempt,
+            error
+        );
+        health.set_reconnect_state(get_reconnect_state(attempt, error));
+
+        tokio::select! {
+            _ = cancellation_token.cancelled() => {
+                break;
+            }
+            _ = tokio::time::sleep(get_backoff(attempt)) => {}
+        }
+    }
+
+    health.set_state(SourceState::Stopped);
+    Ok(())
+}
+
+fn get_reconnect_state(attempt: u32, error: String) -> SourceState {
+    if attempt > SOURCE_FAILED_RECONNECT_ATTEMPTS {
+        SourceState::Failed { error }
+    } else {
+        SourceState::Reconnecting { attempt, error }
+    }
+}
+
+fn get_backoff(attempt: u32) -> Duration {
+    SOURCE_RECONNECT_INITIAL_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+        .min(SOURCE_RECONNECT_MAX_BACKOFF)
+}
+
+/// Datasource the pipeline is built with once. Sources are added to and
+/// removed from it while the pipeline runs, each one consuming under its own
+/// cancellation token so the others and the monitors keep going.
+#[derive(Clone, Default)]
+pub struct Sources {
+    state: Arc<Mutex<SourcesState>>,
+}
+
+#[derive(Default)]
+struct SourcesState {
+    sources: HashMap<String, Source>,
+    /// Set while the pipeline consumes the sources
+    running: Option<Running>,
+    /// Id of the next run of the pipeline
+    next_run: u64,
+}
+
+struct Source {
+    datasource: Arc<dyn Datasource>,
+    health: Arc<SourceHealth>,
+    /// `None` until the pipeline starts consuming
+    cancellation_token: Option<CancellationToken>,
+}
+
+#[derive(Clone)]
+struct Running {
+    /// Tells a run apart from the ones before, whose cleanup can land late
+    run: u64,
+    sender: UnboundedSender<Update>,
+    cancellation_token: CancellationToken,
+    metrics: Arc<MetricsCollection>,
+    runtime: Handle,
+}
+
+impl Sources {
+    /// Replaces the source with the same id, if any. Starts consuming right away
+    /// when the pipeline is running. `health` is the one the datasource reports to.
+    pub fn add(&self, id: String, datasource: Arc<dyn Datasource>, health: Arc<SourceHealth>) {
+        let (running, cancellation_token, previous_source) = {
+            let mut state = self.state.lock().unwrap();
+
+            // A cancelled run is as good as stopped, its cleanup is pending
+            let running = state
+                .running
+                .clone()
+                .filter(|running| !running.cancellation_token.is_cancelled());
+            let cancellation_token = running
+                .as_ref()
+                .map(|running| running.cancellation_token.child_token());
+            let previous_source = state.sources.insert(
+                id.clone(),
+                Source {
+                    datasource: datasource.clone(),
+                    health: health.clone(),
+                    cancellation_token: cancellation_token.clone(),
+                },
+            );
+
+            (running, cancellation_token, previous_source)
+        };
+
+        if let Some(Source {
+            cancellation_token: Some(cancellation_token),
+            ..
+        }) = previous_source
+        {
+            cancellation_token.cancel();
+        }
+
+        if let (Some(running), Some(cancellation_token)) = (running, cancellation_token) {
+            start(id, datasource, health, &running, cancellation_token);
+        }
+    }
+
+    /// Stops consuming the source
+    pub fn remove(&self, id: &str) -> Result<(), SourceError> {
+        let source = match self.state.lock().unwrap().sources.remove(id) {
+            Some(source) => source,
+            None => {
+                return Err(SourceError::UnknownSource(id.to_string()));
+            }
+        };
+
+        if let Some(cancellation_token) = source.cancellation_token {
+            cancellation_token.cancel();
+        }
+
+        Ok(())
+    }
+
+    /// Stops consuming every source, ahead of the pipeline winding down. Sources
+    /// added from then on wait for the next run.
+    pub fn stop(&self) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(running) = state.running.take() {
+            running.cancellation_token.cancel();
+        }
+        for source in state.sources.values_mut() {
+            if let Some(cancellation_token) = source.cancellation_token.take() {
+                cancellation_token.cancel();
+            }
+        }
+    }
+
+    pub fn ids(&self) -> Vec<String> {
+        self.state.lock().unwrap().sources.keys().cloned().collect()
+    }
+
+    pub fn status(&self) -> HashMap<String, SourceStatus> {
+        let mut status = self
+            .state
+            .lock()
+            .unwrap()
+            .sources
+            .iter()
+            .map(|(id, source)| (id.clone(), source.health.status()))
+            .collect::<HashMap<_, _>>();
+
+        let best_slot = status
+            .values()
+            .filter_map(|source_status| source_status.last_slot)
+            .max();
+        for source_status in status.values_mut() {
+            if let (Some(best_slot), Some(last_slot)) = (best_slot, source_status.last_slot) {
+                source_status.slot_lag = Some(best_slot - last_slot);
+            }
+        }
+
+        status
+    }
+}
+
+/// Consumes the source until its cancellation token, a child of the
+/// pipeline's, is cancelled. Spawned on the pipeline's runtime, so that sources
+/// can be added from outside of it.
+fn start(
+    id: String,
+    datasource: Arc<dyn Datasource>,
+    health: Arc<SourceHealth>,
+    running: &Running,
+    cancellation_token: CancellationToken,
+) {
+    let sender = running.sender.clone();
+    let metrics = running.metrics.clone();
+    running.runtime.spawn(async move {
+        if let Err(error) = datasource
+            .consume(&sender, cancellation_token, metrics)
+            .await
+        {
+            tracing::error!("consume source {}: {:?}", id, error);
+            health.set_state(SourceState::Failed {
+                error: error.to_string(),
+            });
+        }
+    });
+}
+
+#[tonic::async_trait]
+impl Datasource for Sources {
+    async fn consume(
+        &self,
+        sender: &UnboundedSender<Update>,
+        cancellation_token: CancellationToken,
+        metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let (running, sources) = {
+            let mut state = self.state.lock().unwrap();
+            let running = Running {
+                run: state.next_run,
+                sender: sender.clone(),
+                cancellation_token: cancellation_token.clone(),
+                metrics,
+                runtime: Handle::current(),
+            };
+            state.next_run += 1;
+
+            let sources = state
+                .sources
+                .iter_mut()
+                .map(|(id, source)| {
+                    let cancellation_token = cancellation_token.child_token();
+                    source.cancellation_token = Some(cancellation_token.clone());
+
+                    (
+                        id.clone(),
+                        source.datasource.clone(),
+                        source.health.clone(),
+                        cancellation_token,
+                    )
+                })
+                .collect::<Vec<_>>();
+            state.running = Some(running.clone());
+
+            (running, sources)
+        };
+
+        for (id, datasource, health, cancellation_token) in sources {
+            start(id, datasource, health, &running, cancellation_token);
+        }
+
+        cancellation_token.cancelled().await;
+
+        // The sources' tokens are cancelled along with the pipeline's. A next
+        // run may have started already, its state is left alone
+        let mut state = self.state.lock().unwrap();
+        if state
+            .running
+            .as_ref()
+            .is_none_or(|current| current.run != running.run)
+        {
+            return Ok(());
+        }
+        state.running = None;
+        for source in state.sources.values_mut() {
+            source.cancellation_token = None;
+        }
+
+        Ok(())
+    }
+
+    fn update_types(&self) -> Vec<UpdateType> {
+        vec![UpdateType::Transaction]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    /// Counts the consumes started and still running
+    #[derive(Default)]
+    struct CountingDatasource {
+        started: AtomicUsize,
+        running: AtomicUsize,
+    }
+
+    #[tonic::async_trait]
+    impl Datasource for CountingDatasource {
+        async fn consume(
+            &self,
+            _sender: &UnboundedSender<Update>,
+            cancellation_token: CancellationToken,
+            _metrics: Arc<MetricsCollection>,
+        ) -> CarbonResult<()> {
+            self.started.fetch_add(1, Ordering::SeqCst);
+            self.running.fetch_add(1, Ordering::SeqCst);
+            cancellation_token.cancelled().await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn update_types(&self) -> Vec<UpdateType> {
+            vec![UpdateType::Transaction]
+        }
+    }
+
+    /// Connects, delivers something the first time only, then fails
+    #[derive(Default)]
+    struct FailingSubscription {
+        health: SourceHealth,
+        subscribed: AtomicBool,
+    }
+
+    #[tonic::async_trait]
+    impl Subscription for FailingSubscription {
+        fn url(&self) -> &str {
+            "http://localhost"
+        }
+
+        fn health(&self) -> &SourceHealth {
+            &self.health
+        }
+
+        async fn subscribe(
+            &self,
+            _sender: &UnboundedSender<Update>,
+            received: &mut bool,
+        ) -> CarbonResult<()> {
+            self.health.set_state(SourceState::Connected);
+            if !self.subscribed.swap(true, Ordering::SeqCst) {
+                self.health.record_message(1);
+                *received = true;
+            }
+            Err(Error::Custom("connection reset".to_string()))
+        }
+    }
+
+    async fn wait_until(condition: impl Fn() -> bool) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !condition() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    fn consume(
+        sources: &Sources,
+        cancellation_token: &CancellationToken,
+    ) -> tokio::task::JoinHandle<CarbonResult<()>> {
+        let sources = sources.clone();
+        let cancellation_token = cancellation_token.clone();
+        tokio::spawn(async move {
+            let (sender, _receiver) = mpsc::unbounded_channel();
+            sources
+                .consume(
+                    &sender,
+                    cancellation_token,
+                    Arc::new(MetricsCollection::new(vec![])),
+                )
+                .await
+        })
+    }
+
+    fn add(sources: &Sources, id: &str) -> Arc<CountingDatasource> {
+        let datasource = Arc::new(CountingDatasource::default());
+        sources.add(id.to_string(), datasource.clone(), Arc::default());
+        datasource
+    }
+
+    #[tokio::test]
+    async fn test_adds_deletes_and_restarts_sources() {
+        let sources = Sources::default();
+        let first = add(&sources, "first");
+        assert_eq!(first.started.load(Ordering::SeqCst), 0);
+
+        let cancellation_token = CancellationToken::new();
+        let run = consume(&sources, &cancellation_token);
+        wait_until(|| first.running.load(Ordering::SeqCst) == 1).await;
+
+        // Added and deleted while running
+        let second = add(&sources, "second");
+        wait_until(|| second.running.load(Ordering::SeqCst) == 1).await;
+        sources.remove("first").unwrap();
+        wait_until(|| first.running.load(Ordering::SeqCst) == 0).await;
+        assert_eq!(
+            sources.remove("first"),
+            Err(SourceError::UnknownSource("first".to_string()))
+        );
+
+        // Replaced
+        let replacement = add(&sources, "second");
+        wait_until(|| replacement.running.load(Ordering::SeqCst) == 1).await;
+        wait_until(|| second.running.load(Ordering::SeqCst) == 0).await;
+        assert_eq!(sources.ids(), vec!["second".to_string()]);
+
+        // The next run starts before the cleanup of this one lands
+        let next_cancellation_token = CancellationToken::new();
+        let next_run = consume(&sources, &next_cancellation_token);
+        cancellation_token.cancel();
+        run.await.unwrap().unwrap();
+        wait_until(|| {
+            replacement.started.load(Ordering::SeqCst) == 2
+                && replacement.running.load(Ordering::SeqCst) == 1
+        })
+        .await;
+
+        let third = add(&sources, "third");
+        wait_until(|| third.running.load(Ordering::SeqCst) == 1).await;
+
+        next_cancellation_token.cancel();
+        next_run.await.unwrap().unwrap();
+        wait_until(|| {
+            replacement.running.load(Ordering::SeqCst) == 0
+                && third.running.load(Ordering::SeqCst) == 0
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_adds_and_removes_sources_across_stop_and_run() {
+        let sources = Sources::default();
+        let kept = add(&sources, "kept");
+        let removed = add(&sources, "removed");
+
+        let cancellation_token = CancellationToken::new();
+        let run = consume(&sources, &cancellation_token);
+        wait_until(|| {
+            kept.running.load(Ordering::SeqCst) == 1 && removed.running.load(Ordering::SeqCst) == 1
+        })
+        .await;
+
+        // The run's own cleanup hasn't happened yet
+        sources.stop();
+        cancellation_token.cancel();
+        let added = add(&sources, "added");
+        sources.remove("removed").unwrap();
+        run.await.unwrap().unwrap();
+        wait_until(|| {
+            kept.running.load(Ordering::SeqCst) == 0 && removed.running.load(Ordering::SeqCst) == 0
+        })
+        .await;
+        assert_eq!(added.started.load(Ordering::SeqCst), 0);
+
+        let cancellation_token = CancellationToken::new();
+        let run = consume(&sources, &cancellation_token);
+        wait_until(|| {
+            kept.running.load(Ordering::SeqCst) == 1 && added.running.load(Ordering::SeqCst) == 1
+        })
+        .await;
+        assert_eq!(removed.started.load(Ordering::SeqCst), 1);
+
+        sources.stop();
+        wait_until(|| {
+            kept.running.load(Ordering::SeqCst) == 0 && added.running.load(Ordering::SeqCst) == 0
+        })
+        .await;
+        cancellation_token.cancel();
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reports_reconnects() {
+        let subscription = FailingSubscription::default();
+        assert_eq!(subscription.health.status().state, SourceState::Stopped);
+
+        let (sender, _receiver) = mpsc::unbounded_channel();
+        let cancellation_token = CancellationToken::new();
+        let consume = consume_with_reconnects(&subscription, &sender, cancellation_token.clone());
+        let check = async {
+            // Reset by the message of the first connection
+            wait_until(|| {
+                matches!(
+                    subscription.health.status().state,
+                    SourceState::Reconnecting { attempt: 1, .. }
+                )
+            })
+            .await;
+            wait_until(|| {
+                matches!(
+                    subscription.health.status().state,
+                    SourceState::Reconnecting { attempt: 2, .. }
+                )
+            })
+            .await;
+            cancellation_token.cancel();
+        };
+        let (result, _) = tokio::join!(consume, check);
+        result.unwrap();
+
+        let status = subscription.health.status();
+        assert_eq!(status.state, SourceState::Stopped);
+        assert_eq!(status.reconnects, 2);
+        assert_eq!(status.last_slot, Some(1));
+    }
+
+    #[test]
+    fn test_get_reconnect_state() {
+        assert_eq!(
+            get_reconnect_state(1, "stream closed".to_string()),
+            SourceState::Reconnecting {
+                attempt: 1,
+                error: "stream closed".to_string()
+            }
+        );
+        assert_eq!(
+            get_reconnect_state(
+                SOURCE_FAILED_RECONNECT_ATTEMPTS,
+                "stream closed".to_string()
+            ),
+            SourceState::Reconnecting {
+                attempt: SOURCE_FAILED_RECONNECT_ATTEMPTS,
+                error: "stream closed".to_string()
+            }
+        );
+        assert_eq!(
+            get_reconnect_state(
+                SOURCE_FAILED_RECONNECT_ATTEMPTS + 1,
+                "stream closed".to_string()
+            ),
+            SourceState::Failed {
+                error: "stream closed".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_get_backoff() {
+        assert_eq!(get_backoff(1), SOURCE_RECONNECT_INITIAL_BACKOFF);
+        assert_eq!(get_backoff(2), SOURCE_RECONNECT_INITIAL_BACKOFF * 2);
+        assert_eq!(get_backoff(4), SOURCE_RECONNECT_INITIAL_BACKOFF * 8);
+        assert_eq!(get_backoff(10), SOURCE_RECONNECT_MAX_BACKOFF);
+        assert_eq!(get_backoff(u32::MAX), SOURCE_RECONNECT_MAX_BACKOFF);
+    }
+}
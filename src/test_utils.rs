@@ -0,0 +1,97 @@
+//! Events for the tests, every field set so that none goes unchecked
+
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+
+use crate::types::{
+    Event, EventType, SchemaVersion, SwapPlatform, TransactionFees, WatchlistEntry,
+};
+#[cfg(any(feature = "serde", feature = "proto"))]
+use crate::types::{FailedSwapAttempt, Fee, PoolState, Swap, SwapAmount, SwapFees};
+
+pub(crate) fn get_event(event_type: EventType) -> Event {
+    Event {
+        schema_version: SchemaVersion,
+        signature: Signature::new_unique(),
+        event_type,
+        user: Pubkey::new_unique(),
+        slot: 321_000_000,
+        block_time: Some(1_750_000_000),
+        transaction_index: Some(42),
+        instruction_index: 2,
+        inner_instruction_index: Some(1),
+        received_at: 1_750_000_000_123,
+        transaction_fees: TransactionFees {
+            total_fee: 105_000,
+            priority_fee: Some(1_000_000),
+            compute_units_consumed: Some(100_000),
+            jito_tip: None,
+        },
+        watchlist_entry: Some(WatchlistEntry {
+            wallet: Pubkey::new_unique(),
+            label: Some("whale".to_string()),
+        }),
+    }
+}
+
+pub(crate) fn get_pool_creation(platform: SwapPlatform) -> EventType {
+    EventType::PoolCreation {
+        mint: Pubkey::new_unique(),
+        platform,
+    }
+}
+
+/// The amount out doesn't fit in a JSON number
+#[cfg(any(feature = "serde", feature = "proto"))]
+pub(crate) fn get_swap(
+    platform: SwapPlatform,
+    pool_state: PoolState,
+    fees: Option<SwapFees>,
+) -> EventType {
+    EventType::Swap(Box::new(Swap {
+        token_in_amount: 1_000_000_000,
+        token_in_decimals: 9,
+        token_in_mint: Pubkey::new_unique(),
+        token_out_amount: u64::MAX,
+        token_out_decimals: 6,
+        token_out_mint: Pubkey::new_unique(),
+        platform,
+        pool: Pubkey::new_unique(),
+        base_vault: Pubkey::new_unique(),
+        quote_vault: Pubkey::new_unique(),
+        pool_state,
+        fees,
+    }))
+}
+
+/// No creator fee, as on every platform but Pump.fun
+#[cfg(any(feature = "serde", feature = "proto"))]
+pub(crate) fn get_swap_fees() -> SwapFees {
+    SwapFees {
+        lp_fee: Some(Fee {
+            amount: 2_500_000,
+            mint: Pubkey::new_unique(),
+        }),
+        protocol_fee: Some(Fee {
+            amount: 300_000,
+            mint: Pubkey::new_unique(),
+        }),
+        creator_fee: None,
+        fund_fee: Some(Fee {
+            amount: 100_000,
+            mint: Pubkey::new_unique(),
+        }),
+    }
+}
+
+#[cfg(any(feature = "serde", feature = "proto"))]
+pub(crate) fn get_failed_swap_attempt(amount: SwapAmount) -> EventType {
+    EventType::FailedSwapAttempt(Box::new(FailedSwapAttempt {
+        platform: SwapPlatform::RaydiumCpmm,
+        pool: Pubkey::new_unique(),
+        token_in_mint: Some(Pubkey::new_unique()),
+        token_out_mint: None,
+        amount,
+        failed_instruction_index: Some(3),
+        error_code: Some(6_005),
+    }))
+}
@@ -0,0 +1,180 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use solana_sdk::pubkey::Pubkey;
+use tokio::sync::mpsc::{Sender, error::TrySendError};
+
+use crate::types::Event;
+
+/// Mint-specific receivers, shared by the listener and the monitors so that
+/// mints are tracked and untracked while the pipeline runs
+#[derive(Debug, Clone, Default)]
+pub struct TrackedMints {
+    senders: Arc<Mutex<HashMap<Pubkey, Sender<Event>>>>,
+}
+
+impl TrackedMints {
+    /// Replaces the sender of the mint, if any, closing its receiver
+    pub fn add(&self, mint: Pubkey, sender: Sender<Event>) {
+        self.senders.lock().unwrap().insert(mint, sender);
+    }
+
+    pub fn remove(&self, mint: &Pubkey) -> bool {
+        self.senders.lock().unwrap().remove(mint).is_some()
+    }
+
+    pub fn mints(&self) -> Vec<Pubkey> {
+        self.senders.lock().unwrap().keys().copied().collect()
+    }
+
+    /// Sends the event to the receiver of each tracked mint it's about. Never
+    /// waits, events are dropped when a receiver is full and lost once it's
+    /// dropped, without stopping the listener
+    pub fn send(&self, event: &Event) {
+        let senders = self.senders.lock().unwrap();
+        for mint in event.event_type.mints() {
+            let sender = match senders.get(&mint) {
+                Some(sender) => sender,
+                None => {
+                    continue;
+                }
+            };
+
+            if let Err(TrySendError::Full(_)) = sender.try_send(event.clone()) {
+                tracing::warn!(
+                    "tracked mint {} receiver full, dropping event {}",
+                    mint,
+                    event.signature
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc::{self, error::TryRecvError};
+
+    use super::*;
+    use crate::{
+        TransactionsListener, decode::parse_transaction, mock_rpc::load_fixture,
+        transaction_events::EventSender, transaction_indexes::TransactionIndexes,
+        watchlist::Watchlist,
+    };
+
+    const MINT: Pubkey = solana_sdk::pubkey!("CpjxMcNsK3DDuw44TbRzbXbi4Bn6vUrgjoq6aGnqwtsd");
+
+    async fn get_buy_event(transactions_listener: &TransactionsListener) -> Event {
+        let update = parse_transaction(&load_fixture("pumpfun_buy").to_string()).unwrap();
+        let decoded = transactions_listener.decode(update).await.unwrap();
+        assert_eq!(decoded.events.len(), 1);
+
+        decoded.events.into_iter().next().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_routes_events_to_receivers_of_their_mints() {
+        let (mut transactions_listener, _receiver) =
+            TransactionsListener::new(16, 255, HashMap::new()).unwrap();
+        let event = get_buy_event(&transactions_listener).await;
+        let mut mint_receiver = transactions_listener.track_mint(MINT);
+        let mut other_receiver = transactions_listener.track_mint(Pubkey::new_unique());
+
+        let (sender, mut events_receiver) = mpsc::channel(16);
+        let event_sender = EventSender::Event {
+            sender,
+            confirmations: None,
+            watchlist: Watchlist::default(),
+            tracked_mints: transactions_listener.tracked_mints.clone(),
+            transaction_indexes: TransactionIndexes::default(),
+            replay_clock: None,
+        };
+        event_sender.send(event.clone()).await.unwrap();
+
+        // Copied to the mint's receiver, on top of the listener's
+        assert_eq!(
+            events_receiver.try_recv().unwrap().signature,
+            event.signature
+        );
+        assert_eq!(mint_receiver.try_recv().unwrap().signature, event.signature);
+        assert_eq!(other_receiver.try_recv().unwrap_err(), TryRecvError::Empty);
+
+        // Untracked, the receiver gets nothing more
+        assert!(transactions_listener.untrack_mint(&MINT));
+        event_sender.send(event).await.unwrap();
+        assert_eq!(
+            mint_receiver.try_recv().unwrap_err(),
+            TryRecvError::Disconnected
+        );
+        assert!(events_receiver.try_recv().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_drops_events_while_receiver_full() {
+        let (transactions_listener, _receiver) =
+            TransactionsListener::new(16, 255, HashMap::new()).unwrap();
+        let event = get_buy_event(&transactions_listener).await;
+
+        let tracked_mints = TrackedMints::default();
+        let (sender, mut receiver) = mpsc::channel(1);
+        tracked_mints.add(MINT, sender);
+
+        tracked_mints.send(&event);
+        tracked_mints.send(&event);
+        assert!(receiver.try_recv().is_ok());
+        assert_eq!(receiver.try_recv().unwrap_err(), TryRecvError::Empty);
+
+        // Room again
+        tracked_mints.send(&event);
+        assert!(receiver.try_recv().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_track_and_untrack_mint() {
+        let (mut transactions_listener, _receiver) =
+            TransactionsListener::new(16, 255, HashMap::new()).unwrap();
+        let mut transaction_filters = transactions_listener.transaction_filters.subscribe();
+
+        let mut first_receiver = transactions_listener.track_mint(MINT);
+        assert!(transaction_filters.has_changed().unwrap());
+        let filters = transaction_filters.borrow_and_update().clone();
+        assert_eq!(
+            filters.len(),
+            transactions_listener.config.get_programs().len()
+        );
+        assert!(
+            filters
+                .values()
+                .all(|filter| filter.account_include.contains(&MINT.to_string()))
+        );
+        assert_eq!(transactions_listener.tracked_mints.mints(), vec![MINT]);
+
+        // Tracking it again replaces the receiver, the filters staying as they
+        // are
+        let mut second_receiver = transactions_listener.track_mint(MINT);
+        assert_eq!(
+            first_receiver.try_recv().unwrap_err(),
+            TryRecvError::Disconnected
+        );
+        assert_eq!(second_receiver.try_recv().unwrap_err(), TryRecvError::Empty);
+        assert!(!transaction_filters.has_changed().unwrap());
+
+        assert!(transactions_listener.untrack_mint(&MINT));
+        assert_eq!(
+            second_receiver.try_recv().unwrap_err(),
+            TryRecvError::Disconnected
+        );
+        assert!(transaction_filters.has_changed().unwrap());
+        let filters = transaction_filters.borrow_and_update().clone();
+        assert!(
+            filters
+                .values()
+                .all(|filter| !filter.account_include.contains(&MINT.to_string()))
+        );
+        assert!(transactions_listener.tracked_mints.mints().is_empty());
+
+        assert!(!transactions_listener.untrack_mint(&MINT));
+    }
+}
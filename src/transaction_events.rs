@@ -0,0 +1,298 @@
+use carbon_core::instruction::{DecodedInstruction, InstructionDecoder};
+use solana_sdk::instruction::Instruction;
+use tokio::sync::mpsc::error::SendError;
+
+use crate::{
+    confirmations::Confirmations, events_cache::EventKey, replay::ReplayClock,
+    tracked_mints::TrackedMints, transaction_indexes::TransactionIndexes, types::TransactionEvents,
+    watchlist::Watchlist,
+};
+
+use super::*;
+
+/// Where the listener emits what it parses
+#[derive(Clone)]
+pub enum ListenerSender {
+    Event(Sender<Event>),
+    /// One `TransactionEvents` per transaction with at least one event
+    TransactionEvents(Sender<TransactionEvents>),
+}
+
+/// Where monitors hand over the events they parse
+#[derive(Clone)]
+pub enum EventSender {
+    Event {
+        sender: Sender<Event>,
+        /// Follows the events once sent, when confirmations are enabled
+        confirmations: Option<Arc<Confirmations>>,
+        watchlist: Watchlist,
+        tracked_mints: TrackedMints,
+        transaction_indexes: TransactionIndexes,
+        /// Dates the events of a replay as they were recorded, when set
+        replay_clock: Option<Arc<ReplayClock>>,
+    },
+    /// Held until `TransactionEventsMonitor` sends the whole transaction
+    Transaction {
+        events: Arc<Mutex<Vec<Event>>>,
+        watchlist: Watchlist,
+        tracked_mints: TrackedMints,
+        transaction_indexes: TransactionIndexes,
+        replay_clock: Option<Arc<ReplayClock>>,
+    },
+}
+
+impl EventSender {
+    /// Tags the event with the watchlist entry of its user and the index of its
+    /// transaction first, and copies it to the receivers of its tracked mints.
+    /// Replayed events are dated from the recording instead and left untagged,
+    /// the watchlist having changed since
+    pub async fn send(&self, mut event: Event) -> Result<(), SendError<Event>> {
+        match self {
+            Self::Event {
+                sender,
+                confirmations,
+                watchlist,
+                tracked_mints,
+                transaction_indexes,
+                replay_clock,
+            } => {
+                match replay_clock {
+                    Some(replay_clock) => event.received_at = replay_clock.now(),
+                    None => event.watchlist_entry = watchlist.get(&event.user),
+                }
+                event.transaction_index = transaction_indexes.get(&event.signature);
+                tracked_mints.send(&event);
+                let (key, slot) = (EventKey::from(&event), event.slot);
+                sender.send(event).await?;
+
+                if let Some(confirmations) = confirmations {
+                    confirmations.track(key, slot);
+                }
+
+                Ok(())
+            }
+            Self::Transaction {
+                events,
+                watchlist,
+                tracked_mints,
+                transaction_indexes,
+                replay_clock,
+            } => {
+                match replay_clock {
+                    Some(replay_clock) => event.received_at = replay_clock.now(),
+                    None => event.watchlist_entry = watchlist.get(&event.user),
+                }
+                event.transaction_index = transaction_indexes.get(&event.signature);
+                tracked_mints.send(&event);
+                events.lock().unwrap().push(event);
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Decodes every instruction so that `TransactionEventsMonitor` runs on every
+/// transaction
+pub struct TransactionEndDecoder;
+
+impl<'a> InstructionDecoder<'a> for TransactionEndDecoder {
+    type InstructionType = ();
+
+    fn decode_instruction(
+        &self,
+        instruction: &'a Instruction,
+    ) -> Option<DecodedInstruction<Self::InstructionType>> {
+        Some(DecodedInstruction {
+            program_id: instruction.program_id,
+            data: (),
+            accounts: Vec::new(),
+        })
+    }
+}
+
+/// Carbon runs every instruction of a transaction through a monitor before
+/// moving to the next monitor, so once registered last this one only runs
+/// after all the others are done with the transaction.
+pub struct TransactionEventsMonitor {
+    pub sender: Sender<TransactionEvents>,
+    pub events: Arc<Mutex<Vec<Event>>>,
+    pub confirmations: Option<Arc<Confirmations>>,
+}
+
+#[tonic::async_trait]
+impl Processor for TransactionEventsMonitor {
+    type InputType = InstructionProcessorInputType<()>;
+
+    async fn process(
+        &mut self,
+        (metadata, _instruction, _nested_instructions): Self::InputType,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let transaction_metadata = &metadata.transaction_metadata;
+
+        let mut events = std::mem::take(&mut *self.events.lock().unwrap());
+
+        // A failing monitor stops carbon from running the next ones on the
+        // transaction, what it left behind isn't the whole transaction
+        events.retain(|event| event.signature == transaction_metadata.signature);
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        events.sort_by_key(|event| (event.instruction_index, event.inner_instruction_index));
+        let keys = events.iter().map(EventKey::from).collect::<Vec<_>>();
+
+        self.sender
+            .send(TransactionEvents::new(transaction_metadata, events))
+            .await
+            .map_err(|error| {
+                Error::Custom(format!("send transaction events to receiver: {}", error))
+            })?;
+
+        if let Some(confirmations) = &self.confirmations {
+            for key in keys {
+                confirmations.track(key, transaction_metadata.slot);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, time::Duration};
+
+    use carbon_core::datasource::{Datasource, UpdateType};
+    use tokio::sync::mpsc::UnboundedSender;
+
+    use super::*;
+    use crate::{
+        TransactionsListener,
+        decode::parse_transaction,
+        mock_rpc::{get_fixture_signature, load_fixture},
+        types::EventType,
+    };
+
+    /// Sends the updates once, in order
+    struct UpdatesSource {
+        updates: Mutex<Vec<Update>>,
+    }
+
+    #[tonic::async_trait]
+    impl Datasource for UpdatesSource {
+        async fn consume(
+            &self,
+            sender: &UnboundedSender<Update>,
+            cancellation_token: CancellationToken,
+            _metrics: Arc<MetricsCollection>,
+        ) -> CarbonResult<()> {
+            let updates = std::mem::take(&mut *self.updates.lock().unwrap());
+            for update in updates {
+                sender
+                    .send(update)
+                    .map_err(|error| Error::Custom(format!("send update: {}", error)))?;
+            }
+
+            cancellation_token.cancelled().await;
+
+            Ok(())
+        }
+
+        fn update_types(&self) -> Vec<UpdateType> {
+            vec![UpdateType::Transaction]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sends_one_envelope_per_transaction_in_order() {
+        // The associated account monitor is off, the second one has no event
+        // and gets no envelope
+        const FIXTURES: [&str; 3] = [
+            "pumpfun_create_and_buy",
+            "create_associated_account",
+            "raydium_amm_v4_split_route",
+        ];
+        let fixtures = FIXTURES.map(load_fixture);
+
+        let (transactions_listener, _receiver) =
+            TransactionsListener::new(16, 255, HashMap::new()).unwrap();
+        let (sender, mut receiver) = mpsc::channel(16);
+        let (cancellation_token, pipeline, _monitor_errors) = transactions_listener
+            .get_pipeline_builder(
+                ListenerSender::TransactionEvents(sender),
+                Arc::new(EventsCache::new(255, None)),
+                None,
+                TrackedMints::default(),
+                TransactionIndexes::default(),
+                None,
+            );
+        let mut pipeline = pipeline
+            .datasource(UpdatesSource {
+                updates: Mutex::new(
+                    fixtures
+                        .iter()
+                        .map(|fixture| parse_transaction(&fixture.to_string()).unwrap())
+                        .collect(),
+                ),
+            })
+            .build()
+            .unwrap();
+        let handle = tokio::spawn(async move { pipeline.run().await });
+
+        let mut transactions_events = Vec::new();
+        for _ in 0..2 {
+            let transaction_events = tokio::time::timeout(Duration::from_secs(10), receiver.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            transactions_events.push(transaction_events);
+        }
+        cancellation_token.cancel();
+        handle.abort();
+        assert!(receiver.try_recv().is_err());
+
+        // The launch, then the dev's buy of it
+        let launch = &transactions_events[0];
+        assert_eq!(launch.signature, get_fixture_signature(&fixtures[0]));
+        assert_eq!(launch.events.len(), 2);
+        assert!(matches!(
+            launch.events[0].event_type,
+            EventType::PoolCreation {
+                platform: SwapPlatform::PumpFun,
+                ..
+            }
+        ));
+        match &launch.events[1].event_type {
+            EventType::Swap(swap) => {
+                assert_eq!(swap.platform, SwapPlatform::PumpFun);
+                assert_eq!(swap.token_out_amount, 35_000_000_000);
+            }
+            event_type => panic!("expected a swap, got {:?}", event_type),
+        }
+        assert_eq!(launch.events[0].user, launch.events[1].user);
+        assert!(launch.events[0].instruction_index < launch.events[1].instruction_index);
+
+        // Both hops of the route, in execution order
+        let route = &transactions_events[1];
+        assert_eq!(route.signature, get_fixture_signature(&fixtures[2]));
+        assert_eq!(
+            route
+                .events
+                .iter()
+                .map(|event| match &event.event_type {
+                    EventType::Swap(swap) => swap.token_in_amount,
+                    event_type => panic!("expected a swap, got {:?}", event_type),
+                })
+                .collect::<Vec<_>>(),
+            [600_000_000, 400_000_000]
+        );
+        assert!(
+            route
+                .events
+                .iter()
+                .all(|event| event.signature == route.signature)
+        );
+    }
+}
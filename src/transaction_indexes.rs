@@ -0,0 +1,43 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use solana_sdk::signature::Signature;
+
+use crate::constants::TRANSACTION_INDEXES_CAPACITY;
+
+/// Index of the latest transactions within their block, by signature. Carbon's
+/// transaction updates have no room for it, so sources report it here and the
+/// monitors' events are tagged from it
+#[derive(Debug, Clone, Default)]
+pub struct TransactionIndexes {
+    indexes: Arc<Mutex<Indexes>>,
+}
+
+#[derive(Debug, Default)]
+struct Indexes {
+    indexes: HashMap<Signature, u64>,
+    /// Insertion order of `indexes`, oldest evicted first
+    signatures: VecDeque<Signature>,
+}
+
+impl TransactionIndexes {
+    pub fn insert(&self, signature: Signature, index: u64) {
+        let mut indexes = self.indexes.lock().unwrap();
+        if indexes.indexes.insert(signature, index).is_some() {
+            return;
+        }
+
+        indexes.signatures.push_back(signature);
+        if indexes.signatures.len() > TRANSACTION_INDEXES_CAPACITY
+            && let Some(signature) = indexes.signatures.pop_front()
+        {
+            indexes.indexes.remove(&signature);
+        }
+    }
+
+    pub fn get(&self, signature: &Signature) -> Option<u64> {
+        self.indexes.lock().unwrap().indexes.get(signature).copied()
+    }
+}
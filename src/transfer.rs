@@ -1,7 +1,12 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
 
+use carbon_core::{instruction::NestedInstruction, transaction::TransactionMetadata};
 use solana_account_decoder::parse_token::UiTokenAmount;
-use solana_sdk::{instruction::CompiledInstruction, pubkey::Pubkey};
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey, signature::Signature};
 use solana_transaction_status::TransactionTokenBalance;
 
 use crate::{
@@ -24,58 +29,61 @@ pub struct TransferData {
     pub decimals: u8,
 }
 
+impl TransferData {
+    /// Fails when the mint couldn't be found in the token balances
+    pub fn get_mint(&self) -> CarbonResult<Pubkey> {
+        Pubkey::from_str(&self.mint)
+            .map_err(|error| Error::Custom(format!("parse transfer mint {}: {}", self.mint, error)))
+    }
+}
+
 pub struct TokenInfo {
     pub mint: String,
     pub amount: UiTokenAmount,
 }
 
-pub fn is_transfer_instruction(instruction: &CompiledInstruction, accounts: &[Pubkey]) -> bool {
-    if accounts[instruction.program_id_index as usize] != programs::TOKEN {
-        return false;
-    }
-
-    if instruction.accounts.len() < 3 || instruction.data.len() < 9 {
-        return false;
-    }
-
-    if instruction.data[0] != 3 {
-        return false;
-    }
-
-    for i in 0..3 {
-        if instruction.accounts[i] >= accounts.len() as u8 {
-            return false;
-        }
-    }
-
-    true
+#[derive(Debug, Clone)]
+pub struct SwapTransfers {
+    pub pay_transfer: TransferData,
+    pub receive_transfer: TransferData,
+    /// Including decimals
+    pub token_in_reserve: u64,
+    /// Including decimals
+    pub token_out_reserve: u64,
 }
 
-pub fn is_transfer_check_instruction(
-    instruction: &CompiledInstruction,
-    accounts: &[Pubkey],
-) -> bool {
-    if accounts[instruction.program_id_index as usize] != programs::TOKEN
-        && accounts[instruction.program_id_index as usize] != programs::TOKEN_2022
-    {
-        return false;
-    }
-
-    if instruction.accounts.len() < 4 || instruction.data.len() < 9 {
-        return false;
-    }
+/// Transfers `extract_swap_transfers` went through and the legs it matched
+#[derive(Debug, Clone)]
+pub struct SwapTransfersTrace {
+    pub signature: Signature,
+    pub input_token_account: Pubkey,
+    pub output_token_account: Pubkey,
+    /// Token transfers the swap instruction made, in order
+    pub transfers: Vec<TransferData>,
+    /// Index in `transfers`
+    pub pay_transfer: Option<usize>,
+    /// Index in `transfers`
+    pub receive_transfer: Option<usize>,
+}
 
-    if instruction.data[0] != 12 {
-        return false;
-    }
+tokio::task_local! {
+    /// Collects a `SwapTransfersTrace` for every `extract_swap_transfers` call
+    /// of the monitors run within its scope, see `TransactionsListener::decode`
+    pub static SWAP_TRANSFERS_TRACE: Arc<Mutex<Vec<SwapTransfersTrace>>>;
+}
 
-    for i in 0..4 {
-        if instruction.accounts[i] >= accounts.len() as u8 {
-            return false;
-        }
-    }
+pub fn is_transfer_instruction(instruction: &Instruction) -> bool {
+    instruction.program_id == programs::TOKEN
+        && instruction.accounts.len() >= 3
+        && instruction.data.len() >= 9
+        && instruction.data[0] == 3
+}
 
-    true
+pub fn is_transfer_check_instruction(instruction: &Instruction) -> bool {
+    (instruction.program_id == programs::TOKEN || instruction.program_id == programs::TOKEN_2022)
+        && instruction.accounts.len() >= 4
+        && instruction.data.len() >= 9
+        && instruction.data[0] == 12
 }
 
 pub fn extract_spl_token_info(
@@ -110,8 +118,7 @@ pub fn extract_spl_token_info(
 }
 
 pub fn process_transfer(
-    instruction: &CompiledInstruction,
-    accounts: &[Pubkey],
+    instruction: &Instruction,
     tokens_info: &HashMap<String, TokenInfo>,
 ) -> CarbonResult<TransferData> {
     let amount = u64::from_le_bytes(
@@ -120,28 +127,27 @@ pub fn process_transfer(
             .map_err(|_| Error::Custom("parse transfer amount".to_string()))?,
     );
 
-    let token_info = if let Some(token_info) =
-        tokens_info.get(&accounts[instruction.accounts[1] as usize].to_string())
-    {
-        token_info
-    } else {
-        &TokenInfo {
-            mint: SOLANA_PUBKEY.to_string(),
-            amount: UiTokenAmount {
-                ui_amount: Some(0.0),
-                decimals: 9,
-                amount: "0".to_string(),
-                ui_amount_string: "0".to_string(),
-            },
-        }
-    };
+    let token_info =
+        if let Some(token_info) = tokens_info.get(&instruction.accounts[1].pubkey.to_string()) {
+            token_info
+        } else {
+            &TokenInfo {
+                mint: SOLANA_PUBKEY.to_string(),
+                amount: UiTokenAmount {
+                    ui_amount: Some(0.0),
+                    decimals: 9,
+                    amount: "0".to_string(),
+                    ui_amount_string: "0".to_string(),
+                },
+            }
+        };
 
     let mut transfer_data = TransferData {
         info: TransferInfo {
             amount,
-            authority: accounts[instruction.accounts[2] as usize].to_string(),
-            destination: accounts[instruction.accounts[1] as usize].to_string(),
-            source: accounts[instruction.accounts[0] as usize].to_string(),
+            authority: instruction.accounts[2].pubkey.to_string(),
+            destination: instruction.accounts[1].pubkey.to_string(),
+            source: instruction.accounts[0].pubkey.to_string(),
         },
         mint: token_info.mint.clone(),
         decimals: token_info.amount.decimals,
@@ -155,8 +161,7 @@ pub fn process_transfer(
 }
 
 pub fn process_transfer_check(
-    instruction: &CompiledInstruction,
-    accounts: &[Pubkey],
+    instruction: &Instruction,
     tokens_info: &HashMap<String, TokenInfo>,
 ) -> CarbonResult<TransferData> {
     let amount = u64::from_le_bytes(
@@ -165,28 +170,27 @@ pub fn process_transfer_check(
             .map_err(|_| Error::Custom("parse transfer amount".to_string()))?,
     );
 
-    let token_info = if let Some(token_info) =
-        tokens_info.get(&accounts[instruction.accounts[2] as usize].to_string())
-    {
-        token_info
-    } else {
-        &TokenInfo {
-            mint: SOLANA_PUBKEY.to_string(),
-            amount: UiTokenAmount {
-                ui_amount: Some(0.0),
-                decimals: 9,
-                amount: "0".to_string(),
-                ui_amount_string: "0".to_string(),
-            },
-        }
-    };
+    let token_info =
+        if let Some(token_info) = tokens_info.get(&instruction.accounts[2].pubkey.to_string()) {
+            token_info
+        } else {
+            &TokenInfo {
+                mint: SOLANA_PUBKEY.to_string(),
+                amount: UiTokenAmount {
+                    ui_amount: Some(0.0),
+                    decimals: 9,
+                    amount: "0".to_string(),
+                    ui_amount_string: "0".to_string(),
+                },
+            }
+        };
 
     let mut transfer_data = TransferData {
         info: TransferInfo {
             amount,
-            authority: accounts[instruction.accounts[3] as usize].to_string(),
-            destination: accounts[instruction.accounts[2] as usize].to_string(),
-            source: accounts[instruction.accounts[0] as usize].to_string(),
+            authority: instruction.accounts[3].pubkey.to_string(),
+            destination: instruction.accounts[2].pubkey.to_string(),
+            source: instruction.accounts[0].pubkey.to_string(),
         },
         mint: token_info.mint.clone(),
         decimals: token_info.amount.decimals,
@@ -198,3 +202,155 @@ pub fn process_transfer_check(
 
     Ok(transfer_data)
 }
+
+/// Returns the static account keys followed by the writable and readonly
+/// addresses loaded from lookup tables, in the order used by compiled
+/// instruction indices.
+pub fn get_account_keys(transaction_metadata: &TransactionMetadata) -> Vec<Pubkey> {
+    let mut account_keys = transaction_metadata.message.static_account_keys().to_vec();
+
+    account_keys.extend(transaction_metadata.meta.loaded_addresses.writable.clone());
+
+    account_keys.extend(transaction_metadata.meta.loaded_addresses.readonly.clone());
+
+    account_keys
+}
+
+/// Mint of the token account, looked up in the token balances which are there
+/// whether the transaction succeeded or not
+pub fn get_token_account_mint(
+    transaction_metadata: &TransactionMetadata,
+    token_account: &Pubkey,
+) -> Option<Pubkey> {
+    let account_keys = get_account_keys(transaction_metadata);
+    let account_index = account_keys
+        .iter()
+        .position(|account_key| account_key == token_account)?;
+
+    let meta = &transaction_metadata.meta;
+    meta.pre_token_balances
+        .iter()
+        .chain(meta.post_token_balances.iter())
+        .flatten()
+        .find(|token_balance| token_balance.account_index as usize == account_index)
+        .and_then(|token_balance| Pubkey::from_str(&token_balance.mint).ok())
+}
+
+/// Finds the transfers the swap instruction made from `input_token_account`
+/// into the pool and from the pool to `output_token_account`, along with the
+/// pool vault balances after the swap. Only the swap's own inner instructions
+/// are looked at, so that the hops of a route paying from the same account
+/// each get their own transfers.
+pub fn extract_swap_transfers<'a>(
+    transaction_metadata: &TransactionMetadata,
+    nested_instructions: impl IntoIterator<Item = &'a NestedInstruction>,
+    input_token_account: &Pubkey,
+    output_token_account: &Pubkey,
+) -> CarbonResult<SwapTransfers> {
+    // Transfers name their accounts as strings
+    let (pay_source, receive_destination) = (
+        input_token_account.to_string(),
+        output_token_account.to_string(),
+    );
+
+    let post_token_balances = match transaction_metadata.meta.post_token_balances.clone() {
+        Some(post_token_balances) => post_token_balances,
+        None => {
+            return Err(Error::Custom("post token balances not found".to_string()));
+        }
+    };
+
+    let pre_token_balances = match transaction_metadata.meta.pre_token_balances.clone() {
+        Some(pre_token_balances) => pre_token_balances,
+        None => {
+            return Err(Error::Custom("pre token balances not found".to_string()));
+        }
+    };
+
+    let address_used = get_account_keys(transaction_metadata);
+
+    let spl_tokens_info = extract_spl_token_info(
+        post_token_balances.clone(),
+        pre_token_balances,
+        &address_used,
+    );
+
+    let mut transfer_data = Vec::new();
+    for nested_instruction in nested_instructions {
+        let instruction = &nested_instruction.instruction;
+        if is_transfer_instruction(instruction) {
+            transfer_data.push(process_transfer(instruction, &spl_tokens_info)?);
+        } else if is_transfer_check_instruction(instruction) {
+            transfer_data.push(process_transfer_check(instruction, &spl_tokens_info)?);
+        }
+    }
+
+    let mut pay_transfer = None;
+    let mut receive_transfer = None;
+
+    for (index, transfer) in transfer_data.iter().enumerate() {
+        // Transfer in
+        if transfer.info.source == pay_source {
+            pay_transfer = Some(index);
+        }
+        // Transfer out
+        else if transfer.info.destination == receive_destination {
+            receive_transfer = Some(index);
+        }
+    }
+
+    let _ = SWAP_TRANSFERS_TRACE.try_with(|trace| {
+        trace.lock().unwrap().push(SwapTransfersTrace {
+            signature: transaction_metadata.signature,
+            input_token_account: *input_token_account,
+            output_token_account: *output_token_account,
+            transfers: transfer_data.clone(),
+            pay_transfer,
+            receive_transfer,
+        })
+    });
+
+    if transfer_data.is_empty() {
+        return Err(Error::Custom("transfer data not found".to_string()));
+    }
+
+    let pay_transfer = if let Some(pay_transfer) = pay_transfer {
+        transfer_data[pay_transfer].clone()
+    } else {
+        return Err(Error::Custom("pay transfer not found".to_string()));
+    };
+
+    let receive_transfer = if let Some(receive_transfer) = receive_transfer {
+        transfer_data[receive_transfer].clone()
+    } else {
+        return Err(Error::Custom("receive transfer not found".to_string()));
+    };
+
+    let mut token_in_reserve = 0;
+    let mut token_out_reserve = 0;
+
+    for post_token_balance in post_token_balances {
+        let account = address_used[post_token_balance.account_index as usize].to_string();
+
+        if account == pay_transfer.info.destination {
+            token_in_reserve = post_token_balance
+                .ui_token_amount
+                .amount
+                .parse::<u64>()
+                .map_err(|error| Error::Custom(format!("parse token in reserve: {}", error)))?;
+        } else if account == receive_transfer.info.source {
+            token_out_reserve = post_token_balance
+                .ui_token_amount
+                .amount
+                .parse::<u64>()
+                .map_err(|error| Error::Custom(format!("parse token out reserve: {}", error)))?;
+        }
+    }
+
+    Ok(SwapTransfers {
+        pay_transfer,
+        receive_transfer,
+        token_in_reserve,
+        token_out_reserve,
+    })
+}
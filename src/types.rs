@@ -1,47 +1,546 @@
-use solana_sdk::{pubkey::Pubkey, signature::Signature};
-use strum::{Display, EnumIter};
+use std::hash::{Hash, Hasher};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+use carbon_core::transaction::TransactionMetadata;
+use solana_sdk::{
+    instruction::InstructionError, pubkey::Pubkey, signature::Signature,
+    transaction::TransactionError,
+};
+use strum::{Display, EnumDiscriminants, EnumIter};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+
+use crate::{constants::programs::*, fees::get_transaction_fees, position::InstructionPosition};
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Event {
+    pub schema_version: SchemaVersion,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
     pub signature: Signature,
     pub event_type: EventType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
     pub user: Pubkey,
-    pub timestamp: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
+    pub slot: u64,
+    /// On-chain block time in seconds, `None` when the source doesn't provide
+    /// it. gRPC sources only learn it from the block meta, sent once the block
+    /// is complete, so it's `None` below confirmed commitment unless
+    /// `ListenerConfig::block_time_wait` holds the transactions back for it
+    pub block_time: Option<i64>,
+    /// Index of the transaction within its block, `None` when the source
+    /// doesn't provide it
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::serde_helpers::option_as_string")
+    )]
+    pub transaction_index: Option<u64>,
+    /// Index of the top-level instruction the event comes from
+    pub instruction_index: u32,
+    /// Index within the top-level instruction's inner instructions, `None` when
+    /// the event comes from the top-level instruction itself
+    pub inner_instruction_index: Option<u32>,
+    /// Time the listener received the transaction, in milliseconds since the
+    /// unix epoch
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
+    pub received_at: u64,
+    pub transaction_fees: TransactionFees,
+    /// Watchlist entry of `user`, when it's watched
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub watchlist_entry: Option<WatchlistEntry>,
 }
 
+impl Event {
+    pub fn new(
+        transaction_metadata: &TransactionMetadata,
+        position: InstructionPosition,
+        event_type: EventType,
+        user: Pubkey,
+        received_at: u64,
+    ) -> Self {
+        Self {
+            schema_version: SchemaVersion,
+            signature: transaction_metadata.signature,
+            event_type,
+            user,
+            slot: transaction_metadata.slot,
+            block_time: transaction_metadata.block_time,
+            // Carbon's transaction metadata doesn't carry it, `EventSender`
+            // sets it
+            transaction_index: None,
+            instruction_index: position.index,
+            inner_instruction_index: position.inner_index,
+            received_at,
+            transaction_fees: get_transaction_fees(transaction_metadata),
+            watchlist_entry: None,
+        }
+    }
+}
+
+// The same event received from several sources differs only by `received_at`,
+// and by `watchlist_entry` if the watchlist changed in between, leave them out
+// so they compare equal
+impl PartialEq for Event {
+    fn eq(&self, other: &Self) -> bool {
+        self.signature == other.signature
+            && self.event_type == other.event_type
+            && self.user == other.user
+            && self.slot == other.slot
+            && self.block_time == other.block_time
+            && self.transaction_index == other.transaction_index
+            && self.instruction_index == other.instruction_index
+            && self.inner_instruction_index == other.inner_instruction_index
+            && self.transaction_fees == other.transaction_fees
+    }
+}
+
+impl Eq for Event {}
+
+impl Hash for Event {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.signature.hash(state);
+        self.event_type.hash(state);
+        self.user.hash(state);
+        self.slot.hash(state);
+        self.block_time.hash(state);
+        self.transaction_index.hash(state);
+        self.instruction_index.hash(state);
+        self.inner_instruction_index.hash(state);
+        self.transaction_fees.hash(state);
+    }
+}
+
+/// Wallet tracked by the listener
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct WatchlistEntry {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
+    pub wallet: Pubkey,
+    pub label: Option<String>,
+}
+
+/// Version of the serialized events, bumped on every breaking change to their
+/// format. Always written as the current version, reading another one fails.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SchemaVersion;
+
+impl SchemaVersion {
+    pub const CURRENT: u32 = 2;
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for SchemaVersion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(Self::CURRENT)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for SchemaVersion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let schema_version = u32::deserialize(deserializer)?;
+        if schema_version != Self::CURRENT {
+            return Err(D::Error::custom(format!(
+                "unsupported schema version {}, expected {}",
+                schema_version,
+                Self::CURRENT
+            )));
+        }
+
+        Ok(Self)
+    }
+}
+
+/// Every event of a transaction, in execution order
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TransactionEvents {
+    pub schema_version: SchemaVersion,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
+    pub signature: Signature,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
+    pub fee_payer: Pubkey,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
+    pub slot: u64,
+    /// Same as `Event::block_time`
+    pub block_time: Option<i64>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::serde_helpers::option_as_string")
+    )]
+    pub transaction_index: Option<u64>,
+    pub transaction_fees: TransactionFees,
+    pub events: Vec<Event>,
+}
+
+impl TransactionEvents {
+    pub fn new(transaction_metadata: &TransactionMetadata, events: Vec<Event>) -> Self {
+        Self {
+            schema_version: SchemaVersion,
+            signature: transaction_metadata.signature,
+            fee_payer: transaction_metadata.fee_payer,
+            slot: transaction_metadata.slot,
+            block_time: transaction_metadata.block_time,
+            transaction_index: events.first().and_then(|event| event.transaction_index),
+            transaction_fees: get_transaction_fees(transaction_metadata),
+            events,
+        }
+    }
+}
+
+/// Fees paid by the whole transaction the event comes from
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TransactionFees {
+    /// Base and priority fees, in lamports
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
+    pub total_fee: u64,
+    /// Compute unit price, in micro-lamports per compute unit, `None` when the
+    /// transaction doesn't set it
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::serde_helpers::option_as_string")
+    )]
+    pub priority_fee: Option<u64>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::serde_helpers::option_as_string")
+    )]
+    pub compute_units_consumed: Option<u64>,
+    /// Lamports sent to the Jito tip accounts
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::serde_helpers::option_as_string")
+    )]
+    pub jito_tip: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EnumDiscriminants)]
+#[strum_discriminants(name(EventKind), derive(Hash))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(tag = "type", rename_all = "snake_case")
+)]
 pub enum EventType {
-    Swap(Swap),
+    Swap(Box<Swap>),
+    /// Only emitted when the listener's `failed_swap_attempts` is set
+    FailedSwapAttempt(Box<FailedSwapAttempt>),
     PoolCreation {
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
         mint: Pubkey,
         platform: SwapPlatform,
     },
     AssociatedAccountCreation {
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
         mint: Pubkey,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
         account: Pubkey,
         idempotent: bool,
     },
 }
 
+impl EventType {
+    /// Mints the event is about, as far as the instruction tells
+    pub fn mints(&self) -> Vec<Pubkey> {
+        match self {
+            EventType::Swap(swap) => vec![swap.token_in_mint, swap.token_out_mint],
+            EventType::FailedSwapAttempt(failed_swap_attempt) => failed_swap_attempt
+                .token_in_mint
+                .into_iter()
+                .chain(failed_swap_attempt.token_out_mint)
+                .collect(),
+            EventType::PoolCreation { mint, .. } => vec![*mint],
+            EventType::AssociatedAccountCreation { mint, .. } => vec![*mint],
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Swap {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
     pub token_in_amount: u64,
     pub token_in_decimals: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
     pub token_in_mint: Pubkey,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
     pub token_out_amount: u64,
     pub token_out_decimals: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
     pub token_out_mint: Pubkey,
     pub platform: SwapPlatform,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
+    pub pool: Pubkey,
+    /// Vault of the pool's base token: the launched token on Pump.fun, coin on
+    /// Raydium AMM v4, token 0 on Raydium CPMM and CLMM, token X on Meteora DLMM
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
+    pub base_vault: Pubkey,
+    /// Vault of the pool's quote token. Pump.fun keeps SOL on the bonding curve
+    /// itself, so it's the same account as `pool`
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
+    pub quote_vault: Pubkey,
+    pub pool_state: PoolState,
+    /// `None` when the pool's fee rates aren't known
+    pub fees: Option<SwapFees>,
+}
+
+/// Swap of a transaction that failed, with what the instruction asked for
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FailedSwapAttempt {
+    pub platform: SwapPlatform,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
+    pub pool: Pubkey,
+    /// `None` when the instruction doesn't name the mint and the token account
+    /// doesn't exist yet
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::serde_helpers::option_as_string")
+    )]
+    pub token_in_mint: Option<Pubkey>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::serde_helpers::option_as_string")
+    )]
+    pub token_out_mint: Option<Pubkey>,
+    pub amount: SwapAmount,
+    /// Index of the top-level instruction the transaction failed in, not
+    /// necessarily the swap's
+    pub failed_instruction_index: Option<u32>,
+    /// Custom error code of the failing program, `None` for runtime errors
+    pub error_code: Option<u32>,
+}
+
+impl FailedSwapAttempt {
+    pub fn new(
+        transaction_metadata: &TransactionMetadata,
+        platform: SwapPlatform,
+        pool: Pubkey,
+        token_in_mint: Option<Pubkey>,
+        token_out_mint: Option<Pubkey>,
+        amount: SwapAmount,
+    ) -> Self {
+        let (failed_instruction_index, error_code) = match &transaction_metadata.meta.status {
+            Err(TransactionError::InstructionError(index, instruction_error)) => {
+                let error_code = match instruction_error {
+                    InstructionError::Custom(error_code) => Some(*error_code),
+                    _ => None,
+                };
+
+                (Some(*index as u32), error_code)
+            }
+            _ => (None, None),
+        };
+
+        Self {
+            platform,
+            pool,
+            token_in_mint,
+            token_out_mint,
+            amount,
+            failed_instruction_index,
+            error_code,
+        }
+    }
+}
+
+/// Amounts a swap instruction asks for, including decimals
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(tag = "type", rename_all = "snake_case")
+)]
+pub enum SwapAmount {
+    ExactIn {
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
+        amount_in: u64,
+        /// `None` when the instruction bounds the price impact instead
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::serde_helpers::option_as_string")
+        )]
+        minimum_amount_out: Option<u64>,
+    },
+    ExactOut {
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
+        amount_out: u64,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
+        maximum_amount_in: u64,
+    },
+}
+
+/// Fees charged on the swap, `None` when the platform doesn't charge it or it
+/// can't be computed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SwapFees {
+    /// Kept by the pool's liquidity providers
+    pub lp_fee: Option<Fee>,
+    pub protocol_fee: Option<Fee>,
+    /// Paid to the token's creator
+    pub creator_fee: Option<Fee>,
+    pub fund_fee: Option<Fee>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Fee {
     /// Including decimals
-    pub token_in_reserve: u64,
-    /// Including decimals
-    pub token_out_reserve: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
+    pub amount: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
+    pub mint: Pubkey,
+}
+
+/// State of the pool right after the swap
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(tag = "type", rename_all = "snake_case")
+)]
+pub enum PoolState {
+    ConstantProduct {
+        /// Including decimals
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
+        token_in_reserve: u64,
+        /// Including decimals
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
+        token_out_reserve: u64,
+    },
+    ConcentratedLiquidity {
+        /// Square root of the token 1 / token 0 price, as a Q64.64 number
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
+        sqrt_price_x64: u128,
+        tick: i32,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::as_string"))]
+        liquidity: u128,
+    },
+    LiquidityBins {
+        active_bin_id: i32,
+        /// Token Y per token X in the active bin, as a Q64.64 number. `None`
+        /// until the pair's creation has been seen, since it depends on the
+        /// pair's bin step
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::serde_helpers::option_as_string")
+        )]
+        bin_price_x64: Option<u128>,
+    },
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq, EnumIter, Display)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum SwapPlatform {
     PumpFun,
     Raydium,
     RaydiumCpmm,
     RaydiumClmm,
+    MeteoraDlmm,
+}
+
+impl SwapPlatform {
+    pub fn program_id(&self) -> Pubkey {
+        match self {
+            SwapPlatform::PumpFun => PUMPFUN,
+            SwapPlatform::Raydium => RAYDIUM,
+            SwapPlatform::RaydiumCpmm => RAYDIUM_CPMM,
+            SwapPlatform::RaydiumClmm => RAYDIUM_CLMM,
+            SwapPlatform::MeteoraDlmm => METEORA_DLMM,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::test_utils::{get_event, get_failed_swap_attempt, get_pool_creation, get_swap};
+
+    #[test]
+    fn test_events_match_schema() {
+        let schema: Value =
+            serde_json::from_str(include_str!("../schema/event.schema.json")).unwrap();
+        let validator = jsonschema::validator_for(&schema).unwrap();
+
+        let fees = SwapFees {
+            lp_fee: Some(Fee {
+                amount: 2_100_000,
+                mint: Pubkey::new_unique(),
+            }),
+            protocol_fee: Some(Fee {
+                amount: 300_000,
+                mint: Pubkey::new_unique(),
+            }),
+            creator_fee: None,
+            fund_fee: None,
+        };
+        let event_types = [
+            get_swap(
+                SwapPlatform::RaydiumCpmm,
+                PoolState::ConstantProduct {
+                    token_in_reserve: 81_000_000_000,
+                    token_out_reserve: 2_765_376_532_388,
+                },
+                Some(fees.clone()),
+            ),
+            get_swap(
+                SwapPlatform::RaydiumClmm,
+                PoolState::ConcentratedLiquidity {
+                    sqrt_price_x64: u128::MAX,
+                    tick: -20_395,
+                    liquidity: 98_765_432_100,
+                },
+                None,
+            ),
+            get_swap(
+                SwapPlatform::MeteoraDlmm,
+                PoolState::LiquidityBins {
+                    active_bin_id: -1_205,
+                    bin_price_x64: None,
+                },
+                Some(fees),
+            ),
+            get_failed_swap_attempt(SwapAmount::ExactIn {
+                amount_in: 1_000_000_000,
+                minimum_amount_out: None,
+            }),
+            get_failed_swap_attempt(SwapAmount::ExactOut {
+                amount_out: 4_567_890_123,
+                maximum_amount_in: 1_100_000_000,
+            }),
+            get_pool_creation(SwapPlatform::PumpFun),
+            EventType::AssociatedAccountCreation {
+                mint: Pubkey::new_unique(),
+                account: Pubkey::new_unique(),
+                idempotent: true,
+            },
+        ];
+
+        for event_type in event_types {
+            let mut event = get_event(event_type);
+            let json = serde_json::to_value(&event).unwrap();
+            let errors = validator
+                .iter_errors(&json)
+                .map(|error| error.to_string())
+                .collect::<Vec<_>>();
+            assert!(errors.is_empty(), "{}: {:?}", json, errors);
+
+            // Nothing optional left out of the schema either
+            event.block_time = None;
+            event.transaction_index = None;
+            event.inner_instruction_index = None;
+            event.watchlist_entry = None;
+            let json = serde_json::to_value(&event).unwrap();
+            assert!(validator.is_valid(&json), "{}", json);
+            assert_eq!(serde_json::from_value::<Event>(json).unwrap(), event);
+        }
+    }
 }
@@ -0,0 +1,194 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::types::WatchlistEntry;
+
+/// Wallets to track, shared by the listener's config and the monitors so that
+/// changes apply to the running pipeline
+#[derive(Debug, Clone, Default)]
+pub struct Watchlist {
+    entries: Arc<RwLock<HashMap<Pubkey, WatchlistEntry>>>,
+}
+
+impl Watchlist {
+    /// Replaces the entry of the same wallet, if any
+    pub fn add(&self, entry: WatchlistEntry) -> Option<WatchlistEntry> {
+        self.entries.write().unwrap().insert(entry.wallet, entry)
+    }
+
+    pub fn remove(&self, wallet: &Pubkey) -> Option<WatchlistEntry> {
+        self.entries.write().unwrap().remove(wallet)
+    }
+
+    pub fn get(&self, wallet: &Pubkey) -> Option<WatchlistEntry> {
+        self.entries.read().unwrap().get(wallet).cloned()
+    }
+
+    pub fn list(&self) -> Vec<WatchlistEntry> {
+        self.entries.read().unwrap().values().cloned().collect()
+    }
+
+    pub fn wallets(&self) -> Vec<Pubkey> {
+        self.entries.read().unwrap().keys().copied().collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.read().unwrap().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::{
+        TransactionsListener, decode::parse_transaction, mock_rpc::load_fixture,
+        tracked_mints::TrackedMints, transaction_events::EventSender,
+        transaction_indexes::TransactionIndexes,
+    };
+
+    /// User of the `pumpfun_buy` fixture
+    const USER: Pubkey = solana_sdk::pubkey!("JC3FaLRvSF92bf3j27VzUyb82cqZxxPnjik5to9qqtC3");
+
+    fn get_entry(wallet: Pubkey, label: &str) -> WatchlistEntry {
+        WatchlistEntry {
+            wallet,
+            label: Some(label.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_adds_removes_and_lists_entries() {
+        let watchlist = Watchlist::default();
+        assert!(watchlist.is_empty());
+
+        let (wallet_a, wallet_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        assert_eq!(watchlist.add(get_entry(wallet_a, "a")), None);
+        assert_eq!(watchlist.add(get_entry(wallet_b, "b")), None);
+        // Replaced, the previous entry given back
+        assert_eq!(
+            watchlist.add(get_entry(wallet_a, "a2")),
+            Some(get_entry(wallet_a, "a"))
+        );
+
+        let mut entries = watchlist.list();
+        entries.sort_by_key(|entry| entry.wallet);
+        let mut expected_entries = vec![get_entry(wallet_a, "a2"), get_entry(wallet_b, "b")];
+        expected_entries.sort_by_key(|entry| entry.wallet);
+        assert_eq!(entries, expected_entries);
+        let mut wallets = watchlist.wallets();
+        wallets.sort();
+        let mut expected_wallets = vec![wallet_a, wallet_b];
+        expected_wallets.sort();
+        assert_eq!(wallets, expected_wallets);
+        assert_eq!(watchlist.get(&wallet_b), Some(get_entry(wallet_b, "b")));
+
+        // Clones share the entries
+        let clone = watchlist.clone();
+        assert_eq!(clone.remove(&wallet_a), Some(get_entry(wallet_a, "a2")));
+        assert_eq!(watchlist.remove(&wallet_a), None);
+        assert_eq!(watchlist.get(&wallet_a), None);
+        assert_eq!(clone.remove(&wallet_b), Some(get_entry(wallet_b, "b")));
+        assert!(watchlist.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_updates_transaction_filters() {
+        let (transactions_listener, _receiver) =
+            TransactionsListener::new(16, 255, HashMap::new()).unwrap();
+        let mut transaction_filters = transactions_listener.transaction_filters.subscribe();
+        let wallet = Pubkey::new_unique();
+
+        assert_eq!(
+            transactions_listener.add_watched_wallet(wallet, Some("a".to_string())),
+            None
+        );
+        assert!(transaction_filters.has_changed().unwrap());
+        assert!(
+            transaction_filters
+                .borrow_and_update()
+                .values()
+                .all(|filter| filter.account_include == vec![wallet.to_string()])
+        );
+        assert_eq!(
+            transactions_listener.watched_wallets(),
+            vec![get_entry(wallet, "a")]
+        );
+
+        // A new label only, the filters stay as they are
+        assert_eq!(
+            transactions_listener.add_watched_wallet(wallet, Some("b".to_string())),
+            Some(get_entry(wallet, "a"))
+        );
+        assert!(!transaction_filters.has_changed().unwrap());
+        assert_eq!(
+            transactions_listener.watched_wallets(),
+            vec![get_entry(wallet, "b")]
+        );
+
+        assert_eq!(
+            transactions_listener.remove_watched_wallet(&wallet),
+            Some(get_entry(wallet, "b"))
+        );
+        assert!(transaction_filters.has_changed().unwrap());
+        // Back to every transaction of the programs
+        assert!(
+            transaction_filters
+                .borrow_and_update()
+                .values()
+                .all(|filter| filter.account_required.is_empty())
+        );
+        assert!(transactions_listener.watched_wallets().is_empty());
+
+        assert_eq!(transactions_listener.remove_watched_wallet(&wallet), None);
+        assert!(!transaction_filters.has_changed().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_tags_events_of_watched_users() {
+        let (transactions_listener, _receiver) =
+            TransactionsListener::new(16, 255, HashMap::new()).unwrap();
+        let update = parse_transaction(&load_fixture("pumpfun_buy").to_string()).unwrap();
+        let event = transactions_listener
+            .decode(update)
+            .await
+            .unwrap()
+            .events
+            .into_iter()
+            .next()
+            .unwrap();
+        assert_eq!(event.user, USER);
+
+        // The watchlist the pipeline holds, changed while it runs
+        let (sender, mut receiver) = mpsc::channel(16);
+        let event_sender = EventSender::Event {
+            sender,
+            confirmations: None,
+            watchlist: transactions_listener.config.wallets.clone(),
+            tracked_mints: TrackedMints::default(),
+            transaction_indexes: TransactionIndexes::default(),
+            replay_clock: None,
+        };
+
+        event_sender.send(event.clone()).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap().watchlist_entry, None);
+
+        transactions_listener.add_watched_wallet(USER, Some("whale".to_string()));
+        event_sender.send(event.clone()).await.unwrap();
+        assert_eq!(
+            receiver.recv().await.unwrap().watchlist_entry,
+            Some(get_entry(USER, "whale"))
+        );
+
+        // Another user's events stay untagged
+        transactions_listener.add_watched_wallet(Pubkey::new_unique(), None);
+        transactions_listener.remove_watched_wallet(&USER);
+        event_sender.send(event).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap().watchlist_entry, None);
+    }
+}